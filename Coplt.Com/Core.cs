@@ -42,5 +42,5 @@ public partial struct IWeak
 {
     public readonly partial uint AddRefWeak();
     public readonly partial uint ReleaseWeak();
-    public readonly partial bool TryUpgrade();
+    public readonly partial B1 TryUpgrade();
 }
@@ -25,6 +25,10 @@ public record RustOverride
 public record RustOutput : AOutput
 {
     public Dictionary<string, RustOverride> Override { get; set; } = new();
+    /// <summary>
+    /// Also emit proxies and stubs for <c>cocom::remote</c>, needs cocom's <c>std</c> feature
+    /// </summary>
+    public bool Remote { get; set; } = false;
 
     internal async ValueTask Output(SymbolDb db)
     {
@@ -35,7 +39,7 @@ internal async ValueTask Output(SymbolDb db)
         sb.AppendLine("#![allow(non_upper_case_globals)]");
 
         sb.AppendLine();
-        sb.AppendLine("use cocom::{Guid, HResult, HResultE, Interface, IUnknown, IWeak, ComPtr};");
+        sb.AppendLine("use cocom::{Guid, HResult, HResultE, ComResult, IntoHResult, Interface, IUnknown, IWeak, ComPtr, Out, OutComPtr, NSpan, NRoSpan, Str8, Str16, StrAny, types::{Char8, Char16}};");
 
         GenInterfaces(db, sb);
         GenTypes(db, sb);
@@ -111,27 +115,67 @@ private static string ToRustName(TypeSymbol symbol, bool is_root = true, bool su
             case TypeKind.Double:
                 return "f64";
             case TypeKind.Char8:
-                return "char";
+                return "Char8";
             case TypeKind.Char16:
-                return "u16";
+                return "Char16";
             case TypeKind.Guid:
                 return "Guid";
             case TypeKind.HResult:
                 return "HResult";
             case TypeKind.NSpan:
-                break;
+                return $"NSpan<{ToRustName(SpanElement(symbol), false, super)}>";
             case TypeKind.NRoSpan:
-                break;
+                return $"NRoSpan<{ToRustName(SpanElement(symbol), false, super)}>";
             case TypeKind.Str8:
-                break;
+                return "Str8";
             case TypeKind.Str16:
-                break;
+                return "Str16";
             case TypeKind.StrAny:
-                break;
+                return "StrAny";
             default:
                 throw new ArgumentOutOfRangeException();
         }
-        return "ERROR";
+    }
+
+    /// return type of a function with a body, nothing for `()`
+    private static string ToRustRet(TypeSymbol symbol) =>
+        symbol.Kind == TypeKind.Void ? "" : $" -> {ToRustName(symbol)}";
+
+    private static readonly HashSet<string> RustKeywords =
+    [
+        "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false", "fn", "for",
+        "gen", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "static",
+        "struct", "trait", "true", "type", "unsafe", "use", "where", "while", "abstract", "become", "box", "do",
+        "final", "macro", "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+    ];
+
+    /// parameter and field names that are rust keywords, `self` and friends cannot be raw
+    private static string ToRustIdent(string name) => name switch
+    {
+        "self" or "Self" or "super" or "crate" or "_" => $"{name}_",
+        _ when RustKeywords.Contains(name) => $"r#{name}",
+        _ => name,
+    };
+
+    private static TypeSymbol SpanElement(TypeSymbol symbol) =>
+        symbol.TargetOrReturn
+        ?? (symbol.GenericsOrParams.IsDefaultOrEmpty ? null : symbol.GenericsOrParams[0])
+        ?? throw new ArgumentException($"Span without element type: {symbol}");
+
+    /// out params of `HResult` methods reach implementations checked, the thunk answers null with `Pointer`
+    private static bool IsCheckedOut(in InterfaceMethod method, in InterfaceMethodParam param) =>
+        method.ReturnType.Kind == TypeKind.HResult
+        && (param.Flags & ParamFlags.Out) != 0
+        && param.Type.Kind is TypeKind.Ptr or TypeKind.Ref
+        && (param.Type.Flags & TypeFlags.Const) == 0;
+
+    /// `OutComPtr` for a pointer to an interface pointer, `Out` for anything else
+    private static (string Wrapper, string Target) ToRustOut(TypeSymbol symbol, bool super = false)
+    {
+        var target = symbol.TargetOrReturn!;
+        if (target.Kind is TypeKind.Ptr && (target.Flags & TypeFlags.Const) == 0 && target.TargetOrReturn!.Kind is TypeKind.Interface)
+            return ("OutComPtr", ToRustName(target.TargetOrReturn!, false, super));
+        return ("Out", ToRustName(target, false, super));
     }
 
     internal void GenTypes(SymbolDb db, StringBuilder root_sb)
@@ -294,7 +338,7 @@ internal void GenTypes(SymbolDb db, StringBuilder root_sb)
                 sb.AppendLine($" {{");
                 foreach (var field in a.Fields)
                 {
-                    sb.AppendLine($"    pub {field.Name}: {ToRustName(field.Type)},");
+                    sb.AppendLine($"    pub {ToRustIdent(field.Name)}: {ToRustName(field.Type)},");
                 }
                 if (a.TypeParams.Count > 0 && !field_has_type_param)
                 {
@@ -359,7 +403,7 @@ string GenImplGeneric()
                             sb.Append($"        ");
                             if (first) first = false;
                             else sb.Append(" && ");
-                            sb.Append($"self.{field.Name} == other.{field.Name}");
+                            sb.Append($"self.{ToRustIdent(field.Name)} == other.{ToRustIdent(field.Name)}");
                             sb.AppendLine();
                         }
                         sb.AppendLine($"    }}");
@@ -370,7 +414,7 @@ string GenImplGeneric()
                         sb.AppendLine($"    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {{");
                         foreach (var field in a.Fields)
                         {
-                            sb.AppendLine($"        match self.{field.Name}.partial_cmp(&other.{field.Name})? {{ core::cmp::Ordering::Equal => (), ord => return Some(ord) }}");
+                            sb.AppendLine($"        match self.{ToRustIdent(field.Name)}.partial_cmp(&other.{ToRustIdent(field.Name)})? {{ core::cmp::Ordering::Equal => (), ord => return Some(ord) }}");
                         }
                         sb.AppendLine($"        Some(core::cmp::Ordering::Equal)");
                         sb.AppendLine($"    }}");
@@ -424,7 +468,7 @@ internal void GenInterfaces(SymbolDb db, StringBuilder root_sb)
                     {
                         sb.Append(", ");
                         var o = (param.Flags & ParamFlags.Out) != 0 ? "/* out */ " : "";
-                        sb.Append($"{o}{param.Name}: {ToRustName(param.Type)}");
+                        sb.Append($"{o}{ToRustIdent(param.Name)}: {ToRustName(param.Type)}");
                     }
                     sb.AppendLine($") -> {ToRustName(method.ReturnType)};");
                 }
@@ -473,7 +517,7 @@ internal void GenInterfacesDetails(SymbolDb db, StringBuilder root_sb)
                     {
                         sb.Append(", ");
                         var o = (param.Flags & ParamFlags.Out) != 0 ? "/* out */ " : "";
-                        sb.Append($"{o}{param.Name}: {ToRustName(param.Type)}");
+                        sb.Append($"{o}{ToRustIdent(param.Name)}: {ToRustName(param.Type)}");
                     }
                     sb.AppendLine($") -> {ToRustName(method.ReturnType)},");
                 }
@@ -499,18 +543,42 @@ internal void GenInterfacesDetails(SymbolDb db, StringBuilder root_sb)
                     {
                         sb.Append(", ");
                         var o = (param.Flags & ParamFlags.Out) != 0 ? "/* out */ " : "";
-                        sb.Append($"{o}{param.Name}: {ToRustName(param.Type)}");
+                        sb.Append($"{o}{ToRustIdent(param.Name)}: {ToRustName(param.Type)}");
+                    }
+                    sb.AppendLine($"){ToRustRet(method.ReturnType)} {{");
+                    sb.AppendLine($"            unsafe {{");
+                    var borrow = (method.Flags & MethodFlags.Const) != 0 ? "Borrow" : "BorrowMut";
+                    if (method.ReturnType.Kind == TypeKind.HResult)
+                    {
+                        sb.AppendLine($"                let Some(_guard) = O::{borrow}(this as _) else {{");
+                        sb.AppendLine($"                    return HResultE::IllegalMethodCall.into();");
+                        sb.AppendLine($"                }};");
+                    }
+                    else
+                    {
+                        sb.AppendLine($"                let Some(_guard) = O::{borrow}(this as _) else {{");
+                        sb.AppendLine($"                    cocom::object::reentrant_call(\"{name}::{method.Name}\");");
+                        sb.AppendLine($"                }};");
                     }
-                    sb.AppendLine($") -> {ToRustName(method.ReturnType)} {{");
-                    sb.Append($"            unsafe {{ (*O::GetObject(this as _)).{method.Name}(");
+                    foreach (var param in method.Params)
+                    {
+                        if (!IsCheckedOut(method, param)) continue;
+                        var (wrapper, _) = ToRustOut(param.Type);
+                        sb.AppendLine($"                let Some({ToRustIdent(param.Name)}) = {wrapper}::new({ToRustIdent(param.Name)}) else {{");
+                        sb.AppendLine($"                    return HResultE::Pointer.into();");
+                        sb.AppendLine($"                }};");
+                    }
+                    sb.Append($"                (*O::GetObject(this as _)).{method.Name}(");
                     var first = true;
                     foreach (var param in method.Params)
                     {
                         if (first) first = false;
                         else sb.Append(", ");
-                        sb.Append($"{param.Name}");
+                        sb.Append($"{ToRustIdent(param.Name)}");
                     }
-                    sb.AppendLine($") }}");
+                    // `ComResult<HResult>` implementations leave their error info for the caller
+                    sb.AppendLine(method.ReturnType.Kind == TypeKind.HResult ? ").into_hresult()" : ")");
+                    sb.AppendLine($"            }}");
                     sb.AppendLine($"        }}");
                 }
                 sb.AppendLine($"    }}");
@@ -520,6 +588,7 @@ internal void GenInterfacesDetails(SymbolDb db, StringBuilder root_sb)
                 sb.AppendLine($"        T::Interface: details::QuIn<T::Interface, T, O>,");
                 sb.AppendLine($"    {{");
                 sb.AppendLine($"        const VTBL: <{name} as Interface>::VitualTable = VT::<T, {name}, O>::VTBL;");
+                sb.AppendLine($"        const VTBL_REF: &'static <{name} as Interface>::VitualTable = &<Self as Vtbl<O>>::VTBL;");
                 sb.AppendLine();
                 sb.AppendLine($"        fn vtbl() -> &'static Self::VitualTable {{");
                 sb.AppendLine($"            &<Self as Vtbl<O>>::VTBL");
@@ -544,6 +613,8 @@ internal void GenInterfacesDetails(SymbolDb db, StringBuilder root_sb)
                 sb.AppendLine($"            }}");
                 sb.AppendLine($"        }}");
                 sb.AppendLine($"    }}");
+                // weak references do not cross processes
+                if (Remote && !is_weak) GenRemote(a, sb);
                 return sb.ToString();
             }).ToList();
         root_sb.AppendJoin("", interfaces);
@@ -553,12 +624,62 @@ internal void GenInterfacesDetails(SymbolDb db, StringBuilder root_sb)
         root_sb.AppendLine("}");
     }
 
+    internal void GenRemote(InterfaceDeclareSymbol a, StringBuilder sb)
+    {
+        var name = a.Name;
+        var parent = a.Parent?.Name ?? "IUnknown";
+        for (var i = 0; i < a.Methods.Count; i++)
+        {
+            var method = a.Methods[i];
+            sb.AppendLine();
+            sb.Append($"    unsafe extern \"C\" fn p_{name}_{method.Name}(this: *const {name}");
+            foreach (var param in method.Params)
+            {
+                sb.Append(", ");
+                var o = (param.Flags & ParamFlags.Out) != 0 ? "/* out */ " : "";
+                sb.Append($"{o}{ToRustIdent(param.Name)}: {ToRustName(param.Type)}");
+            }
+            sb.AppendLine($"){ToRustRet(method.ReturnType)} {{");
+            sb.Append($"        unsafe {{ cocom::remote::proxy_call(this as _, &{name}::GUID, {i}, (");
+            foreach (var param in method.Params)
+            {
+                sb.Append($"{ToRustIdent(param.Name)}, ");
+            }
+            sb.AppendLine($")) }}");
+            sb.AppendLine($"    }}");
+        }
+        sb.AppendLine();
+        sb.AppendLine($"    unsafe impl cocom::remote::RemoteInterface for {name} {{");
+        sb.AppendLine($"        const PROXY_VTBL: VitualTable_{name} = VitualTable_{name} {{");
+        sb.AppendLine($"            b: <{parent} as cocom::remote::RemoteInterface>::PROXY_VTBL,");
+        foreach (var method in a.Methods)
+        {
+            sb.AppendLine($"            f_{method.Name}: p_{name}_{method.Name},");
+        }
+        sb.AppendLine($"        }};");
+        sb.AppendLine($"        const PROXY_VTBL_REF: &'static VitualTable_{name} = &Self::PROXY_VTBL;");
+        sb.AppendLine();
+        sb.AppendLine($"        fn stub(this: &Self, method: u32, call: &mut cocom::remote::StubCall) -> cocom::ComResult<()> {{");
+        sb.AppendLine($"            match method {{");
+        for (var i = 0; i < a.Methods.Count; i++)
+        {
+            var method = a.Methods[i];
+            var args = string.Join("", method.Params.Select(p => $"{ToRustIdent(p.Name)}, "));
+            var call = string.Join(", ", method.Params.Select(p => ToRustIdent(p.Name)));
+            sb.AppendLine($"                {i} => call.invoke(|({args})| this.{method.Name}({call})),");
+        }
+        sb.AppendLine($"                _ => Err(HResultE::NotImpl.into()),");
+        sb.AppendLine($"            }}");
+        sb.AppendLine($"        }}");
+        sb.AppendLine($"    }}");
+    }
+
     internal void GenInterfacesImpls(SymbolDb db, StringBuilder root_sb)
     {
         root_sb.AppendLine();
         root_sb.AppendLine("pub mod impls {");
         root_sb.AppendLine("    pub use cocom::impls::*;");
-        root_sb.AppendLine("    use cocom::{Guid, HResult};");
+        root_sb.AppendLine("    use cocom::{ComResult, Guid, HResult, Out, OutComPtr};");
 
         #region Interfaces
 
@@ -581,10 +702,17 @@ internal void GenInterfacesImpls(SymbolDb db, StringBuilder root_sb)
                     foreach (var param in method.Params)
                     {
                         sb.Append(", ");
+                        if (IsCheckedOut(method, param))
+                        {
+                            var (wrapper, target) = ToRustOut(param.Type, super: true);
+                            sb.Append($"{ToRustIdent(param.Name)}: {wrapper}<{target}>");
+                            continue;
+                        }
                         var o = (param.Flags & ParamFlags.Out) != 0 ? "/* out */ " : "";
-                        sb.Append($"{o}{param.Name}: {ToRustName(param.Type, super: true)}");
+                        sb.Append($"{o}{ToRustIdent(param.Name)}: {ToRustName(param.Type, super: true)}");
                     }
-                    sb.AppendLine($") -> {ToRustName(method.ReturnType, super: true)};");
+                    var ret = method.ReturnType.Kind == TypeKind.HResult ? "ComResult<HResult>" : ToRustName(method.ReturnType, super: true);
+                    sb.AppendLine($") -> {ret};");
                 }
                 sb.AppendLine($"    }}");
                 return sb.ToString();
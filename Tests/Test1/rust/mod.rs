@@ -0,0 +1,370 @@
+#![allow(unused)]
+#![allow(non_snake_case)]
+#![allow(non_camel_case_types)]
+#![allow(non_upper_case_globals)]
+
+use cocom::{Guid, HResult, HResultE, ComResult, IntoHResult, Interface, IUnknown, IWeak, ComPtr, Out, OutComPtr, NSpan, NRoSpan, Str8, Str16, StrAny, types::{Char8, Char16}};
+
+#[cocom::interface("c523bd17-e326-446c-8aab-c4e40774531a")]
+pub trait ITest1 : IUnknown {
+    fn Add(&self, a: u32, b: u32) -> u32;
+}
+
+#[cocom::interface("e6ea2c14-564f-47f8-9a62-7a55446c1438")]
+pub trait ITest2 : ITest1 + IUnknown {
+    fn Sub(&self, a: u32, b: u32) -> u32;
+    fn get_Foo(&self) -> u32;
+    fn set_Foo(&self, value: u32) -> ();
+    fn get_Foo2(&self) -> u32;
+    fn set_Foo3(&self, value: u32) -> ();
+    fn Some(&mut self) -> ();
+}
+
+#[cocom::interface("e785d2ba-cc37-48c6-b2fb-f253a21d0431")]
+pub trait ITest3 : ITest2 + ITest1 + IUnknown {
+    fn Some1(&mut self, a: Struct1, b: Enum1, c: Enum2) -> *mut Struct2<i32>;
+    fn FnPtr(&mut self, r#fn: unsafe extern "C" fn(i32, i32) -> i32) -> ();
+}
+
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Enum1(pub i32);
+
+impl Enum1 {
+    pub const A: Self = Self(0);
+    pub const B: Self = Self(1);
+    pub const C: Self = Self(2);
+}
+
+impl core::fmt::Display for Enum1 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            Self::A => f.write_str("A"),
+            Self::B => f.write_str("B"),
+            Self::C => f.write_str("C"),
+            Self(v) => f.write_fmt(format_args!("{v}")),
+        }
+    }
+}
+
+bitflags::bitflags! {
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+    pub struct Enum2 : i32 {
+        const None = 0;
+        const A = 1;
+        const B = 2;
+        const C = 4;
+        const _ = !0;
+    }
+}
+
+impl Enum2 {
+    #[inline(always)]
+    pub const fn has_flags(self, value: Enum2) -> bool {
+        (self.bits() & value.bits()) == value.bits()
+    }
+    #[inline(always)]
+    pub const fn has_any_flags(self, value: Enum2) -> bool {
+        (self.bits() & value.bits()) != 0
+    }
+    #[inline(always)]
+    pub const fn has_flags_only(self, value: Enum2) -> bool {
+        (self.bits() & !(value.bits())) == 0
+    }
+}
+
+impl From<i32> for Enum2 {
+    fn from(value: i32) -> Self {
+        Self::from_bits_retain(value)
+    }
+}
+
+impl From<Enum2> for i32 {
+    fn from(value: Enum2) -> Self {
+        value.bits()
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union Struct1 {
+    pub a: i32,
+}
+
+impl core::fmt::Debug for Struct1 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Struct1")
+            .finish_non_exhaustive()
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Struct2<T0 /* T */> {
+    pub a: T0,
+}
+
+pub mod details {
+    pub use cocom::details::*;
+    use super::*;
+
+    struct VT<T, V, O>(core::marker::PhantomData<(T, V, O)>);
+
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct VitualTable_ITest1 {
+        b: <IUnknown as Interface>::VitualTable,
+
+        pub f_Add: unsafe extern "C" fn(this: *const ITest1, a: u32, b: u32) -> u32,
+    }
+
+    impl<T: impls::ITest1 + impls::Object, O: impls::ObjectBox<Object = T>> VT<T, ITest1, O>
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        pub const VTBL: VitualTable_ITest1 = VitualTable_ITest1 {
+            b: <IUnknown as Vtbl<O>>::VTBL,
+            f_Add: Self::f_Add,
+        };
+
+        unsafe extern "C" fn f_Add(this: *const ITest1, a: u32, b: u32) -> u32 {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    cocom::object::reentrant_call("ITest1::Add");
+                };
+                (*O::GetObject(this as _)).Add(a, b)
+            }
+        }
+    }
+
+    impl<T: impls::ITest1 + impls::Object, O: impls::ObjectBox<Object = T>> Vtbl<O> for ITest1
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        const VTBL: <ITest1 as Interface>::VitualTable = VT::<T, ITest1, O>::VTBL;
+        const VTBL_REF: &'static <ITest1 as Interface>::VitualTable = &<Self as Vtbl<O>>::VTBL;
+
+        fn vtbl() -> &'static Self::VitualTable {
+            &<Self as Vtbl<O>>::VTBL
+        }
+    }
+
+    impl<T: impls::ITest1 + impls::Object, O: impls::ObjectBox<Object = T>> QuIn<ITest1, T, O> for ITest1 {
+        #[inline(always)]
+        unsafe fn QueryInterface(
+            this: *mut T,
+            guid: Guid,
+            out: *mut *mut core::ffi::c_void,
+        ) -> HResult {
+            unsafe {
+                static GUID: Guid = ITest1::GUID;
+                if guid == GUID {
+                    *out = this as _;
+                    O::AddRef(this as _);
+                    return HResultE::Ok.into();
+                }
+                <IUnknown as QuIn<IUnknown, T, O>>::QueryInterface(this, guid, out)
+            }
+        }
+    }
+
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct VitualTable_ITest2 {
+        b: <ITest1 as Interface>::VitualTable,
+
+        pub f_Sub: unsafe extern "C" fn(this: *const ITest2, a: u32, b: u32) -> u32,
+        pub f_get_Foo: unsafe extern "C" fn(this: *const ITest2) -> u32,
+        pub f_set_Foo: unsafe extern "C" fn(this: *const ITest2, value: u32) -> (),
+        pub f_get_Foo2: unsafe extern "C" fn(this: *const ITest2) -> u32,
+        pub f_set_Foo3: unsafe extern "C" fn(this: *const ITest2, value: u32) -> (),
+        pub f_Some: unsafe extern "C" fn(this: *const ITest2) -> (),
+    }
+
+    impl<T: impls::ITest2 + impls::Object, O: impls::ObjectBox<Object = T>> VT<T, ITest2, O>
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        pub const VTBL: VitualTable_ITest2 = VitualTable_ITest2 {
+            b: <ITest1 as Vtbl<O>>::VTBL,
+            f_Sub: Self::f_Sub,
+            f_get_Foo: Self::f_get_Foo,
+            f_set_Foo: Self::f_set_Foo,
+            f_get_Foo2: Self::f_get_Foo2,
+            f_set_Foo3: Self::f_set_Foo3,
+            f_Some: Self::f_Some,
+        };
+
+        unsafe extern "C" fn f_Sub(this: *const ITest2, a: u32, b: u32) -> u32 {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    cocom::object::reentrant_call("ITest2::Sub");
+                };
+                (*O::GetObject(this as _)).Sub(a, b)
+            }
+        }
+        unsafe extern "C" fn f_get_Foo(this: *const ITest2) -> u32 {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    cocom::object::reentrant_call("ITest2::get_Foo");
+                };
+                (*O::GetObject(this as _)).get_Foo()
+            }
+        }
+        unsafe extern "C" fn f_set_Foo(this: *const ITest2, value: u32) {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    cocom::object::reentrant_call("ITest2::set_Foo");
+                };
+                (*O::GetObject(this as _)).set_Foo(value)
+            }
+        }
+        unsafe extern "C" fn f_get_Foo2(this: *const ITest2) -> u32 {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    cocom::object::reentrant_call("ITest2::get_Foo2");
+                };
+                (*O::GetObject(this as _)).get_Foo2()
+            }
+        }
+        unsafe extern "C" fn f_set_Foo3(this: *const ITest2, value: u32) {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    cocom::object::reentrant_call("ITest2::set_Foo3");
+                };
+                (*O::GetObject(this as _)).set_Foo3(value)
+            }
+        }
+        unsafe extern "C" fn f_Some(this: *const ITest2) {
+            unsafe {
+                let Some(_guard) = O::BorrowMut(this as _) else {
+                    cocom::object::reentrant_call("ITest2::Some");
+                };
+                (*O::GetObject(this as _)).Some()
+            }
+        }
+    }
+
+    impl<T: impls::ITest2 + impls::Object, O: impls::ObjectBox<Object = T>> Vtbl<O> for ITest2
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        const VTBL: <ITest2 as Interface>::VitualTable = VT::<T, ITest2, O>::VTBL;
+        const VTBL_REF: &'static <ITest2 as Interface>::VitualTable = &<Self as Vtbl<O>>::VTBL;
+
+        fn vtbl() -> &'static Self::VitualTable {
+            &<Self as Vtbl<O>>::VTBL
+        }
+    }
+
+    impl<T: impls::ITest2 + impls::Object, O: impls::ObjectBox<Object = T>> QuIn<ITest2, T, O> for ITest2 {
+        #[inline(always)]
+        unsafe fn QueryInterface(
+            this: *mut T,
+            guid: Guid,
+            out: *mut *mut core::ffi::c_void,
+        ) -> HResult {
+            unsafe {
+                static GUID: Guid = ITest2::GUID;
+                if guid == GUID {
+                    *out = this as _;
+                    O::AddRef(this as _);
+                    return HResultE::Ok.into();
+                }
+                <ITest1 as QuIn<ITest1, T, O>>::QueryInterface(this, guid, out)
+            }
+        }
+    }
+
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct VitualTable_ITest3 {
+        b: <ITest2 as Interface>::VitualTable,
+
+        pub f_Some1: unsafe extern "C" fn(this: *const ITest3, a: Struct1, b: Enum1, c: Enum2) -> *mut Struct2<i32>,
+        pub f_FnPtr: unsafe extern "C" fn(this: *const ITest3, r#fn: unsafe extern "C" fn(i32, i32) -> i32) -> (),
+    }
+
+    impl<T: impls::ITest3 + impls::Object, O: impls::ObjectBox<Object = T>> VT<T, ITest3, O>
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        pub const VTBL: VitualTable_ITest3 = VitualTable_ITest3 {
+            b: <ITest2 as Vtbl<O>>::VTBL,
+            f_Some1: Self::f_Some1,
+            f_FnPtr: Self::f_FnPtr,
+        };
+
+        unsafe extern "C" fn f_Some1(this: *const ITest3, a: Struct1, b: Enum1, c: Enum2) -> *mut Struct2<i32> {
+            unsafe {
+                let Some(_guard) = O::BorrowMut(this as _) else {
+                    cocom::object::reentrant_call("ITest3::Some1");
+                };
+                (*O::GetObject(this as _)).Some1(a, b, c)
+            }
+        }
+        unsafe extern "C" fn f_FnPtr(this: *const ITest3, r#fn: unsafe extern "C" fn(i32, i32) -> i32) {
+            unsafe {
+                let Some(_guard) = O::BorrowMut(this as _) else {
+                    cocom::object::reentrant_call("ITest3::FnPtr");
+                };
+                (*O::GetObject(this as _)).FnPtr(r#fn)
+            }
+        }
+    }
+
+    impl<T: impls::ITest3 + impls::Object, O: impls::ObjectBox<Object = T>> Vtbl<O> for ITest3
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        const VTBL: <ITest3 as Interface>::VitualTable = VT::<T, ITest3, O>::VTBL;
+        const VTBL_REF: &'static <ITest3 as Interface>::VitualTable = &<Self as Vtbl<O>>::VTBL;
+
+        fn vtbl() -> &'static Self::VitualTable {
+            &<Self as Vtbl<O>>::VTBL
+        }
+    }
+
+    impl<T: impls::ITest3 + impls::Object, O: impls::ObjectBox<Object = T>> QuIn<ITest3, T, O> for ITest3 {
+        #[inline(always)]
+        unsafe fn QueryInterface(
+            this: *mut T,
+            guid: Guid,
+            out: *mut *mut core::ffi::c_void,
+        ) -> HResult {
+            unsafe {
+                static GUID: Guid = ITest3::GUID;
+                if guid == GUID {
+                    *out = this as _;
+                    O::AddRef(this as _);
+                    return HResultE::Ok.into();
+                }
+                <ITest2 as QuIn<ITest2, T, O>>::QueryInterface(this, guid, out)
+            }
+        }
+    }
+}
+
+pub mod impls {
+    pub use cocom::impls::*;
+    use cocom::{ComResult, Guid, HResult, Out, OutComPtr};
+
+    pub trait ITest1 : IUnknown {
+        fn Add(& self, a: u32, b: u32) -> u32;
+    }
+
+    pub trait ITest2 : ITest1 {
+        fn Sub(& self, a: u32, b: u32) -> u32;
+        fn get_Foo(& self) -> u32;
+        fn set_Foo(& self, value: u32) -> ();
+        fn get_Foo2(& self) -> u32;
+        fn set_Foo3(& self, value: u32) -> ();
+        fn Some(&mut self) -> ();
+    }
+
+    pub trait ITest3 : ITest2 {
+        fn Some1(&mut self, a: super::Struct1, b: super::Enum1, c: super::Enum2) -> *mut super::Struct2<i32>;
+        fn FnPtr(&mut self, r#fn: unsafe extern "C" fn(i32, i32) -> i32) -> ();
+    }
+}
@@ -336,14 +336,14 @@ namespace Coplt
         VirtualTable<IUnknown> b;
         u32 (*const COPLT_CDECL f_AddRefWeak)(const IWeak*);
         u32 (*const COPLT_CDECL f_ReleaseWeak)(const IWeak*);
-        bool (*const COPLT_CDECL f_TryUpgrade)(const IWeak*);
+        b8 (*const COPLT_CDECL f_TryUpgrade)(const IWeak*);
     };
 
     namespace Internal::VirtualImpl_IWeak
     {
         extern "C" u32 COPLT_CDECL AddRefWeak(const IWeak* self) noexcept;
         extern "C" u32 COPLT_CDECL ReleaseWeak(const IWeak* self) noexcept;
-        extern "C" bool COPLT_CDECL TryUpgrade(const IWeak* self) noexcept;
+        extern "C" b8 COPLT_CDECL TryUpgrade(const IWeak* self) noexcept;
     }
 
     template <>
@@ -387,7 +387,7 @@ namespace Coplt
         {
             virtual u32 Impl_AddRefWeak() const = 0;
             virtual u32 Impl_ReleaseWeak() const = 0;
-            virtual bool Impl_TryUpgrade() const = 0;
+            virtual b8 Impl_TryUpgrade() const = 0;
         };
 
         template <std::derived_from<IWeak> Base = IWeak>
@@ -421,7 +421,7 @@ namespace Coplt
                 return AsImpl(self)->Impl_ReleaseWeak();
             }
 
-            static bool COPLT_CDECL f_TryUpgrade(const IWeak* self) noexcept
+            static b8 COPLT_CDECL f_TryUpgrade(const IWeak* self) noexcept
             {
                 return AsImpl(self)->Impl_TryUpgrade();
             }
@@ -449,7 +449,7 @@ namespace Coplt
             return AsImpl<IWeak>(self)->Impl_ReleaseWeak();
         }
 
-        extern "C" inline bool COPLT_CDECL TryUpgrade(const IWeak* self) noexcept
+        extern "C" inline b8 COPLT_CDECL TryUpgrade(const IWeak* self) noexcept
         {
             return AsImpl<IWeak>(self)->Impl_TryUpgrade();
         }
@@ -468,7 +468,7 @@ namespace Coplt
             return COPLT_COM_PVTB(IWeak, self)->f_ReleaseWeak(self);
         }
 
-        static COPLT_FORCE_INLINE bool TryUpgrade(const IWeak* self)
+        static COPLT_FORCE_INLINE b8 TryUpgrade(const IWeak* self)
         {
             return COPLT_COM_PVTB(IWeak, self)->f_TryUpgrade(self);
         }
@@ -487,7 +487,7 @@ namespace Coplt
 
         COPLT_COM_METHOD(AddRefWeak, u32, () const)
         COPLT_COM_METHOD(ReleaseWeak, u32, () const)
-        COPLT_COM_METHOD(TryUpgrade, bool, () const)
+        COPLT_COM_METHOD(TryUpgrade, b8, () const)
     };
 }
 
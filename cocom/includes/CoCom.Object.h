@@ -62,7 +62,7 @@ namespace Coplt
             return r;
         }
 
-        bool Impl_TryUpgrade() const override
+        b8 Impl_TryUpgrade() const override
         {
             size_t cur = m_strong.load(std::memory_order_relaxed);
         re_try:
@@ -217,7 +217,7 @@ namespace Coplt
         }
 
         COPLT_FORCE_INLINE
-        bool Impl_TryUpgrade() const
+        b8 Impl_TryUpgrade() const
         {
             size_t cur = m_strong.load(std::memory_order_relaxed);
         re_try:
@@ -319,7 +319,7 @@ namespace Coplt
             return Impl_WeakRefCount<Self>::Impl_ReleaseWeak();
         }
 
-        u32 TryUpgrade() const
+        b8 TryUpgrade() const
         {
             return Impl_WeakRefCount<Self>::Impl_TryUpgrade();
         }
@@ -49,6 +49,10 @@ namespace Coplt
 
         template <class R, class... Args>
         using Func = R(Args...);
+
+        // mirrored by cocom::types on the rust side
+        static_assert(sizeof(b8) == 1 && sizeof(b32) == 4);
+        static_assert(sizeof(char8) == 1 && sizeof(char16) == 2 && sizeof(char32) == 4);
     }
 
     using namespace Types;
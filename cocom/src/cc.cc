@@ -1 +1,23 @@
 #include "../includes/CoCom.h"
+
+// instantiate the weak objects so the header signatures are checked against the vtables
+namespace Coplt::Internal::HeaderCheck
+{
+    struct WeakObject final : ComObject<IWeak>
+    {
+    };
+
+    struct WeakImpl final : ComImpl<WeakImpl, IWeak>
+    {
+    };
+
+    static_assert(std::same_as<decltype(std::declval<const WeakObject&>().Impl_TryUpgrade()), b8>);
+    static_assert(std::same_as<decltype(std::declval<const WeakRefCount<WeakImpl>&>().TryUpgrade()), b8>);
+
+    void Check()
+    {
+        Rc<WeakObject> a(new WeakObject);
+        [[maybe_unused]] const b8 r = a->TryUpgrade();
+        [[maybe_unused]] constexpr auto& vtb = ComProxy<IWeak>::s_vtb<WeakImpl>;
+    }
+}
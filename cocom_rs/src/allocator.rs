@@ -0,0 +1,640 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    fmt::Debug,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use alloc::sync::Arc;
+
+use crate::{
+    object::{DefaultObjectAllocator, ObjectAllocator},
+    sync::SpinLock,
+};
+
+/// snapshot of an allocator's live allocations
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AllocStats {
+    pub live_bytes: usize,
+    pub live_objects: usize,
+}
+
+/// atomic live bytes / objects counter shared by the allocators in this module
+#[derive(Debug, Default)]
+pub struct AllocCounter {
+    live_bytes: AtomicUsize,
+    live_objects: AtomicUsize,
+}
+
+impl AllocCounter {
+    pub const fn new() -> Self {
+        Self {
+            live_bytes: AtomicUsize::new(0),
+            live_objects: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline(always)]
+    pub fn on_alloc(&self, size: usize) {
+        self.live_bytes.fetch_add(size, Ordering::Relaxed);
+        self.live_objects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub fn on_dealloc(&self, size: usize) {
+        self.live_bytes.fetch_sub(size, Ordering::Relaxed);
+        self.live_objects.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub fn on_realloc(&self, old_size: usize, new_size: usize) {
+        self.live_bytes.fetch_add(new_size, Ordering::Relaxed);
+        self.live_bytes.fetch_sub(old_size, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> AllocStats {
+        AllocStats {
+            live_bytes: self.live_bytes.load(Ordering::Relaxed),
+            live_objects: self.live_objects.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[inline(always)]
+unsafe fn realloc_by_copy<A: ObjectAllocator + ?Sized>(
+    allocator: &A,
+    ptr: *mut u8,
+    layout: Layout,
+    new_size: usize,
+) -> *mut u8 {
+    unsafe {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let new_ptr = allocator.alloc(new_layout);
+        if !new_ptr.is_null() {
+            ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+            allocator.dealloc(ptr, layout);
+        }
+        new_ptr
+    }
+}
+
+#[inline(always)]
+unsafe fn default_alloc(layout: Layout) -> *mut u8 {
+    unsafe { ().alloc(layout) }
+}
+
+#[inline(always)]
+unsafe fn default_dealloc(ptr: *mut u8, layout: Layout) {
+    unsafe { ().dealloc(ptr, layout) }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// shared
+
+impl<A: ObjectAllocator + ?Sized> ObjectAllocator for &'static A {
+    const QUARANTINE: bool = A::QUARANTINE;
+
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        unsafe { (**self).alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        unsafe { (**self).alloc_zeroed(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { (**self).dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        unsafe { (**self).realloc(ptr, layout, new_size) }
+    }
+
+    fn stats(&self) -> Option<AllocStats> {
+        (**self).stats()
+    }
+}
+
+impl<A: ObjectAllocator + ?Sized> ObjectAllocator for Arc<A> {
+    const QUARANTINE: bool = A::QUARANTINE;
+
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        unsafe { (**self).alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        unsafe { (**self).alloc_zeroed(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { (**self).dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        unsafe { (**self).realloc(ptr, layout, new_size) }
+    }
+
+    fn stats(&self) -> Option<AllocStats> {
+        (**self).stats()
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// bridges
+
+/// bridge from any [`GlobalAlloc`]
+#[derive(Default, Clone, Copy)]
+pub struct GlobalAllocator<A: GlobalAlloc>(pub A);
+
+impl<A: GlobalAlloc> Debug for GlobalAllocator<A> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("GlobalAllocator")
+            .field(&core::any::type_name::<A>())
+            .finish()
+    }
+}
+
+impl<A: GlobalAlloc> ObjectAllocator for GlobalAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        unsafe { self.0.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        unsafe { self.0.alloc_zeroed(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { self.0.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        unsafe { self.0.realloc(ptr, layout, new_size) }
+    }
+}
+
+/// bridge from any [`core::alloc::Allocator`]
+#[cfg(nightly)]
+#[derive(Default, Clone, Copy)]
+pub struct CoreAllocator<A: core::alloc::Allocator>(pub A);
+
+#[cfg(nightly)]
+impl<A: core::alloc::Allocator> Debug for CoreAllocator<A> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("CoreAllocator")
+            .field(&core::any::type_name::<A>())
+            .finish()
+    }
+}
+
+#[cfg(nightly)]
+impl<A: core::alloc::Allocator> ObjectAllocator for CoreAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match self.0.allocate(layout) {
+            Ok(ptr) => ptr.cast::<u8>().as_ptr(),
+            Err(_) => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        match self.0.allocate_zeroed(layout) {
+            Ok(ptr) => ptr.cast::<u8>().as_ptr(),
+            Err(_) => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { self.0.deallocate(NonNull::new_unchecked(ptr), layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        unsafe {
+            let ptr = NonNull::new_unchecked(ptr);
+            let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+            let r = if new_size >= layout.size() {
+                self.0.grow(ptr, layout, new_layout)
+            } else {
+                self.0.shrink(ptr, layout, new_layout)
+            };
+            match r {
+                Ok(ptr) => ptr.cast::<u8>().as_ptr(),
+                Err(_) => ptr::null_mut(),
+            }
+        }
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// counting
+
+/// wraps an allocator and counts its live bytes and objects
+///
+/// the allocator is moved into every object, so share it through `&'static` or [`Arc`]
+#[derive(Debug, Default)]
+pub struct CountingAllocator<A: ObjectAllocator = DefaultObjectAllocator> {
+    inner: A,
+    counter: AllocCounter,
+}
+
+impl<A: ObjectAllocator> CountingAllocator<A> {
+    pub const fn new(inner: A) -> Self {
+        Self {
+            inner,
+            counter: AllocCounter::new(),
+        }
+    }
+
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+}
+
+impl<A: ObjectAllocator> ObjectAllocator for CountingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { self.inner.alloc(layout) };
+        if !ptr.is_null() {
+            self.counter.on_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { self.inner.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            self.counter.on_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { self.inner.dealloc(ptr, layout) };
+        self.counter.on_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { self.inner.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            self.counter.on_realloc(layout.size(), new_size);
+        }
+        new_ptr
+    }
+
+    fn stats(&self) -> Option<AllocStats> {
+        Some(self.counter.stats())
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// arena
+
+/// bump allocator for frame-scoped objects
+///
+/// `dealloc` only updates the counters, the memory is reclaimed by [`ArenaAllocator::reset`]
+/// or when the arena drops; requests that do not fit fall back to the global allocator
+pub struct ArenaAllocator {
+    buf: NonNull<u8>,
+    capacity: usize,
+    offset: AtomicUsize,
+    counter: AllocCounter,
+}
+
+unsafe impl Send for ArenaAllocator {}
+unsafe impl Sync for ArenaAllocator {}
+
+impl ArenaAllocator {
+    const ALIGN: usize = 16;
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(Self::ALIGN);
+        unsafe {
+            let layout = Layout::from_size_align_unchecked(capacity, Self::ALIGN);
+            let buf = default_alloc(layout);
+            let Some(buf) = NonNull::new(buf) else {
+                alloc::alloc::handle_alloc_error(layout)
+            };
+            Self {
+                buf,
+                capacity,
+                offset: AtomicUsize::new(0),
+                counter: AllocCounter::new(),
+            }
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// bytes consumed from the arena buffer, including alignment padding
+    pub fn used(&self) -> usize {
+        self.offset.load(Ordering::Relaxed).min(self.capacity)
+    }
+
+    pub fn contains(&self, ptr: *const u8) -> bool {
+        let base = self.buf.as_ptr() as usize;
+        let addr = ptr as usize;
+        addr >= base && addr < base + self.capacity
+    }
+
+    /// rewinds the arena, all objects allocated from it must be dead
+    pub fn reset(&mut self) {
+        assert_eq!(
+            self.counter.stats().live_objects,
+            0,
+            "reset an arena with live objects"
+        );
+        *self.offset.get_mut() = 0;
+    }
+
+    fn bump(&self, layout: Layout) -> Option<*mut u8> {
+        let base = self.buf.as_ptr() as usize;
+        let mut offset = self.offset.load(Ordering::Relaxed);
+        loop {
+            let start = (base + offset).checked_next_multiple_of(layout.align())? - base;
+            let end = start.checked_add(layout.size())?;
+            if end > self.capacity {
+                return None;
+            }
+            match self.offset.compare_exchange_weak(
+                offset,
+                end,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(unsafe { self.buf.as_ptr().add(start) }),
+                Err(cur) => offset = cur,
+            }
+        }
+    }
+}
+
+impl Debug for ArenaAllocator {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ArenaAllocator")
+            .field("capacity", &self.capacity)
+            .field("used", &self.used())
+            .field("stats", &self.counter.stats())
+            .finish()
+    }
+}
+
+impl Drop for ArenaAllocator {
+    fn drop(&mut self) {
+        debug_assert_eq!(
+            self.counter.stats().live_objects,
+            0,
+            "drop an arena with live objects"
+        );
+        unsafe {
+            let layout = Layout::from_size_align_unchecked(self.capacity, Self::ALIGN);
+            default_dealloc(self.buf.as_ptr(), layout);
+        }
+    }
+}
+
+impl ObjectAllocator for ArenaAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = match self.bump(layout) {
+            Some(ptr) => ptr,
+            None => unsafe { default_alloc(layout) },
+        };
+        if !ptr.is_null() {
+            self.counter.on_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        unsafe {
+            let ptr = self.alloc(layout);
+            if !ptr.is_null() {
+                ptr.write_bytes(0, layout.size());
+            }
+            ptr
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if !self.contains(ptr) {
+            unsafe { default_dealloc(ptr, layout) };
+        }
+        self.counter.on_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        unsafe { realloc_by_copy(self, ptr, layout, new_size) }
+    }
+
+    fn stats(&self) -> Option<AllocStats> {
+        Some(self.counter.stats())
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// pool
+
+/// size-class pool that recycles freed object blocks
+///
+/// blocks are grouped by power-of-two size classes from 16 to 4096 bytes;
+/// larger or over-aligned requests go straight to the global allocator
+pub struct PoolAllocator {
+    classes: [SpinLock<FreeList>; Self::CLASS_COUNT],
+    counter: AllocCounter,
+}
+
+#[derive(Default)]
+struct FreeList {
+    head: Option<NonNull<FreeBlock>>,
+    len: usize,
+}
+
+unsafe impl Send for FreeList {}
+
+struct FreeBlock {
+    next: Option<NonNull<FreeBlock>>,
+}
+
+impl PoolAllocator {
+    const MIN_CLASS: usize = 16;
+    const MAX_CLASS: usize = 4096;
+    const ALIGN: usize = 16;
+    const CLASS_COUNT: usize =
+        (Self::MAX_CLASS.trailing_zeros() - Self::MIN_CLASS.trailing_zeros()) as usize + 1;
+
+    pub const fn new() -> Self {
+        Self {
+            classes: [const { SpinLock::new(FreeList { head: None, len: 0 }) }; Self::CLASS_COUNT],
+            counter: AllocCounter::new(),
+        }
+    }
+
+    #[inline(always)]
+    fn class_of(layout: Layout) -> Option<usize> {
+        if layout.size() > Self::MAX_CLASS || layout.align() > Self::ALIGN {
+            return None;
+        }
+        let size = layout.size().max(Self::MIN_CLASS).next_power_of_two();
+        Some((size.trailing_zeros() - Self::MIN_CLASS.trailing_zeros()) as usize)
+    }
+
+    #[inline(always)]
+    fn class_layout(class: usize) -> Layout {
+        unsafe { Layout::from_size_align_unchecked(Self::MIN_CLASS << class, Self::ALIGN) }
+    }
+
+    /// number of freed blocks currently cached for reuse
+    pub fn cached_blocks(&self) -> usize {
+        self.classes.iter().map(|a| a.lock().len).sum()
+    }
+
+    /// returns all cached blocks to the global allocator
+    pub fn trim(&self) {
+        for (class, list) in self.classes.iter().enumerate() {
+            let mut head = {
+                let mut list = list.lock();
+                list.len = 0;
+                list.head.take()
+            };
+            while let Some(block) = head {
+                unsafe {
+                    head = (*block.as_ptr()).next;
+                    default_dealloc(block.as_ptr() as _, Self::class_layout(class));
+                }
+            }
+        }
+    }
+}
+
+impl Default for PoolAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for PoolAllocator {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PoolAllocator")
+            .field("cached_blocks", &self.cached_blocks())
+            .field("stats", &self.counter.stats())
+            .finish()
+    }
+}
+
+impl Drop for PoolAllocator {
+    fn drop(&mut self) {
+        self.trim();
+    }
+}
+
+impl ObjectAllocator for PoolAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = match Self::class_of(layout) {
+            None => unsafe { default_alloc(layout) },
+            Some(class) => {
+                let block = {
+                    let mut list = self.classes[class].lock();
+                    match list.head {
+                        Some(block) => {
+                            list.head = unsafe { (*block.as_ptr()).next };
+                            list.len -= 1;
+                            Some(block)
+                        }
+                        None => None,
+                    }
+                };
+                match block {
+                    Some(block) => block.as_ptr() as *mut u8,
+                    None => unsafe { default_alloc(Self::class_layout(class)) },
+                }
+            }
+        };
+        if !ptr.is_null() {
+            self.counter.on_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        unsafe {
+            let ptr = self.alloc(layout);
+            if !ptr.is_null() {
+                ptr.write_bytes(0, layout.size());
+            }
+            ptr
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        match Self::class_of(layout) {
+            None => unsafe { default_dealloc(ptr, layout) },
+            Some(class) => unsafe {
+                let block = ptr as *mut FreeBlock;
+                let mut list = self.classes[class].lock();
+                block.write(FreeBlock { next: list.head });
+                list.head = Some(NonNull::new_unchecked(block));
+                list.len += 1;
+            },
+        }
+        self.counter.on_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        unsafe {
+            let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+            if let Some(class) = Self::class_of(layout)
+                && Self::class_of(new_layout) == Some(class)
+            {
+                self.counter.on_realloc(layout.size(), new_size);
+                return ptr;
+            }
+            realloc_by_copy(self, ptr, layout, new_size)
+        }
+    }
+
+    fn stats(&self) -> Option<AllocStats> {
+        Some(self.counter.stats())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+    use crate::{allocator::*, impls::ObjectBox, object::MakeObjectWith, *};
+
+    static POOL: PoolAllocator = PoolAllocator::new();
+
+    #[object(IUnknown, &'static PoolAllocator)]
+    #[derive(Debug)]
+    pub struct Pooled {
+        a: u64,
+    }
+
+    #[object(IUnknown, Arc<ArenaAllocator>)]
+    #[derive(Debug)]
+    pub struct Framed {
+        a: u32,
+    }
+
+    #[test]
+    fn pool_recycles_blocks() {
+        let a = Pooled { a: 1 }.make_object_with(&POOL);
+        assert_eq!(POOL.stats().unwrap().live_objects, 1);
+        let first = a.as_com().const_ptr() as usize;
+        drop(a);
+        assert_eq!(POOL.stats().unwrap(), AllocStats::default());
+        assert_eq!(POOL.cached_blocks(), 1);
+        let b = Pooled { a: 2 }.make_object_with(&POOL);
+        assert_eq!(b.as_com().const_ptr() as usize, first);
+        assert_eq!(b.a, 2);
+    }
+
+    #[test]
+    fn arena_bumps_and_resets() {
+        let arena = Arc::new(ArenaAllocator::with_capacity(1024));
+        let a = Framed { a: 1 }.make_com_with(arena.clone());
+        let b = Framed { a: 2 }.make_com_with(arena.clone());
+        assert!(arena.contains(a.const_ptr() as _));
+        assert!(arena.contains(b.const_ptr() as _));
+        assert_eq!(arena.stats().unwrap().live_objects, 2);
+        drop((a, b));
+        let mut arena = Arc::into_inner(arena).unwrap();
+        assert_eq!(arena.stats().unwrap().live_objects, 0);
+        assert_ne!(arena.used(), 0);
+        arena.reset();
+        assert_eq!(arena.used(), 0);
+    }
+}
@@ -0,0 +1,805 @@
+//! Completion based asynchronous calls.
+//!
+//! A method that finishes later hands out an [`IAsyncOperation`]: callers ask for its
+//! [`AsyncStatus`], register one [`IAsyncCompleted`] handler and fetch the result, an object or
+//! nothing, once it completed. [`AsyncCall`] awaits an operation from Rust and
+//! [`AsyncOperation::from_future`] runs a Rust future as one.
+//!
+//! `async fn` in `#[interface]` lowers to this: the vtable slot takes a trailing
+//! `*mut *mut IAsyncOperation` out parameter and returns `HResult`, and the facade method returns
+//! an [`AsyncCall`]. Implementations answer such a slot with [`start`].
+
+use alloc::{sync::Arc, task::Wake};
+use core::{
+    future::{Future, IntoFuture},
+    marker::PhantomData,
+    pin::Pin,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, Ordering},
+    task::{Context, Poll, Waker},
+};
+
+use crate::{
+    ComError, ComPtr, ComResult, Guid, HResult, HResultE, IUnknown, Interface, IntoHResult,
+    OutComPtr, object, object::MakeObject, sync::SpinLock,
+};
+
+/// a plain `u32` on the ABI, so values from foreign code outside the known ones stay valid
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AsyncStatus(pub u32);
+
+impl AsyncStatus {
+    pub const STARTED: Self = Self(0);
+    pub const COMPLETED: Self = Self(1);
+    pub const CANCELED: Self = Self(2);
+    pub const ERROR: Self = Self(3);
+}
+
+#[cocom::interface("5f1d6c0b-8a2e-4b47-9d3f-6e0a1c7b2d41")]
+pub trait IAsyncCompleted: IUnknown {
+    fn Invoke(&self, op: *mut IAsyncOperation, status: AsyncStatus) -> HResult;
+}
+
+#[cocom::interface("5f1d6c0b-8a2e-4b47-9d3f-6e0a1c7b2d42")]
+pub trait IAsyncOperation: IUnknown {
+    fn Status(&self) -> AsyncStatus;
+    /// registers the one handler, called at once if the operation already finished
+    fn SetCompleted(&self, handler: *mut IAsyncCompleted) -> HResult;
+    /// the result object, may be null; the error of a failed or canceled operation
+    fn GetResult(&self, out: *mut *mut IUnknown) -> HResult;
+    fn Cancel(&self) -> HResult;
+}
+
+pub mod details {
+    use super::*;
+    pub use crate::details::*;
+
+    struct VT<T, V, O>(core::marker::PhantomData<(T, V, O)>);
+
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct VitualTable_IAsyncCompleted {
+        b: <IUnknown as Interface>::VitualTable,
+
+        pub f_Invoke: unsafe extern "C" fn(
+            this: *const IAsyncCompleted,
+            op: *mut IAsyncOperation,
+            status: AsyncStatus,
+        ) -> HResult,
+    }
+
+    impl<T: impls::IAsyncCompleted + impls::Object, O: impls::ObjectBox<Object = T>>
+        VT<T, IAsyncCompleted, O>
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        pub const VTBL: VitualTable_IAsyncCompleted = VitualTable_IAsyncCompleted {
+            b: <IUnknown as Vtbl<O>>::VTBL,
+            f_Invoke: Self::f_Invoke,
+        };
+
+        unsafe extern "C" fn f_Invoke(
+            this: *const IAsyncCompleted,
+            op: *mut IAsyncOperation,
+            status: AsyncStatus,
+        ) -> HResult {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    return HResultE::IllegalMethodCall.into();
+                };
+                (*O::GetObject(this as _)).Invoke(op, status)
+            }
+        }
+    }
+
+    impl<T: impls::IAsyncCompleted + impls::Object, O: impls::ObjectBox<Object = T>> Vtbl<O>
+        for IAsyncCompleted
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        const VTBL: <IAsyncCompleted as Interface>::VitualTable = VT::<T, IAsyncCompleted, O>::VTBL;
+        const VTBL_REF: &'static <IAsyncCompleted as Interface>::VitualTable =
+            &<Self as Vtbl<O>>::VTBL;
+
+        fn vtbl() -> &'static Self::VitualTable {
+            &<Self as Vtbl<O>>::VTBL
+        }
+    }
+
+    impl<T: impls::IAsyncCompleted + impls::Object, O: impls::ObjectBox<Object = T>>
+        QuIn<IAsyncCompleted, T, O> for IAsyncCompleted
+    {
+        #[inline(always)]
+        unsafe fn QueryInterface(
+            this: *mut T,
+            guid: Guid,
+            out: *mut *mut core::ffi::c_void,
+        ) -> HResult {
+            unsafe {
+                static GUID: Guid = IAsyncCompleted::GUID;
+                if guid == GUID {
+                    *out = this as _;
+                    O::AddRef(this as _);
+                    return HResultE::Ok.into();
+                }
+                <IUnknown as QuIn<IUnknown, T, O>>::QueryInterface(this, guid, out)
+            }
+        }
+    }
+
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct VitualTable_IAsyncOperation {
+        b: <IUnknown as Interface>::VitualTable,
+
+        pub f_Status: unsafe extern "C" fn(this: *const IAsyncOperation) -> AsyncStatus,
+        pub f_SetCompleted: unsafe extern "C" fn(
+            this: *const IAsyncOperation,
+            handler: *mut IAsyncCompleted,
+        ) -> HResult,
+        pub f_GetResult:
+            unsafe extern "C" fn(this: *const IAsyncOperation, out: *mut *mut IUnknown) -> HResult,
+        pub f_Cancel: unsafe extern "C" fn(this: *const IAsyncOperation) -> HResult,
+    }
+
+    impl<T: impls::IAsyncOperation + impls::Object, O: impls::ObjectBox<Object = T>>
+        VT<T, IAsyncOperation, O>
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        pub const VTBL: VitualTable_IAsyncOperation = VitualTable_IAsyncOperation {
+            b: <IUnknown as Vtbl<O>>::VTBL,
+            f_Status: Self::f_Status,
+            f_SetCompleted: Self::f_SetCompleted,
+            f_GetResult: Self::f_GetResult,
+            f_Cancel: Self::f_Cancel,
+        };
+
+        unsafe extern "C" fn f_Status(this: *const IAsyncOperation) -> AsyncStatus {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    crate::object::reentrant_call("IAsyncOperation::Status");
+                };
+                (*O::GetObject(this as _)).Status()
+            }
+        }
+
+        unsafe extern "C" fn f_SetCompleted(
+            this: *const IAsyncOperation,
+            handler: *mut IAsyncCompleted,
+        ) -> HResult {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    return HResultE::IllegalMethodCall.into();
+                };
+                (*O::GetObject(this as _)).SetCompleted(handler)
+            }
+        }
+
+        unsafe extern "C" fn f_GetResult(
+            this: *const IAsyncOperation,
+            out: *mut *mut IUnknown,
+        ) -> HResult {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    return HResultE::IllegalMethodCall.into();
+                };
+                let Some(out) = OutComPtr::new(out) else {
+                    return HResultE::Pointer.into();
+                };
+                (*O::GetObject(this as _)).GetResult(out)
+            }
+        }
+
+        unsafe extern "C" fn f_Cancel(this: *const IAsyncOperation) -> HResult {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    return HResultE::IllegalMethodCall.into();
+                };
+                (*O::GetObject(this as _)).Cancel()
+            }
+        }
+    }
+
+    impl<T: impls::IAsyncOperation + impls::Object, O: impls::ObjectBox<Object = T>> Vtbl<O>
+        for IAsyncOperation
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        const VTBL: <IAsyncOperation as Interface>::VitualTable = VT::<T, IAsyncOperation, O>::VTBL;
+        const VTBL_REF: &'static <IAsyncOperation as Interface>::VitualTable =
+            &<Self as Vtbl<O>>::VTBL;
+
+        fn vtbl() -> &'static Self::VitualTable {
+            &<Self as Vtbl<O>>::VTBL
+        }
+    }
+
+    impl<T: impls::IAsyncOperation + impls::Object, O: impls::ObjectBox<Object = T>>
+        QuIn<IAsyncOperation, T, O> for IAsyncOperation
+    {
+        #[inline(always)]
+        unsafe fn QueryInterface(
+            this: *mut T,
+            guid: Guid,
+            out: *mut *mut core::ffi::c_void,
+        ) -> HResult {
+            unsafe {
+                static GUID: Guid = IAsyncOperation::GUID;
+                if guid == GUID {
+                    *out = this as _;
+                    O::AddRef(this as _);
+                    return HResultE::Ok.into();
+                }
+                <IUnknown as QuIn<IUnknown, T, O>>::QueryInterface(this, guid, out)
+            }
+        }
+    }
+}
+
+pub mod impls {
+    pub use crate::impls::*;
+    use crate::{HResult, OutComPtr};
+
+    use super::AsyncStatus;
+
+    pub trait IAsyncCompleted: IUnknown {
+        fn Invoke(&self, op: *mut super::IAsyncOperation, status: AsyncStatus) -> HResult;
+    }
+
+    pub trait IAsyncOperation: IUnknown {
+        fn Status(&self) -> AsyncStatus;
+        fn SetCompleted(&self, handler: *mut super::IAsyncCompleted) -> HResult;
+        fn GetResult(&self, out: OutComPtr<super::IUnknown>) -> HResult;
+        fn Cancel(&self) -> HResult;
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// results
+
+/// What an operation can complete with: nothing or an object
+pub trait AsyncResult: Sized {
+    fn into_object(self) -> Option<ComPtr<IUnknown>>;
+    fn from_object(obj: Option<ComPtr<IUnknown>>) -> ComResult<Self>;
+}
+
+impl AsyncResult for () {
+    fn into_object(self) -> Option<ComPtr<IUnknown>> {
+        None
+    }
+
+    fn from_object(obj: Option<ComPtr<IUnknown>>) -> ComResult<Self> {
+        Ok(())
+    }
+}
+
+impl<I: Interface + crate::impls::RefCount> AsyncResult for Option<ComPtr<I>> {
+    fn into_object(self) -> Option<ComPtr<IUnknown>> {
+        self.map(|obj| obj.query_interface::<IUnknown>().unwrap())
+    }
+
+    fn from_object(obj: Option<ComPtr<IUnknown>>) -> ComResult<Self> {
+        Ok(obj.map(|obj| obj.query_interface::<I>()).transpose()?)
+    }
+}
+
+/// a null result is a `Pointer` error
+impl<I: Interface + crate::impls::RefCount> AsyncResult for ComPtr<I> {
+    fn into_object(self) -> Option<ComPtr<IUnknown>> {
+        Some(self).into_object()
+    }
+
+    fn from_object(obj: Option<ComPtr<IUnknown>>) -> ComResult<Self> {
+        Option::<Self>::from_object(obj)?.ok_or_else(|| HResultE::Pointer.into())
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// future adapter
+
+/// Awaits an [`IAsyncOperation`], the output of an `async fn` interface method
+pub struct AsyncCall<R> {
+    op: ComResult<ComPtr<IAsyncOperation>>,
+    waiter: Option<Arc<Waiter>>,
+    _p: PhantomData<fn() -> R>,
+}
+
+impl<R: AsyncResult> AsyncCall<R> {
+    pub fn new(op: ComPtr<IAsyncOperation>) -> Self {
+        Self {
+            op: Ok(op),
+            waiter: None,
+            _p: PhantomData,
+        }
+    }
+
+    /// from the result of an `async fn` slot: its `HResult` and the operation it wrote out
+    ///
+    /// # Safety
+    /// `op` must be null or an operation the caller owns a reference to
+    pub unsafe fn from_raw(hr: HResult, op: *mut IAsyncOperation) -> Self {
+        let op = match unsafe { ComPtr::create(op) } {
+            _ if hr.is_failure() => Err(ComError::last(hr)),
+            Some(op) => Ok(op),
+            None => Err(HResultE::Pointer.into()),
+        };
+        Self {
+            op,
+            waiter: None,
+            _p: PhantomData,
+        }
+    }
+
+    /// the operation, `None` if the call failed to start
+    pub fn operation(&self) -> Option<&ComPtr<IAsyncOperation>> {
+        self.op.as_ref().ok()
+    }
+
+    /// the operation without awaiting it, the error if the call failed to start
+    pub fn into_operation(self) -> ComResult<ComPtr<IAsyncOperation>> {
+        self.op
+    }
+}
+
+impl<R: AsyncResult> Future for AsyncCall<R> {
+    type Output = ComResult<R>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let op = match &this.op {
+            Ok(op) => op,
+            Err(e) => return Poll::Ready(Err(e.clone())),
+        };
+        if op.Status() == AsyncStatus::STARTED {
+            match &this.waiter {
+                Some(waiter) => *waiter.waker.lock() = Some(cx.waker().clone()),
+                None => {
+                    let waiter = Arc::new(Waiter {
+                        waker: SpinLock::new(Some(cx.waker().clone())),
+                    });
+                    let handler = Completed {
+                        waiter: waiter.clone(),
+                    }
+                    .make_com();
+                    let r = op.SetCompleted(handler.const_ptr() as _);
+                    if r.is_failure() {
+                        return Poll::Ready(Err(ComError::last(r)));
+                    }
+                    this.waiter = Some(waiter);
+                }
+            }
+            // the handler may have run before the waker was in place
+            if op.Status() == AsyncStatus::STARTED {
+                return Poll::Pending;
+            }
+        }
+        let mut out = ptr::null_mut();
+        let r = op.GetResult(&mut out);
+        let obj = unsafe { ComPtr::create(out) };
+        if r.is_failure() {
+            return Poll::Ready(Err(ComError::last(r)));
+        }
+        Poll::Ready(R::from_object(obj))
+    }
+}
+
+impl IntoFuture for ComPtr<IAsyncOperation> {
+    type Output = ComResult<Option<ComPtr<IUnknown>>>;
+    type IntoFuture = AsyncCall<Option<ComPtr<IUnknown>>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        AsyncCall::new(self)
+    }
+}
+
+struct Waiter {
+    waker: SpinLock<Option<Waker>>,
+}
+
+#[object(IAsyncCompleted)]
+struct Completed {
+    waiter: Arc<Waiter>,
+}
+
+impl impls::IAsyncCompleted for Completed {
+    fn Invoke(&self, op: *mut IAsyncOperation, status: AsyncStatus) -> HResult {
+        if let Some(waker) = self.waiter.waker.lock().take() {
+            waker.wake();
+        }
+        HResultE::Ok.into()
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// operation
+
+type BoxFuture =
+    Pin<alloc::boxed::Box<dyn Future<Output = ComResult<Option<ComPtr<IUnknown>>>> + Send>>;
+
+/// An [`IAsyncOperation`] running a Rust future.
+///
+/// The future is polled on whichever thread wakes it, starting with the one creating the
+/// operation, so it must not block.
+#[object(IAsyncOperation)]
+pub struct AsyncOperation {
+    task: Arc<Task>,
+}
+
+impl AsyncOperation {
+    pub fn from_future<R: AsyncResult>(
+        future: impl Future<Output = ComResult<R>> + Send + 'static,
+    ) -> ComPtr<IAsyncOperation> {
+        let task = Arc::new(Task {
+            future: SpinLock::new(Some(alloc::boxed::Box::pin(async move {
+                future.await.map(R::into_object)
+            }))),
+            poll: AtomicU8::new(IDLE),
+            op: AtomicPtr::new(ptr::null_mut()),
+            outcome: SpinLock::new(Outcome {
+                status: AsyncStatus::STARTED,
+                result: Ok(None),
+                handler: None,
+                handler_set: false,
+            }),
+        });
+        let op = AsyncOperation { task: task.clone() }.make_com();
+        task.op.store(op.const_ptr() as _, Ordering::Release);
+        task.run();
+        op
+    }
+}
+
+/// Answers an `async fn` slot: runs `future` as an [`AsyncOperation`] written to `out`
+pub fn start<R: AsyncResult>(
+    out: OutComPtr<IAsyncOperation>,
+    future: impl Future<Output = ComResult<R>> + Send + 'static,
+) -> HResult {
+    out.write(AsyncOperation::from_future(future));
+    HResultE::Ok.into()
+}
+
+const IDLE: u8 = 0;
+const POLLING: u8 = 1;
+/// woken while polling, poll again
+const NOTIFIED: u8 = 2;
+
+struct Task {
+    /// only touched by the thread that moved `poll` to `POLLING`
+    future: SpinLock<Option<BoxFuture>>,
+    poll: AtomicU8,
+    /// the operation, only used through the reference `handler` holds
+    op: AtomicPtr<IAsyncOperation>,
+    outcome: SpinLock<Outcome>,
+}
+
+struct Outcome {
+    status: AsyncStatus,
+    result: ComResult<Option<ComPtr<IUnknown>>>,
+    /// holds the operation alive until the handler ran
+    handler: Option<(ComPtr<IAsyncCompleted>, ComPtr<IAsyncOperation>)>,
+    /// a handler can be set only once, even after it ran
+    handler_set: bool,
+}
+
+impl Task {
+    fn run(self: &Arc<Self>) {
+        let mut state = self.poll.load(Ordering::Acquire);
+        loop {
+            let next = if state == IDLE { POLLING } else { NOTIFIED };
+            match self
+                .poll
+                .compare_exchange(state, next, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) if state == IDLE => break,
+                Ok(_) => return,
+                Err(s) => state = s,
+            }
+        }
+        loop {
+            let done = {
+                let mut future = self.future.lock();
+                let canceled = self.outcome.lock().status != AsyncStatus::STARTED;
+                match future.as_mut() {
+                    Some(_) if canceled => {
+                        *future = None;
+                        true
+                    }
+                    Some(f) => {
+                        let waker = Waker::from(self.clone());
+                        match f.as_mut().poll(&mut Context::from_waker(&waker)) {
+                            Poll::Ready(r) => {
+                                *future = None;
+                                drop(future);
+                                self.complete(r);
+                                true
+                            }
+                            Poll::Pending => false,
+                        }
+                    }
+                    None => true,
+                }
+            };
+            if done {
+                self.poll.store(IDLE, Ordering::Release);
+                return;
+            }
+            if self
+                .poll
+                .compare_exchange(POLLING, IDLE, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                return;
+            }
+            self.poll.store(POLLING, Ordering::Release);
+        }
+    }
+
+    /// records the outcome once and calls the handler
+    fn complete(&self, result: ComResult<Option<ComPtr<IUnknown>>>) {
+        let status = match &result {
+            Ok(_) => AsyncStatus::COMPLETED,
+            Err(e) if e.code() == HResultE::Abort.into() => AsyncStatus::CANCELED,
+            Err(_) => AsyncStatus::ERROR,
+        };
+        let handler = {
+            let mut outcome = self.outcome.lock();
+            if outcome.status != AsyncStatus::STARTED {
+                return;
+            }
+            outcome.status = status;
+            outcome.result = result;
+            outcome.handler.take()
+        };
+        if let Some((handler, op)) = handler {
+            handler.Invoke(op.const_ptr() as _, status);
+        }
+    }
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        self.run();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.run();
+    }
+}
+
+impl impls::IAsyncOperation for AsyncOperation {
+    fn Status(&self) -> AsyncStatus {
+        self.task.outcome.lock().status
+    }
+
+    fn SetCompleted(&self, handler: *mut IAsyncCompleted) -> HResult {
+        let Some(handler) = NonNull::new(handler) else {
+            return HResultE::Pointer.into();
+        };
+        let handler = unsafe { ComPtr::new_clone(handler) };
+        let op = unsafe {
+            ComPtr::new_clone(NonNull::new_unchecked(self.task.op.load(Ordering::Acquire)))
+        };
+        let status = {
+            let mut outcome = self.task.outcome.lock();
+            if outcome.handler_set {
+                return HResultE::IllegalMethodCall.into();
+            }
+            outcome.handler_set = true;
+            if outcome.status == AsyncStatus::STARTED {
+                outcome.handler = Some((handler, op));
+                return HResultE::Ok.into();
+            }
+            outcome.status
+        };
+        handler.Invoke(op.const_ptr() as _, status);
+        HResultE::Ok.into()
+    }
+
+    fn GetResult(&self, out: OutComPtr<IUnknown>) -> HResult {
+        let outcome = self.task.outcome.lock();
+        match &outcome.result {
+            _ if outcome.status == AsyncStatus::STARTED => HResultE::IllegalMethodCall.into(),
+            Ok(obj) => {
+                out.write(obj.clone());
+                HResultE::Ok.into()
+            }
+            Err(e) => e.clone().into_hresult(),
+        }
+    }
+
+    /// the future is dropped by whoever polls next, right away if nobody is polling
+    fn Cancel(&self) -> HResult {
+        self.task.complete(Err(HResultE::Abort.into()));
+        self.task.run();
+        HResultE::Ok.into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::{sync::Arc, task::Wake};
+    use core::{
+        future::Future,
+        pin::{Pin, pin},
+        sync::atomic::{AtomicBool, Ordering},
+        task::{Context, Poll, Waker},
+    };
+
+    use self::job::{IJob, impls};
+    use crate::{async_op::*, object::*, sync::SpinLock, *};
+
+    mod job {
+        use crate::{ComPtr, Guid, HResult, IUnknown, Interface, async_op::IAsyncOperation};
+
+        #[cocom::interface("0b7f2e94-3c61-4d8a-a5e2-9f4c1d6b8e07")]
+        pub trait IJob: IUnknown {
+            async fn Spawn(&self) -> ComPtr<IUnknown>;
+        }
+
+        test_details! {
+            IJob: IUnknown {
+                fn Spawn(&self, out: *mut *mut IAsyncOperation => OutComPtr) -> HResult;
+            }
+        }
+
+        pub mod impls {
+            pub use crate::impls::*;
+            use crate::{HResult, OutComPtr};
+
+            pub trait IJob: IUnknown {
+                fn Spawn(&self, out: OutComPtr<super::IAsyncOperation>) -> HResult;
+            }
+        }
+    }
+
+    /// a future that finishes once opened
+    #[derive(Clone, Default)]
+    struct Gate(Arc<SpinLock<(bool, Option<Waker>)>>);
+
+    impl Gate {
+        fn open(&self) {
+            let waker = {
+                let mut state = self.0.lock();
+                state.0 = true;
+                state.1.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+
+    impl Future for Gate {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let mut state = self.0.lock();
+            if state.0 {
+                return Poll::Ready(());
+            }
+            state.1 = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    #[derive(Default)]
+    struct Flag(AtomicBool);
+
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    impl Flag {
+        fn poll<F: Future>(self: &Arc<Self>, f: Pin<&mut F>) -> Poll<F::Output> {
+            self.0.store(false, Ordering::SeqCst);
+            f.poll(&mut Context::from_waker(&Waker::from(self.clone())))
+        }
+
+        fn woken(&self) -> bool {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    #[object(IUnknown)]
+    #[derive(Debug)]
+    struct Leaf {
+        a: u32,
+    }
+
+    #[object(IJob)]
+    struct Job {
+        gate: Gate,
+    }
+
+    impl impls::IJob for Job {
+        fn Spawn(&self, out: OutComPtr<IAsyncOperation>) -> HResult {
+            let gate = self.gate.clone();
+            start(out, async move {
+                gate.await;
+                Ok(Leaf { a: 7 }.make_com())
+            })
+        }
+    }
+
+    #[test]
+    fn future_round_trip() {
+        let gate = Gate::default();
+        let g = gate.clone();
+        let op = AsyncOperation::from_future(async move {
+            g.await;
+            Ok(Leaf { a: 1 }.make_com())
+        });
+        assert_eq!(op.Status(), AsyncStatus::STARTED);
+
+        let flag = Arc::new(Flag::default());
+        let mut call = pin!(AsyncCall::<ComPtr<IUnknown>>::new(op.clone()));
+        assert!(flag.poll(call.as_mut()).is_pending());
+        gate.open();
+        assert!(flag.woken());
+        assert_eq!(op.Status(), AsyncStatus::COMPLETED);
+        let Poll::Ready(Ok(leaf)) = flag.poll(call.as_mut()) else {
+            panic!("operation did not complete");
+        };
+        assert_eq!(leaf.downcast::<Leaf>().unwrap().a, 1);
+
+        // a finished operation completes right away
+        let mut again = pin!(op.into_future());
+        assert!(matches!(
+            flag.poll(again.as_mut()),
+            Poll::Ready(Ok(Some(_)))
+        ));
+    }
+
+    #[test]
+    fn errors_and_cancel() {
+        let flag = Arc::new(Flag::default());
+        let failed =
+            AsyncOperation::from_future(async { Err::<(), _>(HResultE::InvalidArg.into()) });
+        assert_eq!(failed.Status(), AsyncStatus::ERROR);
+        let mut call = pin!(AsyncCall::<()>::new(failed));
+        assert_eq!(
+            flag.poll(call.as_mut()),
+            Poll::Ready(Err(HResultE::InvalidArg.into()))
+        );
+
+        struct Dropped(Arc<AtomicBool>);
+        impl Drop for Dropped {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+        let dropped = Arc::new(AtomicBool::new(false));
+        let d = Dropped(dropped.clone());
+        let pending = AsyncOperation::from_future(async move {
+            let _d = d;
+            Gate::default().await;
+            Ok(())
+        });
+        let mut call = pin!(AsyncCall::<()>::new(pending.clone()));
+        assert!(flag.poll(call.as_mut()).is_pending());
+        assert_eq!(pending.Cancel(), HResult::ok());
+        assert!(dropped.load(Ordering::SeqCst));
+        assert!(flag.woken());
+        assert_eq!(pending.Status(), AsyncStatus::CANCELED);
+        assert_eq!(
+            flag.poll(call.as_mut()),
+            Poll::Ready(Err(HResultE::Abort.into()))
+        );
+    }
+
+    #[test]
+    fn async_fn_interface() {
+        let gate = Gate::default();
+        let job = Job { gate: gate.clone() }.make_com();
+        let flag = Arc::new(Flag::default());
+        let mut call = pin!(job.Spawn());
+        assert!(flag.poll(call.as_mut()).is_pending());
+        gate.open();
+        assert!(flag.woken());
+        let Poll::Ready(Ok(leaf)) = flag.poll(call.as_mut()) else {
+            panic!("operation did not complete");
+        };
+        assert_eq!(leaf.downcast::<Leaf>().unwrap().a, 7);
+    }
+}
@@ -1,8 +1,9 @@
-use crate::{IUnknown, IWeak, impls};
+use crate::{HResult, HResultE, IUnknown, IWeak, Interface, debug, impls};
 use core::{
     fmt::{Debug, Display},
+    marker::PhantomData,
     mem::ManuallyDrop,
-    ops::{Deref, DerefMut},
+    ops::Deref,
     ptr::NonNull,
 };
 
@@ -22,21 +23,41 @@ unsafe impl<T: impls::RefCount> Send for ComPtr<T> {}
 unsafe impl<T: impls::RefCount> Sync for ComPtr<T> {}
 
 impl<T: impls::RefCount> ComPtr<T> {
+    /// takes over a strong reference without an `AddRef`
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a live interface and the caller must own the reference it hands over
     pub unsafe fn new(ptr: NonNull<T>) -> Self {
         Self { ptr }
     }
 
+    /// [`ComPtr::new`] from a raw pointer
+    ///
+    /// # Safety
+    ///
+    /// as [`ComPtr::new`], and `ptr` must not be null
     pub unsafe fn new_unchecked(ptr: *mut T) -> Self {
         Self {
             ptr: unsafe { NonNull::new_unchecked(ptr) },
         }
     }
 
+    /// takes a new strong reference with an `AddRef`
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a live interface
     pub unsafe fn new_clone(ptr: NonNull<T>) -> Self {
         T::AddRef(ptr.as_ptr());
         Self { ptr }
     }
 
+    /// [`ComPtr::new`], `None` for null
+    ///
+    /// # Safety
+    ///
+    /// as [`ComPtr::new`] when `ptr` is not null
     pub unsafe fn create(ptr: *mut T) -> Option<Self> {
         Some(Self {
             ptr: NonNull::new(ptr)?,
@@ -55,20 +76,23 @@ impl<T: impls::RefCount> ComPtr<T> {
         self.ptr.as_ptr()
     }
 
-    pub unsafe fn get_mut(&self) -> &mut T {
-        unsafe { &mut *self.mut_ptr() }
+    /// borrows the reference without an `AddRef`
+    pub fn com_ref(&self) -> ComRef<'_, T> {
+        ComRef::from(self)
     }
 }
 
 impl<T: impls::RefCount> Drop for ComPtr<T> {
     fn drop(&mut self) {
-        unsafe { T::Release(self.ptr.as_ptr()) };
+        let ptr = self.ptr.as_ptr();
+        debug::trace_call!(T, ptr, ComPtr, Release, unsafe { T::Release(ptr) });
     }
 }
 
 impl<T: impls::RefCount> Clone for ComPtr<T> {
     fn clone(&self) -> Self {
-        unsafe { T::AddRef(self.ptr.as_ptr()) };
+        let ptr = self.ptr.as_ptr();
+        debug::trace_call!(T, ptr, ComPtr, AddRef, unsafe { T::AddRef(ptr) });
         Self { ptr: self.ptr }
     }
 }
@@ -81,9 +105,22 @@ impl<T: impls::RefCount> Deref for ComPtr<T> {
     }
 }
 
-impl<T: impls::RefCount> DerefMut for ComPtr<T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        unsafe { self.ptr.as_mut() }
+impl<T: impls::RefCount> ComPtr<T> {
+    pub fn as_unknown(&self) -> &IUnknown {
+        unsafe { &*(self.const_ptr() as *const IUnknown) }
+    }
+
+    /// queries the object for interface `U`
+    pub fn query_interface<U: Interface + impls::RefCount>(&self) -> Result<ComPtr<U>, HResult> {
+        let mut out = core::ptr::null_mut();
+        let r = self.as_unknown().QueryInterface(&U::GUID, &mut out);
+        if r.is_failure() {
+            return Err(r);
+        }
+        match NonNull::new(out) {
+            Some(ptr) => Ok(unsafe { ComPtr::new(ptr.cast()) }),
+            None => Err(HResultE::NoInterface.into()),
+        }
     }
 }
 
@@ -140,14 +177,31 @@ pub struct ComWeak<T: impls::WeakRefCount> {
 }
 
 impl<T: impls::WeakRefCount> ComWeak<T> {
+    /// takes over a weak reference without an `AddRefWeak`
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to an interface whose block is still alive and the caller must own the
+    /// weak reference it hands over
     pub unsafe fn new(ptr: NonNull<T>) -> Self {
         Self { ptr }
     }
+
+    /// takes a new weak reference with an `AddRefWeak`
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to an interface whose block is still alive
     pub unsafe fn downgrade(ptr: NonNull<T>) -> Self {
         unsafe { T::AddRefWeak(ptr.as_ptr()) };
         Self { ptr }
     }
 
+    /// [`ComWeak::new`], `None` for null
+    ///
+    /// # Safety
+    ///
+    /// as [`ComWeak::new`] when `ptr` is not null
     pub unsafe fn create(ptr: *mut T) -> Option<Self> {
         Some(Self {
             ptr: NonNull::new(ptr)?,
@@ -161,13 +215,15 @@ impl<T: impls::WeakRefCount> ComWeak<T> {
 
 impl<T: impls::WeakRefCount> Drop for ComWeak<T> {
     fn drop(&mut self) {
-        unsafe { T::ReleaseWeak(self.ptr.as_ptr()) };
+        let ptr = self.ptr.as_ptr();
+        debug::trace_call!(T, ptr, ComWeak, ReleaseWeak, unsafe { T::ReleaseWeak(ptr) });
     }
 }
 
 impl<T: impls::WeakRefCount> Clone for ComWeak<T> {
     fn clone(&self) -> Self {
-        unsafe { T::AddRefWeak(self.ptr.as_ptr()) };
+        let ptr = self.ptr.as_ptr();
+        debug::trace_call!(T, ptr, ComWeak, AddRefWeak, unsafe { T::AddRefWeak(ptr) });
         Self { ptr: self.ptr }
     }
 }
@@ -182,7 +238,10 @@ impl<T: impls::WeakRefCount> ComWeak<T> {
 impl<T: impls::WeakRefCount> ComWeak<T> {
     pub fn upgrade(&self) -> Option<ComPtr<T>> {
         unsafe {
-            if T::TryUpgrade(self.ptr.as_ptr()) {
+            let ptr = self.ptr.as_ptr();
+            let upgraded =
+                debug::trace_call!(T, ptr, ComWeak, Upgrade, T::TryUpgrade(ptr), |r: &bool| *r);
+            if upgraded {
                 Some(ComPtr::new(self.ptr))
             } else {
                 None
@@ -225,3 +284,212 @@ impl<U: impls::WeakRefCount, T: impls::WeakRefCount + impls::Inherit<U>> Upcast<
         self.clone().upcast()
     }
 }
+
+/// A borrowed interface pointer, for in-parameters the callee does not keep.
+///
+/// No `AddRef` on creation and no `Release` on drop; [`to_com_ptr`](Self::to_com_ptr) takes a
+/// reference of its own. Like `ComPtr`, it is never null, so `Option<ComRef<I>>` and
+/// `Option<ComPtr<I>>` have the layout of `*mut I` with `None` as null.
+#[repr(transparent)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ComRef<'a, T: impls::RefCount> {
+    ptr: NonNull<T>,
+    _p: PhantomData<&'a T>,
+}
+
+unsafe impl<T: impls::RefCount> Send for ComRef<'_, T> {}
+unsafe impl<T: impls::RefCount> Sync for ComRef<'_, T> {}
+
+impl<'a, T: impls::RefCount> ComRef<'a, T> {
+    /// # Safety
+    /// `ptr` must stay alive for `'a`
+    pub unsafe fn new(ptr: NonNull<T>) -> Self {
+        Self {
+            ptr,
+            _p: PhantomData,
+        }
+    }
+
+    /// # Safety
+    /// `ptr` must be null or stay alive for `'a`
+    pub unsafe fn create(ptr: *const T) -> Option<Self> {
+        Some(unsafe { Self::new(NonNull::new(ptr as *mut T)?) })
+    }
+
+    pub fn ptr(&self) -> NonNull<T> {
+        self.ptr
+    }
+
+    pub fn const_ptr(&self) -> *const T {
+        self.ptr.as_ptr()
+    }
+
+    pub fn get(&self) -> &'a T {
+        unsafe { &*self.ptr.as_ptr() }
+    }
+
+    /// a new owned reference
+    pub fn to_com_ptr(&self) -> ComPtr<T> {
+        unsafe { ComPtr::new_clone(self.ptr) }
+    }
+}
+
+impl<T: impls::RefCount> Clone for ComRef<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: impls::RefCount> Copy for ComRef<'_, T> {}
+
+impl<T: impls::RefCount> Deref for ComRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<'a, T: impls::RefCount> From<&'a ComPtr<T>> for ComRef<'a, T> {
+    fn from(value: &'a ComPtr<T>) -> Self {
+        unsafe { Self::new(value.ptr) }
+    }
+}
+
+impl<'a, T: impls::RefCount> From<&'a T> for ComRef<'a, T> {
+    fn from(value: &'a T) -> Self {
+        unsafe { Self::new(NonNull::from(value)) }
+    }
+}
+
+impl<T: impls::RefCount + Debug> Debug for ComRef<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        unsafe { self.ptr.as_ref() }.fmt(f)
+    }
+}
+
+impl<T: impls::RefCount + Display> Display for ComRef<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        unsafe { self.ptr.as_ref() }.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::{
+        mem::{size_of, transmute},
+        ptr,
+    };
+
+    use self::holder::{IHolder, impls};
+    use crate::{object::*, sync::SpinLock, *};
+
+    mod holder {
+        use crate::{ComPtr, ComRef, Guid, HResult, IUnknown, Interface};
+
+        #[cocom::interface("7d3b9f20-5c1e-4a86-9e47-0b2f6c8a1d35")]
+        pub trait IHolder: IUnknown {
+            /// reference count of `obj` as seen by the callee
+            fn Peek(&self, obj: ComRef<IUnknown>) -> u32;
+            fn Keep(&self, obj: Option<ComPtr<IUnknown>>) -> bool;
+            fn Maybe(&self, obj: Option<ComRef<IUnknown>>) -> bool;
+        }
+
+        test_details! {
+            IHolder: IUnknown {
+                fn Peek(&self, obj: ComRef<IUnknown>) -> u32;
+                fn Keep(&self, obj: Option<ComPtr<IUnknown>>) -> bool;
+                fn Maybe(&self, obj: Option<ComRef<IUnknown>>) -> bool;
+            }
+        }
+
+        pub mod impls {
+            pub use crate::impls::*;
+            use crate::{ComPtr, ComRef};
+
+            pub trait IHolder: IUnknown {
+                fn Peek(&self, obj: ComRef<super::IUnknown>) -> u32;
+                fn Keep(&self, obj: Option<ComPtr<super::IUnknown>>) -> bool;
+                fn Maybe(&self, obj: Option<ComRef<super::IUnknown>>) -> bool;
+            }
+        }
+    }
+
+    #[object(IHolder)]
+    struct Holder {
+        kept: SpinLock<Option<ComPtr<IUnknown>>>,
+    }
+
+    impl impls::IHolder for Holder {
+        fn Peek(&self, obj: ComRef<IUnknown>) -> u32 {
+            refs(&obj)
+        }
+
+        fn Keep(&self, obj: Option<ComPtr<IUnknown>>) -> bool {
+            let some = obj.is_some();
+            *self.kept.lock() = obj;
+            some
+        }
+
+        fn Maybe(&self, obj: Option<ComRef<IUnknown>>) -> bool {
+            obj.is_some()
+        }
+    }
+
+    #[object(IUnknown)]
+    struct Leaf;
+
+    fn refs(obj: &IUnknown) -> u32 {
+        obj.AddRef();
+        obj.Release() - 1
+    }
+
+    #[test]
+    fn option_is_nullable_pointer() {
+        assert_eq!(
+            size_of::<Option<ComPtr<IUnknown>>>(),
+            size_of::<*mut IUnknown>()
+        );
+        assert_eq!(
+            size_of::<Option<ComRef<IUnknown>>>(),
+            size_of::<*mut IUnknown>()
+        );
+        let none: Option<ComRef<IUnknown>> = unsafe { transmute(ptr::null_mut::<IUnknown>()) };
+        assert!(none.is_none());
+        let none: Option<ComPtr<IUnknown>> = unsafe { transmute(ptr::null_mut::<IUnknown>()) };
+        assert!(none.is_none());
+
+        let leaf = Leaf.make_com();
+        let raw: *const IUnknown = unsafe { transmute(Some(leaf.com_ref())) };
+        assert_eq!(raw, leaf.const_ptr());
+        let back = unsafe { ComRef::<IUnknown>::create(raw) };
+        assert_eq!(back.map(|r| r.ptr()), Some(leaf.ptr()));
+    }
+
+    #[test]
+    fn ownership_in_signatures() {
+        let holder = Holder {
+            kept: SpinLock::new(None),
+        }
+        .make_com();
+        let leaf = Leaf.make_com().query_interface::<IUnknown>().unwrap();
+        assert_eq!(refs(&leaf), 1);
+
+        assert_eq!(holder.Peek(&leaf), 1);
+        assert_eq!(holder.Peek(&*leaf), 1);
+        assert_eq!(refs(&leaf), 1);
+
+        assert!(holder.Maybe(Some(leaf.com_ref())));
+        assert!(!holder.Maybe(None));
+        assert_eq!(refs(&leaf), 1);
+
+        assert!(holder.Keep(Some(leaf.clone())));
+        assert_eq!(refs(&leaf), 2);
+        assert!(!holder.Keep(None));
+        assert_eq!(refs(&leaf), 1);
+
+        let owned = leaf.com_ref().to_com_ptr();
+        assert_eq!(owned.ptr(), leaf.ptr());
+        assert_eq!(refs(&leaf), 2);
+    }
+}
@@ -0,0 +1,52 @@
+//! Lifetime debugging aids, each compiled in behind its own cargo feature
+
+#[cfg(feature = "track-objects")]
+mod graph;
+#[cfg(feature = "trace-refcount")]
+mod trace;
+#[cfg(feature = "track-objects")]
+mod tracker;
+
+#[cfg(feature = "track-objects")]
+pub use graph::*;
+#[cfg(feature = "trace-refcount")]
+pub use trace::*;
+#[cfg(feature = "track-objects")]
+pub use tracker::*;
+
+/// Emits a [`RefEvent`] when `trace-refcount` is enabled, expands to nothing otherwise
+macro_rules! trace_ref {
+    ($ty:ty, $ptr:expr, $source:ident, $op:ident, $count:expr) => {
+        #[cfg(feature = "trace-refcount")]
+        $crate::debug::emit::<$ty>(
+            $ptr as *const (),
+            $crate::debug::RefSource::$source,
+            $crate::debug::RefOp::$op,
+            Some($count),
+        );
+    };
+}
+pub(crate) use trace_ref;
+
+/// Runs the vtable call of a `ComPtr` or `ComWeak` and, when `trace-refcount` is enabled and
+/// `$changed` accepts the result, emits a [`RefEvent`] unless the object reported it itself
+macro_rules! trace_call {
+    ($ty:ty, $ptr:expr, $source:ident, $op:ident, $call:expr) => {
+        $crate::debug::trace_call!($ty, $ptr, $source, $op, $call, |_| true)
+    };
+    ($ty:ty, $ptr:expr, $source:ident, $op:ident, $call:expr, $changed:expr) => {{
+        #[cfg(feature = "trace-refcount")]
+        let outer = $crate::debug::begin_call();
+        let r = $call;
+        #[cfg(feature = "trace-refcount")]
+        $crate::debug::end_call::<$ty>(
+            outer,
+            $ptr as *const (),
+            $crate::debug::RefSource::$source,
+            $crate::debug::RefOp::$op,
+            ($changed)(&r),
+        );
+        r
+    }};
+}
+pub(crate) use trace_call;
@@ -0,0 +1,321 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    vec::Vec,
+};
+use core::fmt::Write;
+
+use super::tracker::with_live;
+
+/// A tracked object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    /// interface pointer
+    pub ptr: usize,
+    pub type_name: &'static str,
+    pub strong: u32,
+}
+
+/// A strongly connected group of objects, see [`Graph::leak_candidates`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeakCandidate {
+    pub members: Vec<usize>,
+    /// strong references not explained by edges between the members, zero means nothing
+    /// outside the cycle can reach it anymore
+    pub external_refs: u32,
+}
+
+/// Snapshot of who holds strong references to whom among the live tracked objects.
+///
+/// Edges come from [`impls::Object::trace`](crate::impls::Object::trace); targets that are
+/// not tracked objects, e.g. implemented in C++, appear only as edge ends.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    /// `(holder, target)` per reference, repeated if held more than once
+    pub edges: Vec<(usize, usize)>,
+}
+
+/// Walks every live tracked object and collects its references.
+///
+/// Objects with [`CHECK_REENTRANCY`](crate::impls::Object::CHECK_REENTRANCY) are read under a
+/// shared borrow, those inside a `&mut self` method are listed without their edges. Other
+/// objects are read without synchronizing with their own users, take the snapshot at a
+/// quiescent point.
+pub fn graph() -> Graph {
+    with_live(|live| {
+        let mut graph = Graph::default();
+        for (&ptr, obj) in live {
+            graph.nodes.push(Node {
+                ptr,
+                type_name: obj.type_name,
+                strong: obj.strong_count(),
+            });
+            obj.trace(&mut |target: *const ()| graph.edges.push((ptr, target as usize)));
+        }
+        graph
+    })
+}
+
+impl Graph {
+    pub fn node(&self, ptr: usize) -> Option<&Node> {
+        self.nodes.iter().find(|n| n.ptr == ptr)
+    }
+
+    /// edge targets that are not tracked objects
+    pub fn externals(&self) -> BTreeSet<usize> {
+        let nodes: BTreeSet<usize> = self.nodes.iter().map(|n| n.ptr).collect();
+        self.edges
+            .iter()
+            .map(|&(_, to)| to)
+            .filter(|to| !nodes.contains(to))
+            .collect()
+    }
+
+    /// Strongly connected components that contain a cycle, ordered by address of their members
+    pub fn leak_candidates(&self) -> Vec<LeakCandidate> {
+        let index: BTreeMap<usize, usize> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.ptr, i))
+            .collect();
+        let mut succ: Vec<Vec<usize>> = alloc::vec![Vec::new(); self.nodes.len()];
+        for (from, to) in &self.edges {
+            if let (Some(&f), Some(&t)) = (index.get(from), index.get(to)) {
+                succ[f].push(t);
+            }
+        }
+        let mut candidates: Vec<LeakCandidate> = tarjan(&succ)
+            .into_iter()
+            .filter(|scc| scc.len() > 1 || succ[scc[0]].contains(&scc[0]))
+            .map(|mut scc| {
+                scc.sort_unstable();
+                let members: BTreeSet<usize> = scc.iter().copied().collect();
+                let internal = scc
+                    .iter()
+                    .flat_map(|&m| &succ[m])
+                    .filter(|t| members.contains(t))
+                    .count() as u32;
+                let strong: u32 = scc.iter().map(|&m| self.nodes[m].strong).sum();
+                LeakCandidate {
+                    members: scc.into_iter().map(|m| self.nodes[m].ptr).collect(),
+                    external_refs: strong.saturating_sub(internal),
+                }
+            })
+            .collect();
+        candidates.sort_unstable_by_key(|c| c.members[0]);
+        candidates
+    }
+
+    /// Graphviz source, untracked targets drawn as dashed boxes
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph cocom {\n");
+        for n in &self.nodes {
+            _ = writeln!(
+                out,
+                "  \"{:#x}\" [label=\"{}\\nstrong={}\"];",
+                n.ptr,
+                escape(n.type_name),
+                n.strong
+            );
+        }
+        for ext in self.externals() {
+            _ = writeln!(
+                out,
+                "  \"{ext:#x}\" [label=\"external\", shape=box, style=dashed];"
+            );
+        }
+        for (from, to) in &self.edges {
+            _ = writeln!(out, "  \"{from:#x}\" -> \"{to:#x}\";");
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// `{"nodes":[{"ptr","type","strong"}],"edges":[[from,to]],"leak_candidates":[...]}`
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"nodes\":[");
+        for (i, n) in self.nodes.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            _ = write!(
+                out,
+                "{sep}{{\"ptr\":\"{:#x}\",\"type\":\"{}\",\"strong\":{}}}",
+                n.ptr,
+                escape(n.type_name),
+                n.strong
+            );
+        }
+        out.push_str("],\"edges\":[");
+        for (i, (from, to)) in self.edges.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            _ = write!(out, "{sep}[\"{from:#x}\",\"{to:#x}\"]");
+        }
+        out.push_str("],\"leak_candidates\":[");
+        for (i, c) in self.leak_candidates().iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            _ = write!(out, "{sep}{{\"members\":[");
+            for (j, m) in c.members.iter().enumerate() {
+                let sep = if j == 0 { "" } else { "," };
+                _ = write!(out, "{sep}\"{m:#x}\"");
+            }
+            _ = write!(out, "],\"external_refs\":{}}}", c.external_refs);
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// iterative Tarjan, components in reverse topological order
+fn tarjan(succ: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    const UNVISITED: usize = usize::MAX;
+    let n = succ.len();
+    let mut index = alloc::vec![UNVISITED; n];
+    let mut low = alloc::vec![0; n];
+    let mut on_stack = alloc::vec![false; n];
+    let mut stack = Vec::new();
+    let mut sccs = Vec::new();
+    let mut next = 0;
+    for root in 0..n {
+        if index[root] != UNVISITED {
+            continue;
+        }
+        // (node, next successor to look at)
+        let mut call = alloc::vec![(root, 0)];
+        index[root] = next;
+        low[root] = next;
+        next += 1;
+        stack.push(root);
+        on_stack[root] = true;
+        while let Some(&mut (v, ref mut i)) = call.last_mut() {
+            if let Some(&w) = succ[v].get(*i) {
+                *i += 1;
+                if index[w] == UNVISITED {
+                    index[w] = next;
+                    low[w] = next;
+                    next += 1;
+                    stack.push(w);
+                    on_stack[w] = true;
+                    call.push((w, 0));
+                } else if on_stack[w] {
+                    low[v] = low[v].min(index[w]);
+                }
+                continue;
+            }
+            call.pop();
+            if let Some(&(parent, _)) = call.last() {
+                low[parent] = low[parent].min(low[v]);
+            }
+            if low[v] == index[v] {
+                let mut scc = Vec::new();
+                while let Some(w) = stack.pop() {
+                    on_stack[w] = false;
+                    scc.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                sccs.push(scc);
+            }
+        }
+    }
+    sccs
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{debug::*, impls::ObjectBox, object::*, sync::SpinLock, *};
+
+    #[object(IUnknown)]
+    struct Linked {
+        next: SpinLock<Option<ComPtr<IUnknown>>>,
+    }
+
+    #[object(IUnknown)]
+    #[derive(Debug)]
+    struct Standalone {
+        next: Option<ComPtr<IUnknown>>,
+    }
+
+    #[object(IUnknown, check_reentrancy)]
+    #[derive(Debug)]
+    struct Checked {
+        next: Option<ComPtr<IUnknown>>,
+    }
+
+    #[test]
+    fn skips_edges_of_borrowed_objects() {
+        let target = Standalone { next: None }.make_com();
+        let a = Checked {
+            next: Some(target.clone()),
+        }
+        .make_com();
+        let a_ptr = a.const_ptr() as usize;
+        let edge = (a_ptr, target.const_ptr() as usize);
+        let outer = unsafe { Object::<Checked>::BorrowMut(a.mut_ptr()) }.unwrap();
+        let g = graph();
+        assert!(g.node(a_ptr).is_some());
+        assert!(!g.edges.contains(&edge));
+        drop(outer);
+        assert!(graph().edges.contains(&edge));
+    }
+
+    #[test]
+    fn cycle_is_a_candidate() {
+        let a = Linked {
+            next: SpinLock::new(None),
+        }
+        .make_object();
+        let b = Linked {
+            next: SpinLock::new(Some(a.as_com().clone())),
+        }
+        .make_object();
+        *a.next.lock() = Some(b.as_com().clone());
+        let a_ptr = a.as_com().const_ptr() as usize;
+        let b_ptr = b.as_com().const_ptr() as usize;
+
+        let external = Standalone { next: None }.make_com();
+        let tail = Standalone {
+            next: Some(external.clone()),
+        }
+        .make_com();
+
+        let g = graph();
+        assert!(g.edges.contains(&(a_ptr, b_ptr)));
+        assert!(g.edges.contains(&(b_ptr, a_ptr)));
+        let mut pair = [a_ptr, b_ptr];
+        pair.sort();
+        let candidate = g
+            .leak_candidates()
+            .into_iter()
+            .find(|c| c.members == pair)
+            .unwrap();
+        assert_eq!(candidate.external_refs, 2);
+        assert!(
+            g.leak_candidates()
+                .iter()
+                .all(|c| !c.members.contains(&(tail.const_ptr() as usize)))
+        );
+        assert!(
+            g.to_dot()
+                .contains(&alloc::format!("\"{a_ptr:#x}\" -> \"{b_ptr:#x}\""))
+        );
+        assert!(
+            g.to_json()
+                .contains(&alloc::format!("[\"{b_ptr:#x}\",\"{a_ptr:#x}\"]"))
+        );
+
+        drop((a, b));
+        let g = graph();
+        let candidate = g.leak_candidates().into_iter().find(|c| c.members == pair);
+        // a `gc::collect` of another test may have reclaimed it
+        if cfg!(not(feature = "gc")) || candidate.is_some() {
+            assert_eq!(candidate.unwrap().external_refs, 0);
+        }
+        drop((external, tail));
+    }
+}
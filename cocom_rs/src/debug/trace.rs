@@ -0,0 +1,356 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{
+    any::type_name,
+    fmt, ptr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::sync::SpinLock;
+
+/// Which layer saw the refcount change
+///
+/// Under `std`, a change the object reports itself is not reported again by the `ComPtr` or
+/// `ComWeak` that caused it, so pointer events are left for objects implemented elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RefSource {
+    /// the `Object<T>`/`WeakObject<T>` box itself
+    Object,
+    /// a `ComPtr`
+    ComPtr,
+    /// a `ComWeak`
+    ComWeak,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RefOp {
+    /// the reference an object starts out with
+    Create,
+    AddRef,
+    Release,
+    /// a weak reference turned into a strong one
+    Upgrade,
+    AddRefWeak,
+    ReleaseWeak,
+}
+
+impl RefOp {
+    /// change to the strong count
+    pub fn strong_delta(self) -> i32 {
+        match self {
+            RefOp::Create | RefOp::AddRef | RefOp::Upgrade => 1,
+            RefOp::Release => -1,
+            RefOp::AddRefWeak | RefOp::ReleaseWeak => 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RefEvent {
+    /// interface pointer of the object
+    pub ptr: *const (),
+    /// concrete type for [`RefSource::Object`] events, interface type otherwise
+    pub type_name: &'static str,
+    pub source: RefSource,
+    pub op: RefOp,
+    /// the resulting count for [`RefSource::Object`] events; `None` for the others, what a
+    /// vtable returns is only a hint
+    pub count: Option<u32>,
+    /// captured according to `RUST_BACKTRACE`
+    #[cfg(feature = "std")]
+    pub backtrace: std::backtrace::Backtrace,
+}
+
+unsafe impl Send for RefEvent {}
+unsafe impl Sync for RefEvent {}
+
+impl fmt::Display for RefEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} {:?} {} @ {:p}",
+            self.source, self.op, self.type_name, self.ptr
+        )?;
+        if let Some(count) = self.count {
+            write!(f, " -> {count}")?;
+        }
+        #[cfg(feature = "std")]
+        if self.backtrace.status() == std::backtrace::BacktraceStatus::Captured {
+            write!(f, "\n{}", self.backtrace)?;
+        }
+        Ok(())
+    }
+}
+
+/// Receives every refcount change while installed with [`set_trace_sink`]
+pub trait RefTraceSink: Send + Sync {
+    fn event(&self, event: RefEvent);
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static SINK: SpinLock<Option<&'static dyn RefTraceSink>> = SpinLock::new(None);
+
+/// Installs the sink, or removes it with `None`, returning the previous one
+pub fn set_trace_sink(
+    sink: Option<&'static dyn RefTraceSink>,
+) -> Option<&'static dyn RefTraceSink> {
+    let mut slot = SINK.lock();
+    ENABLED.store(sink.is_some(), Ordering::Release);
+    core::mem::replace(&mut *slot, sink)
+}
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    /// object of the last [`RefSource::Object`] event inside the current pointer call
+    static REPORTED: core::cell::Cell<*const ()> = const { core::cell::Cell::new(ptr::null()) };
+}
+
+/// starts the vtable call of a `ComPtr` or `ComWeak`, returns what [`end_call`] restores
+pub(crate) fn begin_call() -> *const () {
+    #[cfg(feature = "std")]
+    if let Ok(outer) = REPORTED.try_with(|r| r.replace(ptr::null())) {
+        return outer;
+    }
+    ptr::null()
+}
+
+/// ends the call, emitting its event if it `changed` a count the object did not report
+pub(crate) fn end_call<T: ?Sized>(
+    outer: *const (),
+    ptr: *const (),
+    source: RefSource,
+    op: RefOp,
+    changed: bool,
+) {
+    #[cfg(feature = "std")]
+    let reported = REPORTED
+        .try_with(|r| r.replace(outer))
+        .unwrap_or(ptr::null());
+    #[cfg(not(feature = "std"))]
+    let reported = {
+        _ = outer;
+        ptr::null()
+    };
+    if changed && reported != ptr {
+        emit::<T>(ptr, source, op, None);
+    }
+}
+
+pub(crate) fn emit<T: ?Sized>(ptr: *const (), source: RefSource, op: RefOp, count: Option<u32>) {
+    if !ENABLED.load(Ordering::Acquire) {
+        return;
+    }
+    #[cfg(feature = "std")]
+    if source == RefSource::Object {
+        _ = REPORTED.try_with(|r| r.set(ptr));
+    }
+    // copied out so the sink can touch refcounts itself
+    let Some(sink) = *SINK.lock() else {
+        return;
+    };
+    sink.event(RefEvent {
+        ptr,
+        type_name: type_name::<T>(),
+        source,
+        op,
+        count,
+        #[cfg(feature = "std")]
+        backtrace: std::backtrace::Backtrace::capture(),
+    });
+}
+
+/// Matches strong increments with releases per object and keeps whatever is left over.
+///
+/// Only [`RefSource::Object`] events are paired: a `ComPtr` adopts references it did not
+/// `AddRef` itself, so its events never balance on their own. Each release is matched with
+/// the most recent outstanding increment; a release with nothing outstanding is a double
+/// release.
+#[derive(Default)]
+pub struct PairingSink {
+    filter: Option<fn(&RefEvent) -> bool>,
+    state: SpinLock<PairingState>,
+}
+
+#[derive(Default)]
+struct PairingState {
+    outstanding: BTreeMap<usize, Vec<RefEvent>>,
+    over_released: Vec<RefEvent>,
+}
+
+impl PairingSink {
+    pub const fn new() -> Self {
+        Self {
+            filter: None,
+            state: SpinLock::new(PairingState {
+                outstanding: BTreeMap::new(),
+                over_released: Vec::new(),
+            }),
+        }
+    }
+
+    /// only pairs events the filter accepts
+    pub const fn with_filter(filter: fn(&RefEvent) -> bool) -> Self {
+        Self {
+            filter: Some(filter),
+            state: SpinLock::new(PairingState {
+                outstanding: BTreeMap::new(),
+                over_released: Vec::new(),
+            }),
+        }
+    }
+
+    /// increments never released, grouped by object
+    pub fn unreleased(&self) -> usize {
+        self.state.lock().outstanding.values().map(Vec::len).sum()
+    }
+
+    /// releases that had no increment to match
+    pub fn over_released(&self) -> usize {
+        self.state.lock().over_released.len()
+    }
+
+    /// forgets everything recorded so far
+    pub fn clear(&self) {
+        let mut state = self.state.lock();
+        state.outstanding.clear();
+        state.over_released.clear();
+    }
+
+    /// writes every unpaired event
+    pub fn write_unpaired(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        let state = self.state.lock();
+        for (ptr, events) in &state.outstanding {
+            writeln!(w, "{} unreleased on {:#x}:", events.len(), ptr)?;
+            for e in events {
+                writeln!(w, "  {e}")?;
+            }
+        }
+        for e in &state.over_released {
+            writeln!(w, "over-release: {e}")?;
+        }
+        Ok(())
+    }
+
+    /// prints every unpaired event to stderr
+    #[cfg(feature = "std")]
+    pub fn print_unpaired(&self) {
+        let mut out = alloc::string::String::new();
+        _ = self.write_unpaired(&mut out);
+        std::eprint!("{out}");
+    }
+}
+
+impl RefTraceSink for PairingSink {
+    fn event(&self, event: RefEvent) {
+        if event.source != RefSource::Object || self.filter.is_some_and(|f| !f(&event)) {
+            return;
+        }
+        let mut state = self.state.lock();
+        let key = event.ptr as usize;
+        match event.op.strong_delta() {
+            1 => state.outstanding.entry(key).or_default().push(event),
+            -1 => {
+                let list = state.outstanding.get_mut(&key);
+                match list.and_then(|l| l.pop()) {
+                    Some(_) => {
+                        if state.outstanding.get(&key).is_some_and(Vec::is_empty) {
+                            state.outstanding.remove(&key);
+                        }
+                    }
+                    None => state.over_released.push(event),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::{boxed::Box, string::String};
+    use core::any::type_name;
+
+    use crate::{debug::*, object::*, sync::SpinLock, *};
+
+    #[object(IUnknown)]
+    #[derive(Debug)]
+    struct Traced {
+        a: u32,
+    }
+
+    fn traced(e: &RefEvent) -> bool {
+        e.type_name == type_name::<Traced>()
+    }
+
+    /// the sink is process wide
+    static SINK_TESTS: SpinLock<()> = SpinLock::new(());
+
+    #[test]
+    fn pairing_sink() {
+        let _serial = SINK_TESTS.lock();
+        let sink: &'static PairingSink = Box::leak(Box::new(PairingSink::with_filter(traced)));
+        set_trace_sink(Some(sink));
+
+        let a = Traced { a: 1 }.make_com();
+        drop(a.clone());
+        assert_eq!(sink.unreleased(), 1);
+        core::mem::forget(a.clone());
+        drop(a);
+        assert_eq!(sink.unreleased(), 1);
+        assert_eq!(sink.over_released(), 0);
+
+        sink.event(RefEvent {
+            ptr: 0x10 as *const (),
+            type_name: type_name::<Traced>(),
+            source: RefSource::Object,
+            op: RefOp::Release,
+            count: Some(u32::MAX),
+            #[cfg(feature = "std")]
+            backtrace: std::backtrace::Backtrace::disabled(),
+        });
+        assert_eq!(sink.over_released(), 1);
+
+        let mut out = String::new();
+        sink.write_unpaired(&mut out).unwrap();
+        assert!(out.contains("1 unreleased on"));
+        assert!(out.contains("over-release: Object Release"));
+
+        set_trace_sink(None);
+    }
+
+    #[derive(Default)]
+    struct Recorder(SpinLock<alloc::vec::Vec<RefEvent>>);
+
+    impl RefTraceSink for Recorder {
+        fn event(&self, event: RefEvent) {
+            self.0.lock().push(event);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn objects_report_their_own_changes() {
+        let _serial = SINK_TESTS.lock();
+        let sink: &'static Recorder = Box::leak(Box::default());
+        set_trace_sink(Some(sink));
+
+        let a = Traced { a: 1 }.make_com();
+        let b = a.clone();
+        drop(b);
+        set_trace_sink(None);
+        let events: alloc::vec::Vec<_> = sink
+            .0
+            .lock()
+            .iter()
+            .filter(|e| e.ptr == a.const_ptr() as *const ())
+            .map(|e| (e.source, e.op, e.count))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                (RefSource::Object, RefOp::Create, Some(1)),
+                (RefSource::Object, RefOp::AddRef, Some(2)),
+                (RefSource::Object, RefOp::Release, Some(1)),
+            ]
+        );
+    }
+}
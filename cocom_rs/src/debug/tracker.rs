@@ -0,0 +1,267 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{
+    any::type_name,
+    fmt,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{
+    impls,
+    object::{BorrowFlag, BorrowGuard},
+    sync::SpinLock,
+    visit::Visitor,
+};
+
+pub(super) struct Tracked {
+    pub type_name: &'static str,
+    allocator: &'static str,
+    serial: u64,
+    val: *const (),
+    strong: *const AtomicU32,
+    borrow: *const BorrowFlag,
+    trace: unsafe fn(*const (), *const BorrowFlag, &mut dyn Visitor) -> bool,
+}
+
+unsafe impl Send for Tracked {}
+
+impl Tracked {
+    pub fn strong_count(&self) -> u32 {
+        unsafe { (*self.strong).load(Ordering::Acquire) }
+    }
+
+    /// false, visiting nothing, while the value is inside a `&mut self` method
+    pub fn trace(&self, visitor: &mut dyn Visitor) -> bool {
+        unsafe { (self.trace)(self.val, self.borrow, visitor) }
+    }
+}
+
+struct Tracker {
+    next: u64,
+    live: BTreeMap<usize, Tracked>,
+}
+
+static TRACKER: SpinLock<Tracker> = SpinLock::new(Tracker {
+    next: 0,
+    live: BTreeMap::new(),
+});
+
+/// `ptr` is the interface pointer, `val`, `strong` and `borrow` live in the same block
+pub(crate) fn track<T: impls::Object>(
+    ptr: *const (),
+    val: *const T,
+    strong: *const AtomicU32,
+    borrow: *const BorrowFlag,
+) {
+    let mut tracker = TRACKER.lock();
+    let serial = tracker.next;
+    tracker.next += 1;
+    tracker.live.insert(
+        ptr as usize,
+        Tracked {
+            type_name: type_name::<T>(),
+            allocator: type_name::<T::Allocator>(),
+            serial,
+            val: val as _,
+            strong,
+            borrow,
+            // same as the vtable thunks, the flag only tracks objects with reentrancy checks
+            trace: |val, borrow, visitor| unsafe {
+                let _guard = match T::CHECK_REENTRANCY {
+                    true => match (*borrow).try_borrow() {
+                        Some(guard) => guard,
+                        None => return false,
+                    },
+                    false => BorrowGuard::unchecked(),
+                };
+                (*(val as *const T)).trace(visitor);
+                true
+            },
+        },
+    );
+}
+
+pub(crate) fn untrack(ptr: *const ()) {
+    TRACKER.lock().live.remove(&(ptr as usize));
+}
+
+/// Runs `f` over every live object by interface pointer.
+///
+/// The tracker stays locked meanwhile, so objects that are dying wait in `untrack` with their
+/// value still intact; `f` must not create or free objects.
+pub(super) fn with_live<R>(f: impl FnOnce(&BTreeMap<usize, Tracked>) -> R) -> R {
+    f(&TRACKER.lock().live)
+}
+
+/// Number of live objects of one concrete type in one allocator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveObjects {
+    pub type_name: &'static str,
+    pub allocator: &'static str,
+    pub count: usize,
+}
+
+fn collect(filter: impl Fn(&Tracked) -> bool) -> Vec<LiveObjects> {
+    let mut counts: BTreeMap<(&'static str, &'static str), usize> = BTreeMap::new();
+    for obj in TRACKER.lock().live.values().filter(|obj| filter(obj)) {
+        *counts.entry((obj.type_name, obj.allocator)).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .map(|((type_name, allocator), count)| LiveObjects {
+            type_name,
+            allocator,
+            count,
+        })
+        .collect()
+}
+
+/// Snapshot of every tracked object still alive, grouped by type and allocator
+pub fn live_objects() -> Vec<LiveObjects> {
+    collect(|_| true)
+}
+
+/// Objects that a [`LeakGuard`] found alive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeakReport(pub Vec<LiveObjects>);
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total: usize = self.0.iter().map(|o| o.count).sum();
+        write!(f, "{total} leaked objects")?;
+        for o in &self.0 {
+            write!(
+                f,
+                "\n  {} x {} (allocator {})",
+                o.count, o.type_name, o.allocator
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Fails with a [`LeakReport`] if objects created during its lifetime are still alive when
+/// it drops.
+///
+/// The tracker is process wide, so objects created on other threads in the meantime count
+/// too; [`LeakGuard::of`] narrows the check to one type for tests that run in parallel.
+#[derive(Debug)]
+pub struct LeakGuard {
+    start: u64,
+    type_name: Option<&'static str>,
+}
+
+impl Default for LeakGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LeakGuard {
+    pub fn new() -> Self {
+        Self {
+            start: TRACKER.lock().next,
+            type_name: None,
+        }
+    }
+
+    /// only checks objects of type `T`
+    pub fn of<T: impls::Object>() -> Self {
+        Self {
+            type_name: Some(type_name::<T>()),
+            ..Self::new()
+        }
+    }
+
+    /// objects created since the guard that are still alive
+    pub fn leaks(&self) -> Vec<LiveObjects> {
+        collect(|obj| obj.serial >= self.start && self.type_name.is_none_or(|t| t == obj.type_name))
+    }
+
+    /// consumes the guard without panicking
+    pub fn finish(self) -> Result<(), LeakReport> {
+        let leaks = self.leaks();
+        core::mem::forget(self);
+        if leaks.is_empty() {
+            Ok(())
+        } else {
+            Err(LeakReport(leaks))
+        }
+    }
+}
+
+impl Drop for LeakGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "std")]
+        if std::thread::panicking() {
+            return;
+        }
+        let leaks = self.leaks();
+        if !leaks.is_empty() {
+            panic!("{}", LeakReport(leaks));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{debug::*, object::*, *};
+
+    #[object(IUnknown)]
+    #[derive(Debug)]
+    struct Tracked {
+        a: u32,
+    }
+
+    #[object(IWeak)]
+    #[derive(Debug)]
+    struct TrackedWeak {
+        a: u32,
+    }
+
+    impl impls::IWeak for TrackedWeak {}
+
+    #[object(IUnknown)]
+    #[derive(Debug)]
+    struct Leaky {
+        a: u32,
+    }
+
+    #[test]
+    fn live_objects() {
+        let guard = LeakGuard::of::<Tracked>();
+        let a = Tracked { a: 1 }.make_object();
+        let b = Tracked { a: 2 }.make_com();
+        let live = debug::live_objects();
+        let tracked = live
+            .iter()
+            .find(|o| o.type_name == core::any::type_name::<Tracked>())
+            .unwrap();
+        assert_eq!(tracked.count, 2);
+        assert_eq!(tracked.allocator, "()");
+        drop(b);
+        assert_eq!(guard.leaks()[0].count, 1);
+        assert_eq!(a.try_unwrap().unwrap().a, 1);
+        assert!(guard.finish().is_ok());
+    }
+
+    #[test]
+    fn leak_report() {
+        let guard = LeakGuard::of::<TrackedWeak>();
+        let a = TrackedWeak { a: 1 }.make_object_weak();
+        let weak = a.downgrade();
+        core::mem::forget(a.clone());
+        drop(a);
+        let report = guard.finish().unwrap_err();
+        assert_eq!(report.0.len(), 1);
+        assert_eq!(report.0[0].count, 1);
+        assert!(alloc::format!("{report}").starts_with("1 leaked objects"));
+        assert!(weak.upgrade().is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "leaked objects")]
+    fn leak_guard_panics() {
+        let _guard = LeakGuard::of::<Leaky>();
+        core::mem::forget(Leaky { a: 1 }.make_com());
+    }
+}
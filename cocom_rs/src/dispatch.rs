@@ -0,0 +1,941 @@
+//! Late-bound calls by method name.
+//!
+//! `#[interface("..", dispatch)]` also describes the interface in an [`InterfaceInfo`]: method
+//! names, parameter kinds and vtable slots, plus a shim that calls a method with [`ComValue`]
+//! arguments. [`Dispatch`] puts an [`IDispatch`] on top of any object reached through such an
+//! interface. Parents of a described interface have to be described too.
+//!
+//! Values and metadata are Rust types, [`IDispatch`] is meant for callers built with the same
+//! crate version, not for foreign code.
+
+use alloc::{string::String, vec::Vec};
+use core::{ffi::c_void, ptr};
+
+use crate::{
+    ComError, ComPtr, ComResult, Guid, HResult, HResultE, IUnknown, IWeak, Interface, IntoHResult,
+    Out, Str8, Str16, StrAny,
+    object::MakeObject,
+    types::{B8, B32, Char8, Char16},
+};
+
+/// The kinds of the define model's `TypeKind`, with the same values
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TypeKind {
+    Unknown,
+    Interface,
+    Generic,
+    Struct,
+    Enum,
+    Ptr,
+    Ref,
+    Fn,
+    ComPtr,
+    Void,
+    Bool,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    Int128,
+    IntPtr,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    UInt128,
+    UIntPtr,
+    Float,
+    Double,
+    Char8,
+    Char16,
+    Guid,
+    HResult,
+    NSpan,
+    NRoSpan,
+    Str8,
+    Str16,
+    StrAny,
+}
+
+/// A dynamically typed argument or result
+#[derive(Debug, Clone, Default)]
+pub enum ComValue {
+    #[default]
+    Void,
+    Bool(bool),
+    Int8(i8),
+    Int16(i16),
+    Int32(i32),
+    Int64(i64),
+    Int128(i128),
+    IntPtr(isize),
+    UInt8(u8),
+    UInt16(u16),
+    UInt32(u32),
+    UInt64(u64),
+    UInt128(u128),
+    UIntPtr(usize),
+    Float(f32),
+    Double(f64),
+    Char8(u8),
+    Char16(u16),
+    Guid(Guid),
+    HResult(HResult),
+    /// also what a `StrAny` holding utf-8 reads as
+    Str8(String),
+    /// also what a `StrAny` holding utf-16 reads as
+    Str16(Vec<u16>),
+    Interface(Option<ComPtr<IUnknown>>),
+}
+
+impl ComValue {
+    pub fn kind(&self) -> TypeKind {
+        match self {
+            ComValue::Void => TypeKind::Void,
+            ComValue::Bool(_) => TypeKind::Bool,
+            ComValue::Int8(_) => TypeKind::Int8,
+            ComValue::Int16(_) => TypeKind::Int16,
+            ComValue::Int32(_) => TypeKind::Int32,
+            ComValue::Int64(_) => TypeKind::Int64,
+            ComValue::Int128(_) => TypeKind::Int128,
+            ComValue::IntPtr(_) => TypeKind::IntPtr,
+            ComValue::UInt8(_) => TypeKind::UInt8,
+            ComValue::UInt16(_) => TypeKind::UInt16,
+            ComValue::UInt32(_) => TypeKind::UInt32,
+            ComValue::UInt64(_) => TypeKind::UInt64,
+            ComValue::UInt128(_) => TypeKind::UInt128,
+            ComValue::UIntPtr(_) => TypeKind::UIntPtr,
+            ComValue::Float(_) => TypeKind::Float,
+            ComValue::Double(_) => TypeKind::Double,
+            ComValue::Char8(_) => TypeKind::Char8,
+            ComValue::Char16(_) => TypeKind::Char16,
+            ComValue::Guid(_) => TypeKind::Guid,
+            ComValue::HResult(_) => TypeKind::HResult,
+            ComValue::Str8(_) => TypeKind::Str8,
+            ComValue::Str16(_) => TypeKind::Str16,
+            ComValue::Interface(_) => TypeKind::Interface,
+        }
+    }
+
+    /// any integer variant, widened
+    fn integer(&self) -> Option<Result<i128, u128>> {
+        Some(Ok(match *self {
+            ComValue::Int8(v) => v as i128,
+            ComValue::Int16(v) => v as i128,
+            ComValue::Int32(v) => v as i128,
+            ComValue::Int64(v) => v as i128,
+            ComValue::Int128(v) => v,
+            ComValue::IntPtr(v) => v as i128,
+            ComValue::UInt8(v) => v as i128,
+            ComValue::UInt16(v) => v as i128,
+            ComValue::UInt32(v) => v as i128,
+            ComValue::UInt64(v) => v as i128,
+            ComValue::UInt128(v) => return Some(Err(v)),
+            ComValue::UIntPtr(v) => v as i128,
+            _ => return None,
+        }))
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// types
+
+/// A parameter or return type of a described method, borrowing from arguments for `'a`
+///
+/// Raw pointers are left out on purpose: a caller could pass any address as the argument.
+pub trait DispatchType<'a>: Sized {
+    const KIND: TypeKind;
+
+    /// fails with `InvalidData` for a value no variant can hold
+    fn into_value(self) -> ComResult<ComValue>;
+    /// fails with `TypeMismatch`, or `Overflow` for an integer out of range
+    fn from_value(value: &'a ComValue) -> ComResult<Self>;
+}
+
+fn mismatch() -> ComError {
+    HResultE::TypeMismatch.into()
+}
+
+impl<'a> DispatchType<'a> for () {
+    const KIND: TypeKind = TypeKind::Void;
+
+    fn into_value(self) -> ComResult<ComValue> {
+        Ok(ComValue::Void)
+    }
+
+    fn from_value(value: &'a ComValue) -> ComResult<Self> {
+        match value {
+            ComValue::Void => Ok(()),
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+impl<'a> DispatchType<'a> for bool {
+    const KIND: TypeKind = TypeKind::Bool;
+
+    fn into_value(self) -> ComResult<ComValue> {
+        Ok(ComValue::Bool(self))
+    }
+
+    fn from_value(value: &'a ComValue) -> ComResult<Self> {
+        match *value {
+            ComValue::Bool(v) => Ok(v),
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+/// integers take any integer variant that fits
+macro_rules! integers {
+    { $($t:ty => $kind:ident;)* } => { $(
+        impl<'a> DispatchType<'a> for $t {
+            const KIND: TypeKind = TypeKind::$kind;
+
+            fn into_value(self) -> ComResult<ComValue> {
+                Ok(ComValue::$kind(self))
+            }
+
+            fn from_value(value: &'a ComValue) -> ComResult<Self> {
+                let v = match value.integer().ok_or_else(mismatch)? {
+                    Ok(v) => <$t>::try_from(v).ok(),
+                    Err(v) => <$t>::try_from(v).ok(),
+                };
+                v.ok_or_else(|| HResultE::Overflow.into())
+            }
+        }
+    )* };
+}
+
+integers! {
+    i8 => Int8;
+    i16 => Int16;
+    i32 => Int32;
+    i64 => Int64;
+    i128 => Int128;
+    isize => IntPtr;
+    u8 => UInt8;
+    u16 => UInt16;
+    u32 => UInt32;
+    u64 => UInt64;
+    u128 => UInt128;
+    usize => UIntPtr;
+}
+
+impl<'a> DispatchType<'a> for f32 {
+    const KIND: TypeKind = TypeKind::Float;
+
+    fn into_value(self) -> ComResult<ComValue> {
+        Ok(ComValue::Float(self))
+    }
+
+    fn from_value(value: &'a ComValue) -> ComResult<Self> {
+        match *value {
+            ComValue::Float(v) => Ok(v),
+            ComValue::Double(v) => Ok(v as f32),
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+impl<'a> DispatchType<'a> for f64 {
+    const KIND: TypeKind = TypeKind::Double;
+
+    fn into_value(self) -> ComResult<ComValue> {
+        Ok(ComValue::Double(self))
+    }
+
+    fn from_value(value: &'a ComValue) -> ComResult<Self> {
+        match *value {
+            ComValue::Float(v) => Ok(v as f64),
+            ComValue::Double(v) => Ok(v),
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+impl<'a> DispatchType<'a> for B8 {
+    const KIND: TypeKind = TypeKind::Bool;
+
+    fn into_value(self) -> ComResult<ComValue> {
+        Ok(ComValue::Bool(self.get()))
+    }
+
+    fn from_value(value: &'a ComValue) -> ComResult<Self> {
+        bool::from_value(value).map(Self::new)
+    }
+}
+
+impl<'a> DispatchType<'a> for B32 {
+    const KIND: TypeKind = TypeKind::Bool;
+
+    fn into_value(self) -> ComResult<ComValue> {
+        Ok(ComValue::Bool(self.get()))
+    }
+
+    fn from_value(value: &'a ComValue) -> ComResult<Self> {
+        bool::from_value(value).map(Self::new)
+    }
+}
+
+impl<'a> DispatchType<'a> for Char8 {
+    const KIND: TypeKind = TypeKind::Char8;
+
+    fn into_value(self) -> ComResult<ComValue> {
+        Ok(ComValue::Char8(self.0))
+    }
+
+    fn from_value(value: &'a ComValue) -> ComResult<Self> {
+        match *value {
+            ComValue::Char8(v) => Ok(Self(v)),
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+impl<'a> DispatchType<'a> for Char16 {
+    const KIND: TypeKind = TypeKind::Char16;
+
+    fn into_value(self) -> ComResult<ComValue> {
+        Ok(ComValue::Char16(self.0))
+    }
+
+    fn from_value(value: &'a ComValue) -> ComResult<Self> {
+        match *value {
+            ComValue::Char16(v) => Ok(Self(v)),
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+impl<'a> DispatchType<'a> for Guid {
+    const KIND: TypeKind = TypeKind::Guid;
+
+    fn into_value(self) -> ComResult<ComValue> {
+        Ok(ComValue::Guid(self))
+    }
+
+    fn from_value(value: &'a ComValue) -> ComResult<Self> {
+        match *value {
+            ComValue::Guid(v) => Ok(v),
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+impl<'a> DispatchType<'a> for HResult {
+    const KIND: TypeKind = TypeKind::HResult;
+
+    fn into_value(self) -> ComResult<ComValue> {
+        Ok(ComValue::HResult(self))
+    }
+
+    fn from_value(value: &'a ComValue) -> ComResult<Self> {
+        match *value {
+            ComValue::HResult(v) => Ok(v),
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+impl<'a, I: Interface + crate::impls::RefCount> DispatchType<'a> for Option<ComPtr<I>> {
+    const KIND: TypeKind = TypeKind::ComPtr;
+
+    fn into_value(self) -> ComResult<ComValue> {
+        Ok(ComValue::Interface(
+            self.map(|obj| obj.query_interface::<IUnknown>().unwrap()),
+        ))
+    }
+
+    fn from_value(value: &'a ComValue) -> ComResult<Self> {
+        match value {
+            ComValue::Interface(obj) => {
+                Ok(obj.as_ref().map(|obj| obj.query_interface()).transpose()?)
+            }
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+/// a null interface is a `Pointer` error
+impl<'a, I: Interface + crate::impls::RefCount> DispatchType<'a> for ComPtr<I> {
+    const KIND: TypeKind = TypeKind::ComPtr;
+
+    fn into_value(self) -> ComResult<ComValue> {
+        Some(self).into_value()
+    }
+
+    fn from_value(value: &'a ComValue) -> ComResult<Self> {
+        Option::<Self>::from_value(value)?.ok_or_else(|| HResultE::Pointer.into())
+    }
+}
+
+/// invalid utf-8 reads as replacement characters
+impl<'a> DispatchType<'a> for Str8<'a> {
+    const KIND: TypeKind = TypeKind::Str8;
+
+    fn into_value(self) -> ComResult<ComValue> {
+        Ok(ComValue::Str8(
+            String::from_utf8_lossy(self.as_bytes()).into_owned(),
+        ))
+    }
+
+    fn from_value(value: &'a ComValue) -> ComResult<Self> {
+        match value {
+            ComValue::Str8(v) => Ok(v.into()),
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+impl<'a> DispatchType<'a> for Str16<'a> {
+    const KIND: TypeKind = TypeKind::Str16;
+
+    fn into_value(self) -> ComResult<ComValue> {
+        Ok(ComValue::Str16(self.as_slice().into()))
+    }
+
+    fn from_value(value: &'a ComValue) -> ComResult<Self> {
+        match value {
+            ComValue::Str16(v) => Ok(v.into()),
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+impl<'a> DispatchType<'a> for StrAny<'a> {
+    const KIND: TypeKind = TypeKind::StrAny;
+
+    /// a foreign kind that is neither string fails with `InvalidData`
+    fn into_value(self) -> ComResult<ComValue> {
+        match (self.as_str8(), self.as_str16()) {
+            (Some(s), _) => s.into_value(),
+            (_, Some(s)) => s.into_value(),
+            _ => Err(HResultE::InvalidData.into()),
+        }
+    }
+
+    fn from_value(value: &'a ComValue) -> ComResult<Self> {
+        match value {
+            ComValue::Str8(v) => Ok(v.into()),
+            ComValue::Str16(v) => Ok(v.into()),
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// metadata
+
+#[derive(Debug, Clone, Copy)]
+pub struct ParamInfo {
+    pub name: &'static str,
+    pub kind: TypeKind,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MethodInfo {
+    pub name: &'static str,
+    /// index in the vtable counting the parents' slots, also the dispatch id
+    pub slot: u32,
+    pub params: &'static [ParamInfo],
+    /// `async fn` returns its `IAsyncOperation`, an `Interface`
+    pub ret: TypeKind,
+}
+
+/// Calls method `index` of the interface `this` points to
+pub type InvokeFn = fn(this: *const c_void, index: usize, args: &[ComValue]) -> ComResult<ComValue>;
+
+/// What `#[interface("..", dispatch)]` knows about an interface
+#[derive(Debug, Clone, Copy)]
+pub struct InterfaceInfo {
+    pub name: &'static str,
+    pub iid: Guid,
+    pub parent: Option<&'static InterfaceInfo>,
+    /// own methods only, the parents' come first in the vtable
+    pub methods: &'static [MethodInfo],
+    pub invoke: InvokeFn,
+}
+
+impl InterfaceInfo {
+    /// this interface and its parents, most derived first
+    pub fn chain(&'static self) -> impl Iterator<Item = &'static InterfaceInfo> {
+        core::iter::successors(Some(self), |info| info.parent)
+    }
+
+    /// the method `name` and the interface declaring it
+    pub fn find(&'static self, name: &str) -> Option<(&'static InterfaceInfo, usize)> {
+        self.chain().find_map(|info| {
+            let index = info.methods.iter().position(|m| m.name == name)?;
+            Some((info, index))
+        })
+    }
+
+    /// the method in `slot` and the interface declaring it
+    pub fn find_slot(&'static self, slot: u32) -> Option<(&'static InterfaceInfo, usize)> {
+        self.chain().find_map(|info| {
+            let index = info.methods.iter().position(|m| m.slot == slot)?;
+            Some((info, index))
+        })
+    }
+}
+
+/// An interface described by `#[interface("..", dispatch)]`
+pub trait Dispatchable: Interface {
+    const INFO: &'static InterfaceInfo;
+}
+
+fn no_methods(this: *const c_void, index: usize, args: &[ComValue]) -> ComResult<ComValue> {
+    Err(HResultE::MemberNotFound.into())
+}
+
+impl Dispatchable for IUnknown {
+    const INFO: &'static InterfaceInfo = &InterfaceInfo {
+        name: "IUnknown",
+        iid: IUnknown::GUID,
+        parent: None,
+        methods: &[],
+        invoke: no_methods,
+    };
+}
+
+impl Dispatchable for IWeak {
+    const INFO: &'static InterfaceInfo = &InterfaceInfo {
+        name: "IWeak",
+        iid: IWeak::GUID,
+        parent: Some(IUnknown::INFO),
+        methods: &[],
+        invoke: no_methods,
+    };
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// IDispatch
+
+#[cocom::interface("9c3e51a7-2d84-4f6b-8e10-7a5d2c9b4f13")]
+pub trait IDispatch: IUnknown {
+    /// the dispatch id of the utf-8 method name `name[..len]`
+    fn GetIdOfName(&self, name: *const u8, len: usize, id: *mut u32) -> HResult;
+    /// calls method `id`, on success writes the result to the uninitialized `result`
+    fn Invoke(&self, id: u32, args: *const ComValue, argc: usize, result: *mut ComValue)
+    -> HResult;
+    fn GetInfo(&self) -> *const InterfaceInfo;
+}
+
+pub mod details {
+    use super::*;
+    pub use crate::details::*;
+
+    struct VT<T, V, O>(core::marker::PhantomData<(T, V, O)>);
+
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct VitualTable_IDispatch {
+        b: <IUnknown as Interface>::VitualTable,
+
+        pub f_GetIdOfName: unsafe extern "C" fn(
+            this: *const IDispatch,
+            name: *const u8,
+            len: usize,
+            id: *mut u32,
+        ) -> HResult,
+        pub f_Invoke: unsafe extern "C" fn(
+            this: *const IDispatch,
+            id: u32,
+            args: *const ComValue,
+            argc: usize,
+            result: *mut ComValue,
+        ) -> HResult,
+        pub f_GetInfo: unsafe extern "C" fn(this: *const IDispatch) -> *const InterfaceInfo,
+    }
+
+    impl<T: impls::IDispatch + impls::Object, O: impls::ObjectBox<Object = T>> VT<T, IDispatch, O>
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        pub const VTBL: VitualTable_IDispatch = VitualTable_IDispatch {
+            b: <IUnknown as Vtbl<O>>::VTBL,
+            f_GetIdOfName: Self::f_GetIdOfName,
+            f_Invoke: Self::f_Invoke,
+            f_GetInfo: Self::f_GetInfo,
+        };
+
+        unsafe extern "C" fn f_GetIdOfName(
+            this: *const IDispatch,
+            name: *const u8,
+            len: usize,
+            id: *mut u32,
+        ) -> HResult {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    return HResultE::IllegalMethodCall.into();
+                };
+                let Some(id) = Out::new(id) else {
+                    return HResultE::Pointer.into();
+                };
+                (*O::GetObject(this as _)).GetIdOfName(name, len, id)
+            }
+        }
+
+        unsafe extern "C" fn f_Invoke(
+            this: *const IDispatch,
+            id: u32,
+            args: *const ComValue,
+            argc: usize,
+            result: *mut ComValue,
+        ) -> HResult {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    return HResultE::IllegalMethodCall.into();
+                };
+                let Some(result) = Out::new(result) else {
+                    return HResultE::Pointer.into();
+                };
+                (*O::GetObject(this as _)).Invoke(id, args, argc, result)
+            }
+        }
+
+        unsafe extern "C" fn f_GetInfo(this: *const IDispatch) -> *const InterfaceInfo {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    crate::object::reentrant_call("IDispatch::GetInfo");
+                };
+                (*O::GetObject(this as _)).GetInfo()
+            }
+        }
+    }
+
+    impl<T: impls::IDispatch + impls::Object, O: impls::ObjectBox<Object = T>> Vtbl<O> for IDispatch
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        const VTBL: <IDispatch as Interface>::VitualTable = VT::<T, IDispatch, O>::VTBL;
+        const VTBL_REF: &'static <IDispatch as Interface>::VitualTable = &<Self as Vtbl<O>>::VTBL;
+
+        fn vtbl() -> &'static Self::VitualTable {
+            &<Self as Vtbl<O>>::VTBL
+        }
+    }
+
+    impl<T: impls::IDispatch + impls::Object, O: impls::ObjectBox<Object = T>> QuIn<IDispatch, T, O>
+        for IDispatch
+    {
+        #[inline(always)]
+        unsafe fn QueryInterface(
+            this: *mut T,
+            guid: Guid,
+            out: *mut *mut core::ffi::c_void,
+        ) -> HResult {
+            unsafe {
+                static GUID: Guid = IDispatch::GUID;
+                if guid == GUID {
+                    *out = this as _;
+                    O::AddRef(this as _);
+                    return HResultE::Ok.into();
+                }
+                <IUnknown as QuIn<IUnknown, T, O>>::QueryInterface(this, guid, out)
+            }
+        }
+    }
+}
+
+pub mod impls {
+    pub use crate::impls::*;
+    use crate::{HResult, Out};
+
+    use super::{ComValue, InterfaceInfo};
+
+    pub trait IDispatch: IUnknown {
+        fn GetIdOfName(&self, name: *const u8, len: usize, id: Out<u32>) -> HResult;
+        fn Invoke(
+            &self,
+            id: u32,
+            args: *const ComValue,
+            argc: usize,
+            result: Out<ComValue>,
+        ) -> HResult;
+        fn GetInfo(&self) -> *const InterfaceInfo;
+    }
+}
+
+impl IDispatch {
+    pub fn id_of_name(&self, name: &str) -> ComResult<u32> {
+        let mut id = 0;
+        let hr = self.GetIdOfName(name.as_ptr(), name.len(), &mut id);
+        hr.to_result()?;
+        Ok(id)
+    }
+
+    pub fn invoke(&self, id: u32, args: &[ComValue]) -> ComResult<ComValue> {
+        let mut result = core::mem::MaybeUninit::uninit();
+        let hr = self.Invoke(id, args.as_ptr(), args.len(), result.as_mut_ptr());
+        hr.to_result()?;
+        Ok(unsafe { result.assume_init() })
+    }
+
+    /// calls the method `name`
+    pub fn call(&self, name: &str, args: &[ComValue]) -> ComResult<ComValue> {
+        self.invoke(self.id_of_name(name)?, args)
+    }
+
+    pub fn info(&self) -> &'static InterfaceInfo {
+        unsafe { &*self.GetInfo() }
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// Dispatch
+
+/// [`IDispatch`] over an object, calling it through one described interface
+#[cocom::object(IDispatch)]
+pub struct Dispatch {
+    /// the described interface, held as its `IUnknown` base
+    target: ComPtr<IUnknown>,
+    info: &'static InterfaceInfo,
+}
+
+impl Dispatch {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<I: Dispatchable + crate::impls::RefCount>(obj: &ComPtr<I>) -> ComPtr<IDispatch> {
+        let target = unsafe { ComPtr::new_clone(obj.ptr().cast()) };
+        Dispatch {
+            target,
+            info: I::INFO,
+        }
+        .make_com()
+    }
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+impl impls::IDispatch for Dispatch {
+    fn GetIdOfName(&self, name: *const u8, len: usize, id: Out<u32>) -> HResult {
+        if name.is_null() {
+            return HResultE::Pointer.into();
+        }
+        let name = unsafe { core::slice::from_raw_parts(name, len) };
+        let Ok(name) = core::str::from_utf8(name) else {
+            return HResultE::InvalidArg.into();
+        };
+        match self.info.find(name) {
+            Some((info, index)) => {
+                id.write(info.methods[index].slot);
+                HResultE::Ok.into()
+            }
+            None => HResultE::UnknownName.into(),
+        }
+    }
+
+    fn Invoke(
+        &self,
+        id: u32,
+        args: *const ComValue,
+        argc: usize,
+        result: Out<ComValue>,
+    ) -> HResult {
+        if args.is_null() && argc != 0 {
+            return HResultE::Pointer.into();
+        }
+        let args = if argc == 0 {
+            &[][..]
+        } else {
+            unsafe { core::slice::from_raw_parts(args, argc) }
+        };
+        let Some((info, index)) = self.info.find_slot(id) else {
+            return HResultE::MemberNotFound.into();
+        };
+        match (info.invoke)(self.target.const_ptr() as _, index, args) {
+            Ok(value) => {
+                result.write(value);
+                HResultE::Ok.into()
+            }
+            Err(e) => e.into_hresult(),
+        }
+    }
+
+    fn GetInfo(&self) -> *const InterfaceInfo {
+        self.info
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::{
+        future::Future,
+        pin::pin,
+        sync::atomic::{AtomicI32, Ordering},
+        task::{Context, Poll, Waker},
+    };
+
+    use self::calc::{ICalc, IScaled, impls};
+    use crate::{dispatch::*, object::*, *};
+
+    mod calc {
+        use crate::{ComPtr, Guid, HResult, IUnknown, Interface, async_op::IAsyncOperation};
+
+        #[cocom::interface("4e8a2c61-7b3d-4f90-a5c1-2d6e9f0b3a77", dispatch)]
+        pub trait ICalc: IUnknown {
+            fn Add(&self, a: i32, b: i32) -> i32;
+            fn Divide(&self, a: f64, b: f64) -> HResult;
+            fn Last(&self) -> f64;
+            async fn Later(&self) -> ();
+        }
+
+        #[cocom::interface("4e8a2c61-7b3d-4f90-a5c1-2d6e9f0b3a78", dispatch)]
+        pub trait IScaled: ICalc {
+            fn Scale(&self, by: u8, other: ComPtr<IUnknown>) -> i64;
+        }
+
+        test_details! {
+            ICalc: IUnknown {
+                fn Add(&self, a: i32, b: i32) -> i32;
+                fn Divide(&self, a: f64, b: f64) -> HResult;
+                fn Last(&self) -> f64;
+                fn Later(&self, out: *mut *mut IAsyncOperation => OutComPtr) -> HResult;
+            }
+            IScaled: ICalc {
+                fn Scale(&self, by: u8, other: ComPtr<IUnknown>) -> i64;
+            }
+        }
+
+        pub mod impls {
+            pub use crate::impls::*;
+            use crate::{ComPtr, HResult, OutComPtr};
+
+            pub trait ICalc: IUnknown {
+                fn Add(&self, a: i32, b: i32) -> i32;
+                fn Divide(&self, a: f64, b: f64) -> HResult;
+                fn Last(&self) -> f64;
+                fn Later(&self, out: OutComPtr<super::IAsyncOperation>) -> HResult;
+            }
+
+            pub trait IScaled: ICalc {
+                fn Scale(&self, by: u8, other: ComPtr<super::IUnknown>) -> i64;
+            }
+        }
+    }
+
+    #[object(IScaled)]
+    struct Calc {
+        last: crate::sync::SpinLock<f64>,
+    }
+
+    impl impls::ICalc for Calc {
+        fn Add(&self, a: i32, b: i32) -> i32 {
+            a + b
+        }
+
+        fn Divide(&self, a: f64, b: f64) -> HResult {
+            if b == 0.0 {
+                return HResultE::InvalidArg.into();
+            }
+            *self.last.lock() = a / b;
+            HResultE::Ok.into()
+        }
+
+        fn Last(&self) -> f64 {
+            *self.last.lock()
+        }
+
+        fn Later(&self, out: OutComPtr<async_op::IAsyncOperation>) -> HResult {
+            async_op::start(out, async { Ok(()) })
+        }
+    }
+
+    impl impls::IScaled for Calc {
+        fn Scale(&self, by: u8, other: ComPtr<IUnknown>) -> i64 {
+            *self.last.lock() as i64 * by as i64
+        }
+    }
+
+    fn calc() -> ComPtr<IScaled> {
+        Calc {
+            last: crate::sync::SpinLock::new(0.0),
+        }
+        .make_com()
+    }
+
+    #[test]
+    fn metadata() {
+        let info = IScaled::INFO;
+        assert_eq!(info.name, "IScaled");
+        assert_eq!(info.iid, IScaled::GUID);
+        let names = info
+            .chain()
+            .map(|info| info.name)
+            .collect::<alloc::vec::Vec<_>>();
+        assert_eq!(names, ["IScaled", "ICalc", "IUnknown"]);
+
+        let add = &ICalc::INFO.methods[0];
+        assert_eq!((add.name, add.slot, add.ret), ("Add", 3, TypeKind::Int32));
+        assert_eq!(add.params[1].name, "b");
+        assert_eq!(add.params[1].kind, TypeKind::Int32);
+        assert_eq!(ICalc::INFO.methods[3].ret, TypeKind::Interface);
+
+        let scale = &info.methods[0];
+        assert_eq!(scale.slot, 7);
+        assert_eq!(scale.params[1].kind, TypeKind::ComPtr);
+        assert_eq!(
+            info.find("Divide").map(|(i, n)| (i.name, n)),
+            Some(("ICalc", 1))
+        );
+    }
+
+    #[test]
+    fn invoke_by_name() {
+        let calc = calc();
+        let disp = Dispatch::new(&calc);
+        assert_eq!(disp.info().name, "IScaled");
+
+        let r = disp.call("Add", &[ComValue::Int32(2), ComValue::UInt8(40)]);
+        assert!(matches!(r, Ok(ComValue::Int32(42))));
+        let r = disp.call("Divide", &[ComValue::Double(7.0), ComValue::Int32(2)]);
+        assert!(matches!(r, Err(e) if e == HResultE::TypeMismatch.into()));
+        let r = disp.call("Divide", &[ComValue::Double(7.0), ComValue::Float(2.0)]);
+        assert!(matches!(r, Ok(ComValue::HResult(hr)) if hr.is_success()));
+        assert!(matches!(disp.call("Last", &[]), Ok(ComValue::Double(3.5))));
+
+        let other = ComValue::Interface(Some(calc.query_interface().unwrap()));
+        let r = disp.call("Scale", &[ComValue::UInt8(2), other]);
+        assert!(matches!(r, Ok(ComValue::Int64(6))));
+        let r = disp.call("Scale", &[ComValue::Int32(300), ComValue::Interface(None)]);
+        assert!(matches!(r, Err(e) if e == HResultE::Overflow.into()));
+        let r = disp.call("Scale", &[ComValue::Int32(3), ComValue::Interface(None)]);
+        assert!(matches!(r, Err(e) if e == HResultE::Pointer.into()));
+
+        let r = disp.call("Add", &[ComValue::Int32(2)]);
+        assert!(matches!(r, Err(e) if e == HResultE::BadParamCount.into()));
+        let r = disp.call("Missing", &[]);
+        assert!(matches!(r, Err(e) if e == HResultE::UnknownName.into()));
+        let r = disp.invoke(1, &[]);
+        assert!(matches!(r, Err(e) if e == HResultE::MemberNotFound.into()));
+    }
+
+    #[test]
+    fn string_values() {
+        let value = ComValue::Str8("text".into());
+        assert_eq!(Str8::from_value(&value).unwrap().to_str(), Ok("text"));
+        assert_eq!(StrAny::from_value(&value).unwrap().kind(), StrKind::STR8);
+        assert!(Str16::from_value(&value).is_err());
+
+        let wide = ComValue::Str16("wide".encode_utf16().collect());
+        let any = StrAny::from_value(&wide).unwrap();
+        assert_eq!(any.kind(), StrKind::STR16);
+        assert!(matches!(any.into_value(), Ok(ComValue::Str16(v)) if v.len() == 4));
+        assert!(
+            matches!(Str8::from(&b"\xffa"[..]).into_value(), Ok(ComValue::Str8(s)) if s.ends_with('a'))
+        );
+
+        let foreign = unsafe { StrAny::from_raw_parts(ptr::null(), 0, StrKind(2)) };
+        assert!(matches!(foreign.into_value(), Err(e) if e == HResultE::InvalidData.into()));
+    }
+
+    #[test]
+    fn invoke_async() {
+        let disp = Dispatch::new(&calc());
+        let Ok(ComValue::Interface(Some(op))) = disp.call("Later", &[]) else {
+            panic!("no operation");
+        };
+        let op = op.query_interface::<async_op::IAsyncOperation>().unwrap();
+        assert_eq!(op.Status(), async_op::AsyncStatus::COMPLETED);
+        let mut call = pin!(async_op::AsyncCall::<()>::new(op));
+        let poll = call.as_mut().poll(&mut Context::from_waker(Waker::noop()));
+        assert!(matches!(poll, Poll::Ready(Ok(()))));
+    }
+}
@@ -0,0 +1,632 @@
+//! Error info carried next to an [`HResult`].
+//!
+//! Like `SetErrorInfo` / `GetErrorInfo`, each thread has one slot for the [`IErrorInfo`] of the
+//! last failed call: a description, its source, a help context and the error that caused it.
+//! Implementations fail with a [`ComError`] and the thunk leaves its info in the slot through
+//! [`IntoHResult`], a successful call clears it; the caller takes it back with
+//! [`HResult::to_result`]. Libraries loaded through [`Module`](crate::loader::Module) share the
+//! slot of the host, see [`ErrorInfoSlot`]. Without the `std` feature there is no slot of its own
+//! and the info is dropped, unless a host shared its slot.
+
+use alloc::string::String;
+use core::{
+    fmt::{Debug, Display},
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use crate::{
+    ComPtr, Guid, HResult, HResultE, IUnknown, Interface, OutComPtr, Str8, object,
+    object::MakeObject,
+};
+
+#[cocom::interface("3a9f6d24-81c5-4e07-b2d8-5c1e7a04f962")]
+pub trait IErrorInfo: IUnknown {
+    fn GetCode(&self) -> HResult;
+    /// utf-8, valid while the object lives
+    fn GetDescription(&self) -> Str8<'static>;
+    /// what raised the error, utf-8, valid while the object lives
+    fn GetSource(&self) -> Str8<'static>;
+    fn GetHelpContext(&self) -> u32;
+    /// the error that caused this one, null at the end of the chain
+    fn GetInner(&self, out: *mut *mut IErrorInfo) -> HResult;
+}
+
+pub mod details {
+    use super::*;
+    pub use crate::details::*;
+
+    struct VT<T, V, O>(core::marker::PhantomData<(T, V, O)>);
+
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct VitualTable_IErrorInfo {
+        b: <IUnknown as Interface>::VitualTable,
+
+        pub f_GetCode: unsafe extern "C" fn(this: *const IErrorInfo) -> HResult,
+        pub f_GetDescription: unsafe extern "C" fn(this: *const IErrorInfo) -> Str8<'static>,
+        pub f_GetSource: unsafe extern "C" fn(this: *const IErrorInfo) -> Str8<'static>,
+        pub f_GetHelpContext: unsafe extern "C" fn(this: *const IErrorInfo) -> u32,
+        pub f_GetInner:
+            unsafe extern "C" fn(this: *const IErrorInfo, out: *mut *mut IErrorInfo) -> HResult,
+    }
+
+    impl<T: impls::IErrorInfo + impls::Object, O: impls::ObjectBox<Object = T>> VT<T, IErrorInfo, O>
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        pub const VTBL: VitualTable_IErrorInfo = VitualTable_IErrorInfo {
+            b: <IUnknown as Vtbl<O>>::VTBL,
+            f_GetCode: Self::f_GetCode,
+            f_GetDescription: Self::f_GetDescription,
+            f_GetSource: Self::f_GetSource,
+            f_GetHelpContext: Self::f_GetHelpContext,
+            f_GetInner: Self::f_GetInner,
+        };
+
+        unsafe extern "C" fn f_GetCode(this: *const IErrorInfo) -> HResult {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    return HResultE::IllegalMethodCall.into();
+                };
+                (*O::GetObject(this as _)).GetCode()
+            }
+        }
+
+        unsafe extern "C" fn f_GetDescription(this: *const IErrorInfo) -> Str8<'static> {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    crate::object::reentrant_call("IErrorInfo::GetDescription");
+                };
+                (*O::GetObject(this as _)).GetDescription()
+            }
+        }
+
+        unsafe extern "C" fn f_GetSource(this: *const IErrorInfo) -> Str8<'static> {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    crate::object::reentrant_call("IErrorInfo::GetSource");
+                };
+                (*O::GetObject(this as _)).GetSource()
+            }
+        }
+
+        unsafe extern "C" fn f_GetHelpContext(this: *const IErrorInfo) -> u32 {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    crate::object::reentrant_call("IErrorInfo::GetHelpContext");
+                };
+                (*O::GetObject(this as _)).GetHelpContext()
+            }
+        }
+
+        unsafe extern "C" fn f_GetInner(
+            this: *const IErrorInfo,
+            out: *mut *mut IErrorInfo,
+        ) -> HResult {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    return HResultE::IllegalMethodCall.into();
+                };
+                let Some(out) = OutComPtr::new(out) else {
+                    return HResultE::Pointer.into();
+                };
+                (*O::GetObject(this as _)).GetInner(out)
+            }
+        }
+    }
+
+    impl<T: impls::IErrorInfo + impls::Object, O: impls::ObjectBox<Object = T>> Vtbl<O> for IErrorInfo
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        const VTBL: <IErrorInfo as Interface>::VitualTable = VT::<T, IErrorInfo, O>::VTBL;
+        const VTBL_REF: &'static <IErrorInfo as Interface>::VitualTable = &<Self as Vtbl<O>>::VTBL;
+
+        fn vtbl() -> &'static Self::VitualTable {
+            &<Self as Vtbl<O>>::VTBL
+        }
+    }
+
+    impl<T: impls::IErrorInfo + impls::Object, O: impls::ObjectBox<Object = T>>
+        QuIn<IErrorInfo, T, O> for IErrorInfo
+    {
+        #[inline(always)]
+        unsafe fn QueryInterface(
+            this: *mut T,
+            guid: Guid,
+            out: *mut *mut core::ffi::c_void,
+        ) -> HResult {
+            unsafe {
+                static GUID: Guid = IErrorInfo::GUID;
+                if guid == GUID {
+                    *out = this as _;
+                    O::AddRef(this as _);
+                    return HResultE::Ok.into();
+                }
+                <IUnknown as QuIn<IUnknown, T, O>>::QueryInterface(this, guid, out)
+            }
+        }
+    }
+}
+
+pub mod impls {
+    pub use crate::impls::*;
+    use crate::{HResult, OutComPtr, Str8};
+
+    pub trait IErrorInfo: IUnknown {
+        fn GetCode(&self) -> HResult;
+        fn GetDescription(&self) -> Str8<'static>;
+        fn GetSource(&self) -> Str8<'static>;
+        fn GetHelpContext(&self) -> u32;
+        fn GetInner(&self, out: OutComPtr<super::IErrorInfo>) -> HResult;
+    }
+}
+
+impl IErrorInfo {
+    pub fn code(&self) -> HResult {
+        self.GetCode()
+    }
+
+    /// empty when not valid utf-8
+    pub fn description(&self) -> &str {
+        self.GetDescription().to_str().unwrap_or_default()
+    }
+
+    /// empty when not valid utf-8
+    pub fn source(&self) -> &str {
+        self.GetSource().to_str().unwrap_or_default()
+    }
+
+    pub fn help_context(&self) -> u32 {
+        self.GetHelpContext()
+    }
+
+    pub fn inner(&self) -> Option<ComPtr<IErrorInfo>> {
+        let mut out = None;
+        self.GetInner(OutComPtr::from(&mut out).as_ptr());
+        out
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// ErrorInfo
+
+/// the [`IErrorInfo`] that [`ComError`] builds
+#[object(IErrorInfo)]
+#[derive(Debug)]
+struct ErrorInfo {
+    code: HResult,
+    description: String,
+    source: String,
+    help_context: u32,
+    inner: Option<ComPtr<IErrorInfo>>,
+}
+
+impl ErrorInfo {
+    fn blank(code: HResult) -> Self {
+        Self {
+            code,
+            description: String::new(),
+            source: String::new(),
+            help_context: 0,
+            inner: None,
+        }
+    }
+
+    fn read(info: &IErrorInfo) -> Self {
+        Self {
+            code: info.code(),
+            description: info.description().into(),
+            source: info.source().into(),
+            help_context: info.help_context(),
+            inner: info.inner(),
+        }
+    }
+}
+
+impl impls::IErrorInfo for ErrorInfo {
+    fn GetCode(&self) -> HResult {
+        self.code
+    }
+
+    fn GetDescription(&self) -> Str8<'static> {
+        unsafe { Str8::from_raw_parts(self.description.as_ptr(), self.description.len() as u32) }
+    }
+
+    fn GetSource(&self) -> Str8<'static> {
+        unsafe { Str8::from_raw_parts(self.source.as_ptr(), self.source.len() as u32) }
+    }
+
+    fn GetHelpContext(&self) -> u32 {
+        self.help_context
+    }
+
+    fn GetInner(&self, out: OutComPtr<IErrorInfo>) -> HResult {
+        out.write(self.inner.clone());
+        HResultE::Ok.into()
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// slot
+
+/// The error info slot as C entry points, both acting on the calling thread.
+///
+/// Every copy of this crate has a slot of its own. [`Module`](crate::loader::Module) hands the
+/// host's to each library built with [`export_module!`](crate::export_module) through its
+/// `DllShareErrorInfo` export, so info left by the library's thunks reaches the host.
+#[repr(C)]
+#[derive(Debug)]
+pub struct ErrorInfoSlot {
+    /// replaces the info, taking over the reference; null clears it
+    pub set: unsafe extern "C" fn(info: *mut IErrorInfo),
+    /// takes the info out, the reference goes to the caller; null if there is none
+    pub take: unsafe extern "C" fn() -> *mut IErrorInfo,
+}
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static SLOT: core::cell::RefCell<Option<ComPtr<IErrorInfo>>> =
+        const { core::cell::RefCell::new(None) };
+}
+
+static LOCAL: ErrorInfoSlot = ErrorInfoSlot {
+    set: local_set,
+    take: local_take,
+};
+
+/// the slot shared by whoever loaded this copy, null for its own
+static SHARED: AtomicPtr<ErrorInfoSlot> = AtomicPtr::new(ptr::null_mut());
+
+/// the slot is gone while the thread's locals are destroyed, the info is dropped then
+unsafe extern "C" fn local_set(info: *mut IErrorInfo) {
+    let info = NonNull::new(info).map(|info| unsafe { ComPtr::new(info) });
+    #[cfg(feature = "std")]
+    let _ = SLOT.try_with(|slot| drop(slot.replace(info)));
+    #[cfg(not(feature = "std"))]
+    drop(info);
+}
+
+unsafe extern "C" fn local_take() -> *mut IErrorInfo {
+    #[cfg(feature = "std")]
+    if let Ok(Some(info)) = SLOT.try_with(|slot| slot.take()) {
+        return info.leak();
+    }
+    ptr::null_mut()
+}
+
+/// the slot [`set_error_info`] and [`take_error_info`] use: the shared one, or this copy's own
+pub fn error_info_slot() -> &'static ErrorInfoSlot {
+    let shared = SHARED.load(Ordering::Acquire);
+    if shared.is_null() {
+        &LOCAL
+    } else {
+        unsafe { &*shared }
+    }
+}
+
+/// Routes the error info of this copy of the crate through `slot`
+///
+/// # Safety
+///
+/// the entry points of `slot` must stay callable while this copy is loaded
+pub unsafe fn share_error_info_slot(slot: &'static ErrorInfoSlot) {
+    // a copy handed its own slot back would forward to itself
+    if !ptr::eq(slot, &LOCAL) {
+        SHARED.store(ptr::from_ref(slot).cast_mut(), Ordering::Release);
+    }
+}
+
+/// replaces the error info of the current thread
+pub fn set_error_info(info: Option<ComPtr<IErrorInfo>>) {
+    let info = info.map_or(ptr::null_mut(), ComPtr::leak);
+    unsafe { (error_info_slot().set)(info) }
+}
+
+/// takes the error info of the current thread, leaving none
+pub fn take_error_info() -> Option<ComPtr<IErrorInfo>> {
+    NonNull::new(unsafe { (error_info_slot().take)() }).map(|info| unsafe { ComPtr::new(info) })
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// ComError
+
+/// result of a COM call, failing with a [`ComError`]
+pub type ComResult<T> = Result<T, ComError>;
+
+/// A failed [`HResult`] and the [`IErrorInfo`] describing it, if any
+#[derive(Clone)]
+pub struct ComError {
+    code: HResult,
+    info: Option<ComPtr<IErrorInfo>>,
+}
+
+impl ComError {
+    pub fn new(code: impl Into<HResult>, description: impl Into<String>) -> Self {
+        let code = code.into();
+        let info = ErrorInfo {
+            description: description.into(),
+            ..ErrorInfo::blank(code)
+        };
+        Self {
+            code,
+            info: Some(info.make_com()),
+        }
+    }
+
+    /// `code` with the info the current thread holds for it; info left by another code is
+    /// dropped
+    pub fn last(code: HResult) -> Self {
+        let info = take_error_info().filter(|info| info.code() == code);
+        Self { code, info }
+    }
+
+    pub const fn code(&self) -> HResult {
+        self.code
+    }
+
+    pub fn info(&self) -> Option<&ComPtr<IErrorInfo>> {
+        self.info.as_ref()
+    }
+
+    pub fn description(&self) -> &str {
+        self.info.as_ref().map_or("", |info| info.description())
+    }
+
+    pub fn source(&self) -> &str {
+        self.info.as_ref().map_or("", |info| info.source())
+    }
+
+    pub fn help_context(&self) -> u32 {
+        self.info.as_ref().map_or(0, |info| info.help_context())
+    }
+
+    pub fn inner(&self) -> Option<ComError> {
+        let info = self.info.as_ref()?.inner()?;
+        Some(Self {
+            code: info.code(),
+            info: Some(info),
+        })
+    }
+
+    pub fn with_source(self, source: impl Into<String>) -> Self {
+        self.edit(|info| info.source = source.into())
+    }
+
+    pub fn with_help_context(self, help_context: u32) -> Self {
+        self.edit(|info| info.help_context = help_context)
+    }
+
+    /// chains the error that caused this one
+    pub fn with_inner(self, inner: impl Into<ComError>) -> Self {
+        let inner = inner.into();
+        let inner = inner
+            .info
+            .unwrap_or_else(|| ErrorInfo::blank(inner.code).make_com());
+        self.edit(|info| info.inner = Some(inner))
+    }
+
+    /// info objects are immutable once shared, so this makes a new one
+    fn edit(self, f: impl FnOnce(&mut ErrorInfo)) -> Self {
+        let mut info = match &self.info {
+            Some(info) => ErrorInfo::read(info),
+            None => ErrorInfo::blank(self.code),
+        };
+        f(&mut info);
+        Self {
+            code: self.code,
+            info: Some(info.make_com()),
+        }
+    }
+}
+
+impl From<HResult> for ComError {
+    fn from(code: HResult) -> Self {
+        Self { code, info: None }
+    }
+}
+
+impl From<HResultE> for ComError {
+    fn from(code: HResultE) -> Self {
+        HResult::from(code).into()
+    }
+}
+
+impl From<ComError> for HResult {
+    fn from(value: ComError) -> Self {
+        value.code
+    }
+}
+
+/// compares codes, not info
+impl PartialEq for ComError {
+    fn eq(&self, other: &Self) -> bool {
+        self.code == other.code
+    }
+}
+
+impl Eq for ComError {}
+
+impl Debug for ComError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut d = f.debug_struct("ComError");
+        d.field("code", &self.code);
+        if let Some(info) = &self.info {
+            d.field("description", &info.description());
+            d.field("source", &info.source());
+        }
+        d.finish()
+    }
+}
+
+impl Display for ComError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:#010x}", self.code.value as u32)?;
+        let mut next = self.info.clone();
+        while let Some(info) = next {
+            if !info.description().is_empty() {
+                write!(f, ": {}", info.description())?;
+            }
+            next = info.inner();
+        }
+        Ok(())
+    }
+}
+
+impl core::error::Error for ComError {}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// thunks
+
+/// What a thunk returns for an implementation's result; errors leave their info in the slot,
+/// success codes clear it
+pub trait IntoHResult {
+    fn into_hresult(self) -> HResult;
+}
+
+impl IntoHResult for HResult {
+    fn into_hresult(self) -> HResult {
+        if self.is_success() {
+            set_error_info(None);
+        }
+        self
+    }
+}
+
+impl IntoHResult for ComError {
+    fn into_hresult(self) -> HResult {
+        set_error_info(self.info);
+        self.code
+    }
+}
+
+impl IntoHResult for ComResult<()> {
+    fn into_hresult(self) -> HResult {
+        match self {
+            Ok(()) => HResult::ok().into_hresult(),
+            Err(e) => e.into_hresult(),
+        }
+    }
+}
+
+/// keeps success codes other than `S_OK`
+impl IntoHResult for ComResult<HResult> {
+    fn into_hresult(self) -> HResult {
+        match self {
+            Ok(hr) => hr.into_hresult(),
+            Err(e) => e.into_hresult(),
+        }
+    }
+}
+
+impl HResult {
+    /// `Err` with the info the current thread holds for this code, see [`ComError::last`]
+    pub fn to_result(self) -> ComResult<()> {
+        if self.is_success() {
+            return Ok(());
+        }
+        Err(ComError::last(self))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::string::ToString;
+
+    use self::parser::{IParser, impls};
+    use crate::{error::*, object::*, *};
+
+    mod parser {
+        use crate::{Guid, HResult, IUnknown, Interface, Str8};
+
+        #[cocom::interface("6d2f8a41-c39e-4b57-8e0a-1f7c5b3d9e26")]
+        pub trait IParser: IUnknown {
+            fn Parse(&self, text: Str8, out: *mut u32) -> HResult;
+        }
+
+        test_details! {
+            IParser: IUnknown {
+                fn Parse(&self, text: Str8, out: *mut u32 => Out) -> HResult;
+            }
+        }
+
+        pub mod impls {
+            pub use crate::impls::*;
+            use crate::{ComResult, HResult, Out, Str8};
+
+            pub trait IParser: IUnknown {
+                fn Parse(&self, text: Str8, out: Out<u32>) -> ComResult<HResult>;
+            }
+        }
+    }
+
+    #[object(IParser)]
+    struct Parser;
+
+    impl impls::IParser for Parser {
+        /// `S_FALSE` for zero
+        fn Parse(&self, text: Str8, out: Out<u32>) -> ComResult<HResult> {
+            let text = text
+                .to_str()
+                .map_err(|_| ComError::new(HResultE::InvalidData, "not utf-8"))?;
+            let n = text.parse::<u32>().map_err(|e| {
+                ComError::new(HResultE::InvalidArg, "not a number")
+                    .with_source("Parser")
+                    .with_help_context(3)
+                    .with_inner(ComError::new(HResultE::Fail, e.to_string()))
+            })?;
+            out.write(n);
+            Ok(if n == 0 {
+                HResult::new(1)
+            } else {
+                HResult::ok()
+            })
+        }
+    }
+
+    #[test]
+    fn builder() {
+        let e = ComError::new(HResultE::InvalidArg, "outer")
+            .with_inner(ComError::new(HResultE::Overflow, "middle").with_inner(HResultE::Fail))
+            .with_source("test");
+        assert_eq!(e, HResultE::InvalidArg.into());
+        assert_eq!((e.description(), e.source()), ("outer", "test"));
+        let middle = e.inner().unwrap();
+        assert_eq!(middle.code(), HResultE::Overflow.into());
+        assert_eq!(middle.inner().unwrap().description(), "");
+        assert!(middle.inner().unwrap().inner().is_none());
+        assert_eq!(e.to_string(), "0x80070057: outer: middle");
+
+        let plain = ComError::from(HResultE::Abort);
+        assert!(plain.info().is_none());
+        assert_eq!(plain.clone().with_help_context(9).help_context(), 9);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn reported_through_the_thunk() {
+        let parser = Parser.make_com();
+        let mut n = 0;
+        assert_eq!(parser.Parse("12", &mut n).to_result(), Ok(()));
+        assert_eq!(n, 12);
+        assert_eq!(parser.Parse("0", &mut n), HResult::new(1));
+
+        let e = parser.Parse("twelve", &mut n).to_result().unwrap_err();
+        assert_eq!(e.code(), HResultE::InvalidArg.into());
+        assert_eq!((e.description(), e.source()), ("not a number", "Parser"));
+        assert_eq!(e.help_context(), 3);
+        assert_eq!(
+            e.inner().unwrap().description(),
+            "invalid digit found in string"
+        );
+        assert!(take_error_info().is_none());
+
+        // info left for another code is not attached
+        assert!(parser.Parse("-1", &mut n).is_failure());
+        let e = HResult::from(HResultE::Pointer).to_result().unwrap_err();
+        assert!(e.info().is_none());
+        assert!(take_error_info().is_none());
+
+        // nor is info from a failure the caller ignored
+        assert!(parser.Parse("-1", &mut n).is_failure());
+        assert_eq!(parser.Parse("1", &mut n), HResult::ok());
+        assert!(take_error_info().is_none());
+    }
+}
@@ -0,0 +1,285 @@
+use alloc::collections::BTreeMap;
+use core::{
+    fmt::Debug,
+    marker::PhantomData,
+    ptr::{self, NonNull},
+};
+
+use crate::{
+    ComPtr, ComResult, Guid, HResult, HResultE, IUnknown, Interface, OutComPtr, module, object,
+    object::MakeObject, sync::SpinLock, types::B32,
+};
+
+#[cocom::interface("00000001-0000-0000-c000-000000000046")]
+pub trait IClassFactory: IUnknown {
+    fn CreateInstance(
+        &self,
+        outer: *mut IUnknown,
+        iid: *const Guid,
+        out: *mut *mut IUnknown,
+    ) -> HResult;
+    fn LockServer(&self, lock: B32) -> HResult;
+}
+
+pub mod details {
+    use super::*;
+    pub use crate::details::*;
+
+    struct VT<T, V, O>(core::marker::PhantomData<(T, V, O)>);
+
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct VitualTable_IClassFactory {
+        b: <IUnknown as Interface>::VitualTable,
+
+        pub f_CreateInstance: unsafe extern "C" fn(
+            this: *const IClassFactory,
+            outer: *mut IUnknown,
+            iid: *const Guid,
+            out: *mut *mut IUnknown,
+        ) -> HResult,
+        pub f_LockServer: unsafe extern "C" fn(this: *const IClassFactory, lock: B32) -> HResult,
+    }
+
+    impl<T: impls::IClassFactory + impls::Object, O: impls::ObjectBox<Object = T>>
+        VT<T, IClassFactory, O>
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        pub const VTBL: VitualTable_IClassFactory = VitualTable_IClassFactory {
+            b: <IUnknown as Vtbl<O>>::VTBL,
+            f_CreateInstance: Self::f_CreateInstance,
+            f_LockServer: Self::f_LockServer,
+        };
+
+        unsafe extern "C" fn f_CreateInstance(
+            this: *const IClassFactory,
+            outer: *mut IUnknown,
+            iid: *const Guid,
+            out: *mut *mut IUnknown,
+        ) -> HResult {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    return HResultE::IllegalMethodCall.into();
+                };
+                let Some(out) = OutComPtr::new(out) else {
+                    return HResultE::Pointer.into();
+                };
+                (*O::GetObject(this as _)).CreateInstance(outer, iid, out)
+            }
+        }
+
+        unsafe extern "C" fn f_LockServer(this: *const IClassFactory, lock: B32) -> HResult {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    return HResultE::IllegalMethodCall.into();
+                };
+                (*O::GetObject(this as _)).LockServer(lock)
+            }
+        }
+    }
+
+    impl<T: impls::IClassFactory + impls::Object, O: impls::ObjectBox<Object = T>> Vtbl<O>
+        for IClassFactory
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        const VTBL: <IClassFactory as Interface>::VitualTable = VT::<T, IClassFactory, O>::VTBL;
+        const VTBL_REF: &'static <IClassFactory as Interface>::VitualTable =
+            &<Self as Vtbl<O>>::VTBL;
+
+        fn vtbl() -> &'static Self::VitualTable {
+            &<Self as Vtbl<O>>::VTBL
+        }
+    }
+
+    impl<T: impls::IClassFactory + impls::Object, O: impls::ObjectBox<Object = T>>
+        QuIn<IClassFactory, T, O> for IClassFactory
+    {
+        #[inline(always)]
+        unsafe fn QueryInterface(
+            this: *mut T,
+            guid: Guid,
+            out: *mut *mut core::ffi::c_void,
+        ) -> HResult {
+            unsafe {
+                static GUID: Guid = IClassFactory::GUID;
+                if guid == GUID {
+                    *out = this as _;
+                    O::AddRef(this as _);
+                    return HResultE::Ok.into();
+                }
+                <IUnknown as QuIn<IUnknown, T, O>>::QueryInterface(this, guid, out)
+            }
+        }
+    }
+}
+
+pub mod impls {
+    pub use crate::impls::*;
+    use crate::{Guid, HResult, OutComPtr, types::B32};
+
+    pub trait IClassFactory: IUnknown {
+        fn CreateInstance(
+            &self,
+            outer: *mut super::IUnknown,
+            iid: *const Guid,
+            out: OutComPtr<super::IUnknown>,
+        ) -> HResult;
+        fn LockServer(&self, lock: B32) -> HResult;
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// factory
+
+/// `IClassFactory` creating `T::default()`, aggregation is not supported
+#[object(IClassFactory)]
+pub struct ClassFactory<T> {
+    _p: PhantomData<fn() -> T>,
+}
+
+impl<T> ClassFactory<T> {
+    pub const fn new() -> Self {
+        Self { _p: PhantomData }
+    }
+}
+
+impl<T> Default for ClassFactory<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Debug for ClassFactory<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ClassFactory")
+            .field("class", &core::any::type_name::<T>())
+            .finish()
+    }
+}
+
+impl<T> impls::IClassFactory for ClassFactory<T>
+where
+    T: Default + crate::impls::Object + MakeObject<ComOutput = ComPtr<T::Interface>>,
+    T::Interface: crate::impls::RefCount,
+{
+    fn CreateInstance(
+        &self,
+        outer: *mut IUnknown,
+        iid: *const Guid,
+        out: OutComPtr<IUnknown>,
+    ) -> HResult {
+        if iid.is_null() {
+            return HResultE::Pointer.into();
+        }
+        if !outer.is_null() {
+            return HResultE::NoAggregation.into();
+        }
+        T::default()
+            .make_com()
+            .as_unknown()
+            .QueryInterface(iid, out.as_ptr() as _)
+    }
+
+    fn LockServer(&self, lock: B32) -> HResult {
+        if lock.get() {
+            module::lock();
+        } else {
+            module::unlock();
+        }
+        HResultE::Ok.into()
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// registry
+
+static CLASSES: SpinLock<BTreeMap<Guid, ComPtr<IClassFactory>>> = SpinLock::new(BTreeMap::new());
+
+/// Makes `factory` the process-wide factory for `clsid`, returns the one it replaces
+pub fn register_class(
+    clsid: Guid,
+    factory: ComPtr<IClassFactory>,
+) -> Option<ComPtr<IClassFactory>> {
+    CLASSES.lock().insert(clsid, factory)
+}
+
+/// [`register_class`] with a [`ClassFactory<T>`]
+pub fn register_default<T>(clsid: Guid) -> Option<ComPtr<IClassFactory>>
+where
+    T: Default + crate::impls::Object + MakeObject<ComOutput = ComPtr<T::Interface>>,
+    T::Interface: crate::impls::RefCount,
+{
+    register_class(clsid, ClassFactory::<T>::new().make_com())
+}
+
+/// Removes the factory for `clsid`, objects it already created stay alive
+pub fn revoke_class(clsid: &Guid) -> Option<ComPtr<IClassFactory>> {
+    CLASSES.lock().remove(clsid)
+}
+
+/// The factory registered for `clsid`, `ClassNotRegistered` if there is none
+pub fn get_class_object(clsid: &Guid) -> ComResult<ComPtr<IClassFactory>> {
+    CLASSES
+        .lock()
+        .get(clsid)
+        .cloned()
+        .ok_or_else(|| HResultE::ClassNotRegistered.into())
+}
+
+/// Creates an instance of the class `clsid` through its registered factory, as an `I`
+pub fn create_instance<I: Interface + crate::impls::RefCount>(
+    clsid: &Guid,
+) -> ComResult<ComPtr<I>> {
+    let factory = get_class_object(clsid)?;
+    let mut out = ptr::null_mut();
+    let r = factory.CreateInstance(ptr::null_mut(), &I::GUID, &mut out);
+    r.to_result()?;
+    match NonNull::new(out) {
+        Some(out) => Ok(unsafe { ComPtr::new(out.cast()) }),
+        None => Err(HResultE::Pointer.into()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::factory::impls;
+    use crate::{factory::*, object::*, *};
+
+    #[object(IUnknown)]
+    #[derive(Debug, Default)]
+    pub struct Widget {
+        a: u32,
+    }
+
+    const WIDGET: Guid = Guid::from_str("5b0a3b4e-2f7c-4f0e-9a43-0d6a8c1e7f21").unwrap();
+    const MISSING: Guid = Guid::from_str("5b0a3b4e-2f7c-4f0e-9a43-0d6a8c1e7f22").unwrap();
+
+    #[test]
+    fn create_registered() {
+        assert!(register_default::<Widget>(WIDGET).is_none());
+        let a = create_instance::<IUnknown>(&WIDGET).unwrap();
+        let b = create_instance::<IUnknown>(&WIDGET).unwrap();
+        assert_ne!(a.const_ptr() as *const (), b.const_ptr() as *const ());
+        assert_eq!(a.downcast::<Widget>().unwrap().a, 0);
+        assert_eq!(
+            create_instance::<IWeak>(&WIDGET).unwrap_err().code(),
+            HResult::from(HResultE::NoInterface)
+        );
+        assert_eq!(
+            create_instance::<IUnknown>(&MISSING).unwrap_err().code(),
+            HResult::from(HResultE::ClassNotRegistered)
+        );
+
+        let factory = get_class_object(&WIDGET).unwrap();
+        let mut out = core::ptr::null_mut();
+        let outer = b.const_ptr() as *mut IUnknown;
+        assert_eq!(
+            factory.CreateInstance(outer, &IUnknown::GUID, &mut out),
+            HResult::from(HResultE::NoAggregation)
+        );
+        assert!(out.is_null());
+
+        assert!(revoke_class(&WIDGET).is_some());
+        assert!(create_instance::<IUnknown>(&WIDGET).is_err());
+    }
+}
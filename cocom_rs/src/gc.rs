@@ -0,0 +1,398 @@
+//! Trial-deletion cycle collector for `Object<T>`, enabled by the `gc` feature.
+//!
+//! Every `Release` of an [`Object`](crate::object::Object) that does not reach zero buffers the
+//! object as a possible cycle root. [`collect`] then runs the synchronous Bacon-Rajan
+//! algorithm over the buffered objects and everything they reach through
+//! [`impls::Object::trace`]: internal references are subtracted from copies of the strong
+//! counts, anything left with a nonzero count is held from outside and keeps what it reaches
+//! alive, and the rest is garbage.
+//!
+//! Objects implemented in C++, `WeakObject`s and objects whose references are not visible to
+//! `trace` are never collected; references they hold count as external roots. The collector
+//! trusts `trace` to report only references the value owns.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{object::FINALIZING, sync::SpinLock, visit::Visitor};
+
+/// Type erased operations on one `Object<T>`, all taking the object pointer
+pub(crate) struct Hooks {
+    pub trace: unsafe fn(*const (), &mut dyn Visitor),
+    pub final_release: unsafe fn(*mut ()),
+    /// unregisters the object and drops the value in place, the block stays allocated
+    pub drop_value: unsafe fn(*mut ()),
+    /// a regular `Release`
+    pub release: unsafe fn(*mut ()),
+    /// frees the block of an object whose value was dropped
+    pub free: unsafe fn(*mut ()),
+}
+
+#[derive(Clone, Copy)]
+struct Node {
+    strong: *const AtomicU32,
+    hooks: &'static Hooks,
+}
+
+impl Node {
+    fn strong_count(&self) -> u32 {
+        unsafe { (*self.strong).load(Ordering::Acquire) }
+    }
+}
+
+/// object pointer, node and the count including the collector's reference
+type Pinned = (usize, Node, u32);
+
+struct Heap {
+    nodes: BTreeMap<usize, Node>,
+    candidates: BTreeSet<usize>,
+}
+
+unsafe impl Send for Heap {}
+
+/// only held for bookkeeping, never while calling into an object: `trace` may lock fields whose
+/// holders release references and land in [`possible_root`]
+static HEAP: SpinLock<Heap> = SpinLock::new(Heap {
+    nodes: BTreeMap::new(),
+    candidates: BTreeSet::new(),
+});
+
+/// one [`collect`] at a time
+static COLLECTING: SpinLock<()> = SpinLock::new(());
+
+pub(crate) fn register(ptr: *const (), strong: *const AtomicU32, hooks: &'static Hooks) {
+    HEAP.lock()
+        .nodes
+        .insert(ptr as usize, Node { strong, hooks });
+}
+
+pub(crate) fn unregister(ptr: *const ()) {
+    let mut heap = HEAP.lock();
+    heap.nodes.remove(&(ptr as usize));
+    heap.candidates.remove(&(ptr as usize));
+}
+
+/// a release left the object alive, it may be part of a garbage cycle now
+pub(crate) fn possible_root(ptr: *const ()) {
+    HEAP.lock().candidates.insert(ptr as usize);
+}
+
+/// number of objects waiting to be looked at by the next [`collect`]
+pub fn candidates() -> usize {
+    HEAP.lock().candidates.len()
+}
+
+/// Frees unreachable cycles among the buffered objects, returns how many objects were freed.
+///
+/// Garbage objects all get [`final_release`](crate::impls::Object::final_release) before any
+/// value is dropped, and all values are dropped before any block is freed, so destructors may
+/// still touch each other. If a strong count moves while the collector decides, or a
+/// `final_release` resurrects its object, the pass is abandoned and the candidates stay
+/// buffered. Once the values start dropping, weak references to the garbage no longer upgrade.
+///
+/// # Safety
+///
+/// No other thread may change the fields reported by `trace` while this runs. A reference
+/// moved out of a traced field keeps the strong count as it was, so the collector cannot
+/// notice it and frees the target while it is still referenced.
+pub unsafe fn collect() -> usize {
+    let _pass = COLLECTING.lock();
+    let roots = core::mem::take(&mut HEAP.lock().candidates);
+    let garbage = match find_garbage(&roots) {
+        Ok(garbage) => garbage,
+        Err(pinned) => {
+            HEAP.lock().candidates.extend(roots);
+            unpin(&pinned);
+            return 0;
+        }
+    };
+    for (ptr, node, _) in &garbage {
+        unsafe { (node.hooks.final_release)(*ptr as _) };
+    }
+    // a hook may drop references into the garbage, but not hand out new ones
+    if garbage
+        .iter()
+        .any(|(_, node, pinned)| node.strong_count() > *pinned)
+    {
+        HEAP.lock()
+            .candidates
+            .extend(garbage.iter().map(|&(ptr, _, _)| ptr));
+        unpin(&garbage);
+        return 0;
+    }
+    // from here on upgrades through weak references must fail, the values still release
+    // their references into each other so the counts keep going down
+    for (i, (_, node, pinned)) in garbage.iter().enumerate() {
+        let r = unsafe {
+            (*node.strong).compare_exchange(
+                *pinned,
+                *pinned | FINALIZING,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            )
+        };
+        if r.is_err() {
+            for (_, node, _) in &garbage[..i] {
+                unsafe { (*node.strong).fetch_and(!FINALIZING, Ordering::Release) };
+            }
+            HEAP.lock()
+                .candidates
+                .extend(garbage.iter().map(|&(ptr, _, _)| ptr));
+            unpin(&garbage);
+            return 0;
+        }
+    }
+    for (ptr, node, _) in &garbage {
+        unsafe { (node.hooks.drop_value)(*ptr as _) };
+    }
+    // releases between the values buffered garbage that is already unregistered
+    {
+        let mut heap = HEAP.lock();
+        for (ptr, _, _) in &garbage {
+            heap.candidates.remove(ptr);
+        }
+    }
+    for (ptr, node, _) in &garbage {
+        debug_assert_eq!(
+            node.strong_count(),
+            FINALIZING | 1,
+            "collected object is still referenced"
+        );
+        unsafe { (node.hooks.free)(*ptr as _) };
+    }
+    garbage.len()
+}
+
+/// Takes a collector reference on a registered object, returns its node and the count before.
+///
+/// Dying objects (count zero) and objects being finalized are left out. The heap lock keeps
+/// the object from being unregistered and freed between the lookup and the increment.
+fn pin(ptr: usize) -> Option<(Node, u32)> {
+    let heap = HEAP.lock();
+    let node = *heap.nodes.get(&ptr)?;
+    let seen = unsafe {
+        (*node.strong).fetch_update(Ordering::Acquire, Ordering::Relaxed, |c| {
+            (c != 0 && c & FINALIZING == 0).then_some(c + 1)
+        })
+    };
+    Some((node, seen.ok()?))
+}
+
+/// drops collector references, going through `Release` only for the last one so live objects
+/// are not buffered as candidates again
+fn unpin(pinned: &[Pinned]) {
+    for (ptr, node, _) in pinned {
+        let r = unsafe {
+            (*node.strong).fetch_update(Ordering::Release, Ordering::Relaxed, |c| {
+                (c & !FINALIZING > 1).then_some(c - 1)
+            })
+        };
+        if r.is_err() {
+            unsafe { (node.hooks.release)(*ptr as _) };
+        }
+    }
+}
+
+/// Trial deletion over the subgraph reachable from `roots`.
+///
+/// Every object of the subgraph is pinned with an extra strong reference before it is traced,
+/// so nothing is freed under the collector. Garbage comes back still pinned, next to its
+/// pinned count, the rest is unpinned. If a count moved since it was pinned, all pinned
+/// objects are returned as the error.
+fn find_garbage(roots: &BTreeSet<usize>) -> Result<Vec<Pinned>, Vec<Pinned>> {
+    // the counts of the subgraph as they were before pinning
+    let mut counts: BTreeMap<usize, (Node, u32)> = BTreeMap::new();
+    let mut edges: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    let mut seen: BTreeSet<usize> = BTreeSet::new();
+    let mut queue: Vec<usize> = roots.iter().copied().collect();
+    while let Some(ptr) = queue.pop() {
+        if !seen.insert(ptr) {
+            continue;
+        }
+        let Some((node, strong)) = pin(ptr) else {
+            continue;
+        };
+        counts.insert(ptr, (node, strong));
+        let mut out = Vec::new();
+        let mut visit = |target: *const ()| {
+            out.push(target as usize);
+            queue.push(target as usize);
+        };
+        unsafe { (node.hooks.trace)(ptr as _, &mut visit) };
+        edges.insert(ptr, out);
+    }
+    let pinned = |filter: &dyn Fn(&usize) -> bool| -> Vec<Pinned> {
+        counts
+            .iter()
+            .filter(|(p, _)| filter(p))
+            .map(|(&p, &(node, seen))| (p, node, seen + 1))
+            .collect()
+    };
+
+    // subtract the references held inside the subgraph
+    let mut trial: BTreeMap<usize, u32> = counts.iter().map(|(&p, &(_, c))| (p, c)).collect();
+    for t in edges.values().flatten() {
+        if let Some(c) = trial.get_mut(t) {
+            *c = c.saturating_sub(1);
+        }
+    }
+
+    // whatever is still referenced from outside keeps everything it reaches
+    let mut live: BTreeSet<usize> = BTreeSet::new();
+    let mut stack: Vec<usize> = trial
+        .iter()
+        .filter(|&(_, &c)| c > 0)
+        .map(|(&p, _)| p)
+        .collect();
+    while let Some(ptr) = stack.pop() {
+        if live.insert(ptr) {
+            stack.extend(edges[&ptr].iter().filter(|t| counts.contains_key(t)));
+        }
+    }
+
+    let garbage = pinned(&|p| !live.contains(p));
+    if garbage
+        .iter()
+        .any(|(_, node, pinned)| node.strong_count() != *pinned)
+    {
+        return Err(pinned(&|_| true));
+    }
+    unpin(&pinned(&|p| live.contains(p)));
+    Ok(garbage)
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    use crate::{
+        gc,
+        object::*,
+        sync::SpinLock,
+        weak_ref::{self, ComWeakAny, IWeakReference, IWeakReferenceSource, WeakRefSource},
+        *,
+    };
+
+    #[object(IUnknown)]
+    struct Ring {
+        next: SpinLock<Option<ComPtr<IUnknown>>>,
+        dropped: Arc<AtomicU32>,
+    }
+
+    impl Drop for Ring {
+        fn drop(&mut self) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn ring(dropped: &Arc<AtomicU32>) -> ObjectPtr<Ring> {
+        Ring {
+            next: SpinLock::new(None),
+            dropped: dropped.clone(),
+        }
+        .make_object()
+    }
+
+    #[test]
+    fn collects_cycles() {
+        let dropped = Arc::new(AtomicU32::new(0));
+        let a = ring(&dropped);
+        let b = ring(&dropped);
+        let c = ring(&dropped);
+        *a.next.lock() = Some(b.as_com().clone());
+        *b.next.lock() = Some(c.as_com().clone());
+        *c.next.lock() = Some(a.as_com().clone());
+        let held = ring(&dropped);
+        let d = ring(&dropped);
+        *held.next.lock() = Some(d.as_com().clone());
+        *d.next.lock() = Some(held.as_com().clone());
+        // `held` stays referenced from outside, so its cycle must survive
+        drop((a, b, c, d));
+
+        unsafe { gc::collect() };
+        assert_eq!(dropped.load(Ordering::Relaxed), 3);
+        assert_eq!(held.strong_count(), 2);
+
+        *held.next.lock() = None;
+        drop(held);
+        unsafe { gc::collect() };
+        assert_eq!(dropped.load(Ordering::Relaxed), 5);
+    }
+
+    #[test]
+    fn traces_without_the_heap_lock() {
+        extern crate std;
+        use std::{thread, time::Duration};
+
+        let dropped = Arc::new(AtomicU32::new(0));
+        let a = ring(&dropped);
+        let b = ring(&dropped);
+        *a.next.lock() = Some(b.as_com().clone());
+        drop(a.as_com().clone());
+
+        // the collector blocks on `a.next` while this thread releases a reference under it
+        let mut next = a.next.lock();
+        let collector = thread::spawn(|| unsafe { gc::collect() });
+        thread::sleep(Duration::from_millis(50));
+        drop(b.as_com().clone());
+        *next = None;
+        drop(next);
+        collector.join().unwrap();
+        assert_eq!(b.strong_count(), 1);
+        assert_eq!(dropped.load(Ordering::Relaxed), 0);
+    }
+
+    #[object(IWeakReferenceSource)]
+    struct Watched {
+        next: SpinLock<Option<ComPtr<IUnknown>>>,
+        peer: SpinLock<Option<ComWeakAny<IWeakReferenceSource>>>,
+        weak: WeakRefSource,
+        upgraded: Arc<AtomicU32>,
+    }
+
+    impl weak_ref::impls::IWeakReferenceSource for Watched {
+        fn GetWeakReference(&self, out: OutComPtr<IWeakReference>) -> HResult {
+            unsafe { self.weak.get_weak_reference(self, out) }
+        }
+    }
+
+    impl Drop for Watched {
+        fn drop(&mut self) {
+            // the peer is garbage too, whether or not its value is gone yet
+            if let Some(peer) = self.peer.get_mut().take()
+                && peer.upgrade().is_some()
+            {
+                self.upgraded.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[test]
+    fn no_upgrade_into_garbage() {
+        let upgraded = Arc::new(AtomicU32::new(0));
+        let watched = || {
+            Watched {
+                next: SpinLock::new(None),
+                peer: SpinLock::new(None),
+                weak: WeakRefSource::new(),
+                upgraded: upgraded.clone(),
+            }
+            .make_object()
+        };
+        let a = watched();
+        let b = watched();
+        *a.next.lock() = Some(b.as_com().query_interface().unwrap());
+        *b.next.lock() = Some(a.as_com().query_interface().unwrap());
+        *a.peer.lock() = Some(b.as_com().downgrade_any().unwrap());
+        *b.peer.lock() = Some(a.as_com().downgrade_any().unwrap());
+        drop((a, b));
+
+        unsafe { gc::collect() };
+        assert_eq!(upgraded.load(Ordering::Relaxed), 0);
+        assert_eq!(Arc::strong_count(&upgraded), 1);
+    }
+}
@@ -34,6 +34,7 @@ impl Guid {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub const fn new(
         a: u32,
         b: u16,
@@ -101,6 +102,7 @@ impl Guid {
     }
 }
 
+#[allow(clippy::from_over_into)]
 impl Into<u128> for Guid {
     fn into(self) -> u128 {
         self.to_u128()
@@ -113,6 +115,7 @@ impl From<u128> for Guid {
     }
 }
 
+#[allow(clippy::from_over_into)]
 impl Into<i128> for Guid {
     fn into(self) -> i128 {
         self.to_u128() as i128
@@ -133,6 +136,7 @@ impl PartialEq for Guid {
     }
 
     #[inline(always)]
+    #[allow(clippy::partialeq_ne_impl)]
     fn ne(&self, other: &Self) -> bool {
         self.to_u128() != other.to_u128()
     }
@@ -146,11 +150,13 @@ impl PartialEq for Guid {
     }
 
     #[inline(always)]
+    #[allow(clippy::partialeq_ne_impl)]
     fn ne(&self, other: &Self) -> bool {
         self.to_v128() != other.to_v128()
     }
 }
 
+#[allow(clippy::non_canonical_partial_ord_impl)]
 impl PartialOrd for Guid {
     fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
         self.to_u128().partial_cmp(&other.to_u128())
@@ -175,6 +181,7 @@ const fn reverse_endianness_u32(value: u32) -> u32 {
     + u32::rotate_left(value & 0xFF00FF00u32, 8) // ww yy
 }
 
+#[allow(clippy::manual_rotate)]
 const fn reverse_endianness_u16(value: u16) -> u16 {
     (value >> 8) + (value << 8)
 }
@@ -230,6 +237,7 @@ macro_rules! try_decode_byte {
 }
 
 impl Guid {
+    #[allow(clippy::needless_return)]
     pub const fn from_str(str: &str) -> Option<Guid> {
         let str = str.as_bytes();
         if str.len() != 36 {
@@ -0,0 +1,183 @@
+//! Refcount sanity checks and use-after-free trapping, enabled by the `hardened` feature.
+//!
+//! Without the feature the checks compile to nothing. With it, a strong or weak count growing
+//! past [`MAX_REFCOUNT`] or released at zero aborts the process, and freed objects of the
+//! default allocator are quarantined: their vtable pointer is replaced by a poison table whose
+//! every slot reports the use after free, and the block is only handed back to its allocator
+//! once [`QUARANTINE_LEN`] newer objects have been freed after it. Other allocators, see
+//! [`ObjectAllocator::QUARANTINE`](crate::object::ObjectAllocator::QUARANTINE), get their
+//! blocks back right away.
+
+use core::{any::type_name, fmt};
+
+use crate::object::FINALIZING;
+
+/// Counts beyond this abort, half way below the bit a strong count reserves for finalization so
+/// that increments racing with the abort cannot reach it
+pub const MAX_REFCOUNT: u32 = FINALIZING / 2 - 1;
+
+/// number of freed blocks kept poisoned before they are really deallocated
+#[cfg(feature = "hardened")]
+pub const QUARANTINE_LEN: usize = 64;
+
+/// checks the count returned by an increment, a strong count may carry the finalization bit
+#[inline(always)]
+pub(crate) fn check_add<T: ?Sized>(prev: u32) {
+    #[cfg(feature = "hardened")]
+    if prev & !FINALIZING > MAX_REFCOUNT {
+        fatal(format_args!("refcount overflow on {}", type_name::<T>()));
+    }
+}
+
+/// checks the count returned by a decrement
+#[inline(always)]
+pub(crate) fn check_release<T: ?Sized>(prev: u32) {
+    #[cfg(feature = "hardened")]
+    if prev == 0 {
+        fatal(format_args!(
+            "release of {} with a refcount of zero",
+            type_name::<T>()
+        ));
+    }
+}
+
+/// aborts without unwinding into foreign frames
+pub(crate) fn fatal(args: fmt::Arguments) -> ! {
+    #[cfg(feature = "std")]
+    {
+        std::eprintln!("cocom: {args}");
+        std::process::abort()
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        // a panic while panicking aborts
+        struct Abort;
+        impl Drop for Abort {
+            fn drop(&mut self) {
+                panic!("cocom: aborting");
+            }
+        }
+        let _abort = Abort;
+        panic!("cocom: {args}")
+    }
+}
+
+#[cfg(feature = "hardened")]
+pub use quarantine::*;
+
+#[cfg(feature = "hardened")]
+mod quarantine {
+    use alloc::collections::VecDeque;
+    use core::{any::type_name, ffi::c_void, fmt, marker::PhantomData, mem::size_of};
+
+    use super::{QUARANTINE_LEN, fatal};
+    use crate::{Interface, sync::SpinLock};
+
+    const POISON_SLOTS: usize = 256;
+
+    #[repr(C)]
+    struct PoisonVtbl([unsafe extern "C" fn(this: *const c_void); POISON_SLOTS]);
+
+    struct Poison<I>(PhantomData<I>);
+
+    impl<I: Interface> Poison<I> {
+        const VTBL: PoisonVtbl = PoisonVtbl([Self::poisoned; POISON_SLOTS]);
+
+        fn vtbl() -> &'static PoisonVtbl {
+            const {
+                assert!(size_of::<I::VitualTable>() <= size_of::<PoisonVtbl>());
+            }
+            &Self::VTBL
+        }
+
+        unsafe extern "C" fn poisoned(this: *const c_void) {
+            fatal(format_args!(
+                "use after free: method of {} called on freed object {:p}",
+                type_name::<I>(),
+                this
+            ))
+        }
+    }
+
+    struct Quarantined {
+        ptr: *mut u8,
+        free: unsafe fn(*mut u8),
+    }
+
+    unsafe impl Send for Quarantined {}
+
+    static QUARANTINE: SpinLock<VecDeque<Quarantined>> = SpinLock::new(VecDeque::new());
+
+    /// Poisons the vtable of a dead object and parks the block, `free` deallocates it later
+    pub(crate) unsafe fn quarantine<I: Interface>(ptr: *mut I, free: unsafe fn(*mut u8)) {
+        unsafe { (ptr as *mut *const PoisonVtbl).write(Poison::<I>::vtbl()) };
+        let evicted = {
+            let mut queue = QUARANTINE.lock();
+            queue.push_back(Quarantined {
+                ptr: ptr as _,
+                free,
+            });
+            if queue.len() > QUARANTINE_LEN {
+                queue.pop_front()
+            } else {
+                None
+            }
+        };
+        if let Some(q) = evicted {
+            unsafe { (q.free)(q.ptr) };
+        }
+    }
+
+    /// Deallocates every quarantined block, e.g. before resetting an arena
+    pub fn flush_quarantine() {
+        let queue = core::mem::take(&mut *QUARANTINE.lock());
+        for q in queue {
+            unsafe { (q.free)(q.ptr) };
+        }
+    }
+
+    /// Whether `ptr` is a freed object whose vtable was poisoned as an `I`
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to memory still readable as an interface, e.g. a quarantined block
+    pub unsafe fn is_poisoned<I: Interface>(ptr: *const I) -> bool {
+        unsafe { core::ptr::eq(*(ptr as *const *const PoisonVtbl), Poison::<I>::vtbl()) }
+    }
+}
+
+#[cfg(all(test, feature = "hardened"))]
+mod test {
+    use crate::{hardened::*, object::*, *};
+
+    #[object(IUnknown)]
+    #[derive(Debug)]
+    struct Guarded {
+        a: u32,
+    }
+
+    #[object(IWeak)]
+    #[derive(Debug)]
+    struct GuardedWeak {
+        a: u32,
+    }
+
+    impl impls::IWeak for GuardedWeak {}
+
+    #[test]
+    fn poisoned_on_free() {
+        let a = Guarded { a: 1 }.make_com();
+        let ptr = a.const_ptr();
+        assert!(!unsafe { is_poisoned(ptr) });
+        drop(a);
+        assert!(unsafe { is_poisoned(ptr) });
+
+        let b = GuardedWeak { a: 2 }.make_com_weak();
+        let weak = b.downgrade();
+        let ptr = b.const_ptr();
+        drop(b);
+        assert!(!unsafe { is_poisoned(ptr) });
+        drop(weak);
+        assert!(unsafe { is_poisoned(ptr) });
+    }
+}
@@ -8,11 +8,22 @@ pub enum HResultE {
     Pointer = 0x80004003,
     Abort = 0x80004004,
     Fail = 0x80004005,
+    IllegalMethodCall = 0x8000000E,
     Unexpected = 0x8000FFFF,
     AccessDenied = 0x80070005,
     Handle = 0x80070006,
     OutOfMemory = 0x8007000E,
     InvalidArg = 0x80070057,
+    NoAggregation = 0x80040110,
+    ClassNotAvailable = 0x80040111,
+    ClassNotRegistered = 0x80040154,
+    Disconnected = 0x80010108,
+    InvalidData = 0x8007000D,
+    MemberNotFound = 0x80020003,
+    TypeMismatch = 0x80020005,
+    UnknownName = 0x80020006,
+    Overflow = 0x8002000A,
+    BadParamCount = 0x8002000E,
 }
 
 #[repr(transparent)]
@@ -40,6 +51,9 @@ impl HResult {
     pub const fn fail() -> Self {
         Self::new(0x80004005u32 as i32)
     }
+    pub const fn illegal_method_call() -> Self {
+        Self::new(0x8000000Eu32 as i32)
+    }
     pub const fn unexpected() -> Self {
         Self::new(0x8000FFFFu32 as i32)
     }
@@ -55,6 +69,36 @@ impl HResult {
     pub const fn invalid_arg() -> Self {
         Self::new(0x80070057u32 as i32)
     }
+    pub const fn no_aggregation() -> Self {
+        Self::new(0x80040110u32 as i32)
+    }
+    pub const fn class_not_available() -> Self {
+        Self::new(0x80040111u32 as i32)
+    }
+    pub const fn class_not_registered() -> Self {
+        Self::new(0x80040154u32 as i32)
+    }
+    pub const fn disconnected() -> Self {
+        Self::new(0x80010108u32 as i32)
+    }
+    pub const fn invalid_data() -> Self {
+        Self::new(0x8007000Du32 as i32)
+    }
+    pub const fn member_not_found() -> Self {
+        Self::new(0x80020003u32 as i32)
+    }
+    pub const fn type_mismatch() -> Self {
+        Self::new(0x80020005u32 as i32)
+    }
+    pub const fn unknown_name() -> Self {
+        Self::new(0x80020006u32 as i32)
+    }
+    pub const fn overflow() -> Self {
+        Self::new(0x8002000Au32 as i32)
+    }
+    pub const fn bad_param_count() -> Self {
+        Self::new(0x8002000Eu32 as i32)
+    }
 }
 
 impl HResult {
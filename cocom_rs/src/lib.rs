@@ -3,8 +3,12 @@
 #![allow(non_camel_case_types)]
 #![allow(unused)]
 #![cfg_attr(nightly, feature(portable_simd))]
+#![cfg_attr(nightly, feature(allocator_api))]
 
 extern crate alloc;
+extern crate self as cocom;
+#[cfg(feature = "std")]
+extern crate std;
 
 use alloc::ffi;
 pub use cocom_proc::*;
@@ -12,15 +16,147 @@ pub use cocom_proc::*;
 use core::fmt::Debug;
 use core::ops::Deref;
 
+/// the `details` module the generator emits, for interfaces declared in tests
+///
+/// methods take their raw parameters, `=> Out` or `=> OutComPtr` marks the out parameters the
+/// thunk checks for null; returns are a single token so `HResult` can be told apart, items after
+/// a `;` go into the module too
+#[cfg(test)]
+macro_rules! test_details {
+    ($($name:ident: $parent:ident {
+        $(fn $m:ident(&$($recv:ident)+ $(, $p:ident: $pt:ty $(=> $w:ident)?)* $(,)?) $(-> $ret:tt)?;)*
+    })* $(; $($extra:item)*)?) => { paste::paste! {
+        pub mod details {
+            use super::*;
+            pub use $crate::details::*;
+
+            struct VT<T, V, O>(core::marker::PhantomData<(T, V, O)>);
+
+            $(
+                #[repr(C)]
+                #[derive(Debug)]
+                pub struct [<VitualTable_ $name>] {
+                    b: <$parent as $crate::Interface>::VitualTable,
+
+                    $(pub [<f_ $m>]: unsafe extern "C" fn(this: *const $name $(, $p: $pt)*) $(-> $ret)?,)*
+                }
+
+                impl<T: impls::$name + impls::Object, O: impls::ObjectBox<Object = T>> VT<T, $name, O>
+                where
+                    T::Interface: details::QuIn<T::Interface, T, O>,
+                {
+                    pub const VTBL: [<VitualTable_ $name>] = [<VitualTable_ $name>] {
+                        b: <$parent as Vtbl<O>>::VTBL,
+                        $([<f_ $m>]: Self::[<f_ $m>],)*
+                    };
+
+                    $(
+                        unsafe extern "C" fn [<f_ $m>](this: *const $name $(, $p: $pt)*) $(-> $ret)? {
+                            unsafe {
+                                let Some(_guard) = test_details!(@borrow O, this, $($recv)+) else {
+                                    test_details!(@reentrant $name::$m $(-> $ret)?)
+                                };
+                                $($(
+                                    let Some($p) = $crate::$w::new($p) else {
+                                        return $crate::HResultE::Pointer.into();
+                                    };
+                                )?)*
+                                test_details!(@ret [(*O::GetObject(this as _)).$m($($p),*)] $(-> $ret)?)
+                            }
+                        }
+                    )*
+                }
+
+                impl<T: impls::$name + impls::Object, O: impls::ObjectBox<Object = T>> Vtbl<O> for $name
+                where
+                    T::Interface: details::QuIn<T::Interface, T, O>,
+                {
+                    const VTBL: <$name as $crate::Interface>::VitualTable = VT::<T, $name, O>::VTBL;
+                    const VTBL_REF: &'static <$name as $crate::Interface>::VitualTable =
+                        &<Self as Vtbl<O>>::VTBL;
+
+                    fn vtbl() -> &'static Self::VitualTable {
+                        &<Self as Vtbl<O>>::VTBL
+                    }
+                }
+
+                impl<T: impls::$name + impls::Object, O: impls::ObjectBox<Object = T>>
+                    QuIn<$name, T, O> for $name
+                {
+                    #[inline(always)]
+                    unsafe fn QueryInterface(
+                        this: *mut T,
+                        guid: $crate::Guid,
+                        out: *mut *mut core::ffi::c_void,
+                    ) -> $crate::HResult {
+                        unsafe {
+                            static GUID: $crate::Guid = <$name as $crate::Interface>::GUID;
+                            if guid == GUID {
+                                *out = this as _;
+                                O::AddRef(this as _);
+                                return $crate::HResultE::Ok.into();
+                            }
+                            <$parent as QuIn<$parent, T, O>>::QueryInterface(this, guid, out)
+                        }
+                    }
+                }
+            )*
+
+            $($($extra)*)?
+        }
+    }};
+    (@borrow $o:ident, $this:ident, self) => { $o::Borrow($this as _) };
+    (@borrow $o:ident, $this:ident, mut self) => { $o::BorrowMut($this as _) };
+    (@reentrant $name:ident::$m:ident -> HResult) => {
+        return $crate::HResultE::IllegalMethodCall.into()
+    };
+    (@reentrant $name:ident::$m:ident $(-> $ret:tt)?) => {
+        $crate::object::reentrant_call(concat!(stringify!($name), "::", stringify!($m)))
+    };
+    (@ret [$call:expr] -> HResult) => { $crate::IntoHResult::into_hresult($call) };
+    (@ret [$call:expr] $(-> $ret:tt)?) => { $call };
+}
+
+pub mod allocator;
+pub mod async_op;
 pub mod com_ptr;
+pub mod debug;
+pub mod dispatch;
+pub mod error;
+pub mod factory;
+#[cfg(feature = "gc")]
+pub mod gc;
 pub mod guid;
+pub mod hardened;
 pub mod hresult;
+#[cfg(all(feature = "std", unix))]
+pub mod loader;
+pub mod module;
 pub mod object;
+pub mod out;
+#[cfg(feature = "std")]
+pub mod remote;
+pub mod scoped;
+pub mod span;
+pub mod string;
+mod sync;
+#[cfg(feature = "std")]
+pub mod thread;
+pub mod types;
+pub mod visit;
+pub mod weak_ref;
 
 pub use com_ptr::*;
+pub use error::{ComError, ComResult, IErrorInfo, IntoHResult};
+pub use factory::{ClassFactory, IClassFactory, create_instance};
 pub use guid::*;
 pub use hresult::*;
 pub use object::{MakeObject, MakeObjectWeak};
+pub use out::{Out, OutComPtr};
+pub use scoped::{ScopedObject, StaticObject};
+pub use span::{NRoSpan, NSpan};
+pub use string::{Str8, Str16, StrAny, StrKind};
+pub use weak_ref::{ComWeakAny, IWeakReference, IWeakReferenceSource, WeakRefSource};
 
 /// field projection for mut ptr
 #[macro_export]
@@ -33,6 +169,37 @@ macro_rules! pmp {
     };
 }
 
+/// expands its input only under the `std` feature, for items the proc macros emit
+#[doc(hidden)]
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! __std {
+    ($($t:tt)*) => { $($t)* };
+}
+
+#[doc(hidden)]
+#[cfg(not(feature = "std"))]
+#[macro_export]
+macro_rules! __std {
+    ($($t:tt)*) => {};
+}
+
+/// expands its input only under the `track-objects` or `gc` feature, the users of
+/// [`impls::Object::trace`]
+#[doc(hidden)]
+#[cfg(any(feature = "track-objects", feature = "gc"))]
+#[macro_export]
+macro_rules! __trace {
+    ($($t:tt)*) => { $($t)* };
+}
+
+#[doc(hidden)]
+#[cfg(not(any(feature = "track-objects", feature = "gc")))]
+#[macro_export]
+macro_rules! __trace {
+    ($($t:tt)*) => {};
+}
+
 /// field projection for const ptr
 #[macro_export]
 macro_rules! pcp {
@@ -63,12 +230,15 @@ pub mod details {
 
     pub trait Vtbl<O>: Interface {
         const VTBL: Self::VitualTable;
+        /// [`Vtbl::VTBL`] as a reference usable from const contexts
+        const VTBL_REF: &'static Self::VitualTable;
 
         fn vtbl() -> &'static Self::VitualTable;
     }
 
     struct VT<T, V, O>(core::marker::PhantomData<(T, V, O)>);
 
+    #[allow(clippy::missing_safety_doc)]
     pub trait QuIn<I, T, O>: Interface {
         unsafe fn QueryInterface(
             this: *mut T,
@@ -119,6 +289,7 @@ pub mod details {
         T::Interface: details::QuIn<T::Interface, T, O>,
     {
         const VTBL: <IUnknown as Interface>::VitualTable = VT::<T, IUnknown, O>::VTBL;
+        const VTBL_REF: &'static <IUnknown as Interface>::VitualTable = &<Self as Vtbl<O>>::VTBL;
 
         fn vtbl() -> &'static Self::VitualTable {
             &<Self as Vtbl<O>>::VTBL
@@ -132,7 +303,7 @@ pub mod details {
 
         pub f_AddRefWeak: unsafe extern "C" fn(this: *const IWeak) -> u32,
         pub f_ReleaseWeak: unsafe extern "C" fn(this: *const IWeak) -> u32,
-        pub f_TryUpgrade: unsafe extern "C" fn(this: *const IWeak) -> bool,
+        pub f_TryUpgrade: unsafe extern "C" fn(this: *const IWeak) -> types::B8,
     }
 
     impl<T: impls::IWeak + impls::Object, O: ObjectBox<Object = T> + ObjectBoxWeak> VT<T, IWeak, O>
@@ -152,8 +323,8 @@ pub mod details {
         unsafe extern "C" fn f_ReleaseWeak(this: *const IWeak) -> u32 {
             unsafe { O::ReleaseWeak(this as _) }
         }
-        unsafe extern "C" fn f_TryUpgrade(this: *const IWeak) -> bool {
-            unsafe { O::TryUpgrade(this as _) }
+        unsafe extern "C" fn f_TryUpgrade(this: *const IWeak) -> types::B8 {
+            unsafe { O::TryUpgrade(this as _).into() }
         }
     }
 
@@ -162,6 +333,7 @@ pub mod details {
         T::Interface: details::QuIn<T::Interface, T, O>,
     {
         const VTBL: <IWeak as Interface>::VitualTable = VT::<T, IWeak, O>::VTBL;
+        const VTBL_REF: &'static <IWeak as Interface>::VitualTable = &<Self as Vtbl<O>>::VTBL;
 
         fn vtbl() -> &'static Self::VitualTable {
             &<Self as Vtbl<O>>::VTBL
@@ -237,6 +409,7 @@ impl Interface for IUnknown {
     }
 }
 
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 impl IUnknown {
     pub fn v_ptr(&self) -> *const details::VitualTable_IUnknown {
         unsafe { self.v_ptr as *const _ }
@@ -255,6 +428,7 @@ impl IUnknown {
     }
 }
 
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 impl impls::QueryInterface for IUnknown {
     fn QueryInterface(
         this: *const Self,
@@ -265,6 +439,7 @@ impl impls::QueryInterface for IUnknown {
     }
 }
 
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 impl impls::RefCount for IUnknown {
     fn AddRef(this: *const Self) -> u32 {
         Self::AddRef(unsafe { &*this })
@@ -326,10 +501,11 @@ impl IWeak {
     }
 
     pub fn TryUpgrade(&self) -> bool {
-        unsafe { ((*self.v_ptr()).f_TryUpgrade)(self) }
+        unsafe { ((*self.v_ptr()).f_TryUpgrade)(self).into() }
     }
 }
 
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 impl impls::RefCount for IWeak {
     fn AddRef(this: *const Self) -> u32 {
         IUnknown::AddRef(unsafe { &*this })
@@ -340,6 +516,7 @@ impl impls::RefCount for IWeak {
     }
 }
 
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 impl impls::WeakRefCount for IWeak {
     fn AddRefWeak(this: *const Self) -> u32 {
         Self::AddRefWeak(unsafe { &*this })
@@ -357,11 +534,45 @@ impl impls::WeakRefCount for IWeak {
 pub mod impls {
     use super::*;
 
+    /// `Self` derives from the interface `T`
+    ///
+    /// # Safety
+    ///
+    /// `T` must be laid out as a prefix of `Self`, upcasts reinterpret the pointer
     pub unsafe trait Inherit<T>: AsRef<T> + AsMut<T> {}
 
     pub trait Object {
         type Interface: Interface + Sized;
         type Allocator: object::ObjectAllocator + Debug;
+
+        /// make the vtable thunks track borrows of the value and reject reentrant calls
+        const CHECK_REENTRANCY: bool = false;
+
+        /// called once the object is fully constructed, before the pointer is handed out
+        fn final_construct(this: &object::ObjectPtr<Self>)
+        where
+            Self: Sized,
+        {
+            _ = this;
+        }
+
+        /// [`Object::final_construct`] for objects boxed in a [`object::WeakObject`]
+        fn final_construct_weak(this: &object::WeakObjectPtr<Self>)
+        where
+            Self: Sized,
+        {
+            _ = this;
+        }
+
+        /// called before the value drops, weak upgrades already fail but references taken here resurrect it
+        fn final_release(&mut self) {}
+
+        /// called when `try_unwrap` or `into_inner` moves the value out, before the block is freed,
+        /// `#[object]` detaches the [`weak_ref::WeakRefSource`] fields here
+        fn detach(&mut self) {}
+
+        /// visits the strong references held by the value, see [`visit`]
+        fn trace(&self, visitor: &mut dyn visit::Visitor) {}
     }
 
     pub trait ObjectBoxNew: ObjectBox {
@@ -375,25 +586,30 @@ pub mod impls {
         ) -> *mut Self;
     }
 
+    #[allow(clippy::missing_safety_doc)]
     pub trait ObjectBox {
         type Object: Object;
 
         unsafe fn GetObject(this: *mut <Self::Object as Object>::Interface) -> *mut Self::Object;
 
-        #[inline(always)]
         unsafe fn AddRef(this: *mut <Self::Object as Object>::Interface) -> u32;
-        #[inline(always)]
         unsafe fn Release(this: *mut <Self::Object as Object>::Interface) -> u32;
+
+        /// shared borrow of the value for a `&self` method, `None` on a conflicting borrow
+        unsafe fn Borrow<'a>(
+            this: *mut <Self::Object as Object>::Interface,
+        ) -> Option<object::BorrowGuard<'a>>;
+        /// exclusive borrow of the value for a `&mut self` method, `None` on a reentrant call
+        unsafe fn BorrowMut<'a>(
+            this: *mut <Self::Object as Object>::Interface,
+        ) -> Option<object::BorrowGuard<'a>>;
     }
 
+    #[allow(clippy::missing_safety_doc)]
     pub trait ObjectBoxWeak: ObjectBox {
-        #[inline(always)]
         unsafe fn AddRefWeak(this: *mut <Self::Object as Object>::Interface) -> u32;
-        #[inline(always)]
         unsafe fn ReleaseWeak(this: *mut <Self::Object as Object>::Interface) -> u32;
-        #[inline(always)]
         unsafe fn TryUpgrade(this: *mut <Self::Object as Object>::Interface) -> bool;
-        #[inline(always)]
         unsafe fn TryDowngrade(this: *mut <Self::Object as Object>::Interface) -> bool;
     }
 
@@ -406,7 +622,6 @@ pub mod impls {
     }
 
     pub trait QueryInterface {
-        #[inline(always)]
         fn QueryInterface(
             this: *const Self,
             guid: *const Guid,
@@ -0,0 +1,218 @@
+//! Loading components from shared libraries built with [`export_module!`](crate::export_module),
+//! enabled by the `std` feature on unix.
+//!
+//! A [`Module`] maps the library with `dlopen` and resolves `DllGetClassObject` and
+//! `DllCanUnloadNow`, and hands the library the host's error info slot through
+//! `DllShareErrorInfo` when it exports one. Objects created from it only hold the module's own lock count, so when
+//! the last [`Module`] handle drops while objects are still alive, the library stays mapped
+//! and is parked until [`free_unused_modules`] finds that it can unload.
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    ffi::{CStr, c_char, c_int, c_void},
+    fmt,
+    ptr::{self, NonNull},
+};
+use std::{ffi::CString, path::Path, sync::Mutex};
+
+use crate::{
+    ComPtr, ComResult, Guid, HResult, HResultE, IClassFactory, Interface,
+    error::{ErrorInfoSlot, error_info_slot},
+    impls,
+};
+
+const RTLD_NOW: c_int = 2;
+
+unsafe extern "C" {
+    fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlclose(handle: *mut c_void) -> c_int;
+    fn dlerror() -> *mut c_char;
+}
+
+type GetClassObject =
+    unsafe extern "C" fn(clsid: *const Guid, iid: *const Guid, out: *mut *mut c_void) -> HResult;
+type CanUnloadNow = unsafe extern "C" fn() -> HResult;
+type ShareErrorInfo = unsafe extern "C" fn(slot: *const ErrorInfoSlot);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    /// `dlopen` failed, with the message from `dlerror`
+    Open(String),
+    /// the library does not export this entry point
+    MissingEntryPoint(&'static str),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Open(msg) => write!(f, "cannot load module: {msg}"),
+            LoadError::MissingEntryPoint(name) => write!(f, "module does not export {name}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+struct Library {
+    handle: *mut c_void,
+    get_class_object: GetClassObject,
+    can_unload_now: CanUnloadNow,
+}
+
+impl Library {
+    fn can_unload(&self) -> bool {
+        unsafe { (self.can_unload_now)() == HResult::ok() }
+    }
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        if self.can_unload() {
+            unsafe { dlclose(self.handle) };
+        } else {
+            PARKED.lock().unwrap().push(Parked {
+                handle: self.handle,
+                can_unload_now: self.can_unload_now,
+            });
+        }
+    }
+}
+
+unsafe impl Send for Library {}
+unsafe impl Sync for Library {}
+
+/// a library whose last [`Module`] dropped while it still had live objects
+struct Parked {
+    handle: *mut c_void,
+    can_unload_now: CanUnloadNow,
+}
+
+unsafe impl Send for Parked {}
+
+static PARKED: Mutex<Vec<Parked>> = Mutex::new(Vec::new());
+
+/// Unloads parked libraries that report they can unload now, returns how many were unloaded
+pub fn free_unused_modules() -> usize {
+    let mut parked = PARKED.lock().unwrap();
+    let before = parked.len();
+    parked.retain(|lib| {
+        if unsafe { (lib.can_unload_now)() } != HResult::ok() {
+            return true;
+        }
+        unsafe { dlclose(lib.handle) };
+        false
+    });
+    before - parked.len()
+}
+
+/// A loaded component library, cheap to clone.
+///
+/// The library is unloaded when the last clone drops, or parked if it still has live objects.
+#[derive(Clone)]
+pub struct Module {
+    lib: Arc<Library>,
+}
+
+impl Module {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let path = CString::new(path.as_ref().as_os_str().as_encoded_bytes())
+            .map_err(|_| LoadError::Open("path contains a nul byte".to_string()))?;
+        unsafe {
+            let handle = dlopen(path.as_ptr(), RTLD_NOW);
+            if handle.is_null() {
+                let msg = dlerror();
+                let msg = if msg.is_null() {
+                    String::new()
+                } else {
+                    CStr::from_ptr(msg).to_string_lossy().into_owned()
+                };
+                return Err(LoadError::Open(msg));
+            }
+            let symbol = |name: &'static CStr| {
+                let sym = dlsym(handle, name.as_ptr());
+                if sym.is_null() {
+                    Err(LoadError::MissingEntryPoint(name.to_str().unwrap()))
+                } else {
+                    Ok(sym)
+                }
+            };
+            let entries = symbol(c"DllGetClassObject")
+                .and_then(|get_class_object| Ok((get_class_object, symbol(c"DllCanUnloadNow")?)));
+            let (get_class_object, can_unload_now) = match entries {
+                Ok(entries) => entries,
+                Err(e) => {
+                    dlclose(handle);
+                    return Err(e);
+                }
+            };
+            let share_error_info = dlsym(handle, c"DllShareErrorInfo".as_ptr());
+            if !share_error_info.is_null() {
+                let share_error_info =
+                    core::mem::transmute::<*mut c_void, ShareErrorInfo>(share_error_info);
+                share_error_info(error_info_slot());
+            }
+            Ok(Self {
+                lib: Arc::new(Library {
+                    handle,
+                    get_class_object: core::mem::transmute::<*mut c_void, GetClassObject>(
+                        get_class_object,
+                    ),
+                    can_unload_now: core::mem::transmute::<*mut c_void, CanUnloadNow>(
+                        can_unload_now,
+                    ),
+                }),
+            })
+        }
+    }
+
+    /// the class object for `clsid`, queried for `I`
+    pub fn get_class_object<I: Interface + impls::RefCount>(
+        &self,
+        clsid: &Guid,
+    ) -> ComResult<ComPtr<I>> {
+        let mut out = ptr::null_mut();
+        let r = unsafe { (self.lib.get_class_object)(clsid, &I::GUID, &mut out) };
+        r.to_result()?;
+        match NonNull::new(out) {
+            Some(out) => Ok(unsafe { ComPtr::new(out.cast()) }),
+            None => Err(HResultE::Pointer.into()),
+        }
+    }
+
+    pub fn class_factory(&self, clsid: &Guid) -> ComResult<ComPtr<IClassFactory>> {
+        self.get_class_object(clsid)
+    }
+
+    /// creates an instance of `clsid` through its class factory, as an `I`
+    pub fn create_instance<I: Interface + impls::RefCount>(
+        &self,
+        clsid: &Guid,
+    ) -> ComResult<ComPtr<I>> {
+        let factory = self.class_factory(clsid)?;
+        let mut out = ptr::null_mut();
+        let r = factory.CreateInstance(ptr::null_mut(), &I::GUID, &mut out);
+        r.to_result()?;
+        match NonNull::new(out) {
+            Some(out) => Ok(unsafe { ComPtr::new(out.cast()) }),
+            None => Err(HResultE::Pointer.into()),
+        }
+    }
+
+    /// whether the library reports no live objects or server locks
+    pub fn can_unload_now(&self) -> bool {
+        self.lib.can_unload()
+    }
+}
+
+impl fmt::Debug for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Module")
+            .field("handle", &self.lib.handle)
+            .finish()
+    }
+}
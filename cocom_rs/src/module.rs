@@ -0,0 +1,169 @@
+//! Entry points for components shipped as shared libraries, see [`export_module!`].
+//!
+//! The module lock count is held by every `Object<T>` and `WeakObject<T>` from construction
+//! until its block is deallocated, and by every `LockServer(TRUE)` on a factory. While it is
+//! nonzero, code of this module may still run and the host must not unload it.
+
+use core::{
+    ffi::c_void,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    ComPtr, Guid, HResult, HResultE, IClassFactory, Interface, factory::ClassFactory, impls,
+    object::MakeObject,
+};
+
+static LOCKS: AtomicUsize = AtomicUsize::new(0);
+
+/// takes a module lock
+#[inline]
+pub fn lock() {
+    LOCKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// releases a module lock taken with [`lock`]
+#[inline]
+pub fn unlock() {
+    let r = LOCKS.fetch_sub(1, Ordering::Release);
+    debug_assert!(r > 0, "module unlocked more often than locked");
+}
+
+/// live objects and server locks of this module
+pub fn lock_count() -> usize {
+    LOCKS.load(Ordering::Acquire)
+}
+
+/// `S_OK` when nothing keeps the module alive, `S_FALSE` otherwise
+///
+/// With `hardened`, quarantined blocks are freed first, their poison lives in this module.
+pub fn can_unload_now() -> HResult {
+    #[cfg(feature = "hardened")]
+    crate::hardened::flush_quarantine();
+    if lock_count() == 0 {
+        HResultE::Ok.into()
+    } else {
+        HResult::new(1)
+    }
+}
+
+/// A creatable class with a fixed class id, for [`export_module!`]
+pub trait Class {
+    const CLSID: Guid;
+}
+
+/// One row of the class table built by [`export_module!`]
+#[derive(Debug, Clone, Copy)]
+pub struct ClassEntry {
+    pub clsid: Guid,
+    pub factory: fn() -> ComPtr<IClassFactory>,
+}
+
+impl ClassEntry {
+    pub fn of<T>() -> Self
+    where
+        T: Class + Default + impls::Object + MakeObject<ComOutput = ComPtr<T::Interface>>,
+        T::Interface: impls::RefCount,
+    {
+        Self {
+            clsid: T::CLSID,
+            factory: || ClassFactory::<T>::new().make_com(),
+        }
+    }
+}
+
+/// `DllGetClassObject` over a class table
+///
+/// # Safety
+/// `out` must be null or valid for a write, `clsid` and `iid` null or valid for a read
+pub unsafe fn get_class_object(
+    classes: &[ClassEntry],
+    clsid: *const Guid,
+    iid: *const Guid,
+    out: *mut *mut c_void,
+) -> HResult {
+    if clsid.is_null() || iid.is_null() || out.is_null() {
+        return HResultE::Pointer.into();
+    }
+    unsafe {
+        *out = core::ptr::null_mut();
+        match classes.iter().find(|c| c.clsid == *clsid) {
+            Some(class) => (class.factory)().as_unknown().QueryInterface(iid, out),
+            None => HResultE::ClassNotAvailable.into(),
+        }
+    }
+}
+
+/// Exports `DllGetClassObject` and `DllCanUnloadNow` for the listed [`Class`]es, and
+/// `DllShareErrorInfo` taking the host's [`ErrorInfoSlot`](crate::error::ErrorInfoSlot).
+///
+/// ```ignore
+/// cocom::export_module! {
+///     classes: [Foo, Bar],
+/// }
+/// ```
+#[macro_export]
+macro_rules! export_module {
+    { classes: [$($class:ty),* $(,)?] $(,)? } => {
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn DllGetClassObject(
+            clsid: *const $crate::Guid,
+            iid: *const $crate::Guid,
+            out: *mut *mut ::core::ffi::c_void,
+        ) -> $crate::HResult {
+            let classes = [$($crate::module::ClassEntry::of::<$class>()),*];
+            unsafe { $crate::module::get_class_object(&classes, clsid, iid, out) }
+        }
+
+        #[unsafe(no_mangle)]
+        pub extern "C" fn DllCanUnloadNow() -> $crate::HResult {
+            $crate::module::can_unload_now()
+        }
+
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn DllShareErrorInfo(slot: *const $crate::error::ErrorInfoSlot) {
+            if let Some(slot) = unsafe { slot.as_ref() } {
+                unsafe { $crate::error::share_error_info_slot(slot) }
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use core::ptr;
+
+    use crate::{module::*, object::*, *};
+
+    #[object(IUnknown)]
+    #[derive(Debug, Default)]
+    struct Exported {
+        a: u32,
+    }
+
+    impl Class for Exported {
+        const CLSID: Guid = Guid::from_str("c1d3a4f0-7b52-4e8e-8f0a-2b6d9e4c1a70").unwrap();
+    }
+
+    #[test]
+    fn class_table() {
+        let classes = [ClassEntry::of::<Exported>()];
+        let mut out = ptr::null_mut();
+        let r =
+            unsafe { get_class_object(&classes, &Exported::CLSID, &IClassFactory::GUID, &mut out) };
+        assert_eq!(r, HResult::ok());
+        let factory = unsafe { ComPtr::new_unchecked(out as *mut IClassFactory) };
+        let mut out = ptr::null_mut();
+        assert_eq!(
+            factory.CreateInstance(ptr::null_mut(), &IUnknown::GUID, &mut out),
+            HResult::ok()
+        );
+        let obj = unsafe { ComPtr::new_unchecked(out) };
+        assert!(lock_count() >= 2);
+        drop((factory, obj));
+
+        let mut out = ptr::null_mut();
+        let r = unsafe { get_class_object(&classes, &IUnknown::GUID, &IUnknown::GUID, &mut out) };
+        assert_eq!(r, HResult::from(HResultE::ClassNotAvailable));
+    }
+}
@@ -1,32 +1,55 @@
+use alloc::{boxed::Box, vec::Vec};
 use core::{
     alloc::Layout,
+    any::TypeId,
     hash::Hash,
+    marker::PhantomData,
     mem::ManuallyDrop,
     ops::DerefMut,
     ptr::{self, NonNull},
-    sync::atomic::{AtomicU32, Ordering},
+    sync::atomic::{self, AtomicU32, Ordering},
 };
 
 use crate::{
     com_ptr::*,
     impls::{self, ObjectBox, WeakRefCount},
+    sync::SpinLock,
     *,
 };
 
 pub trait ObjectAllocator {
-    #[inline]
+    /// # Safety
+    ///
+    /// as [`GlobalAlloc::alloc`](core::alloc::GlobalAlloc::alloc)
     unsafe fn alloc(&self, layout: Layout) -> *mut u8;
-    #[inline]
+    /// # Safety
+    ///
+    /// as [`GlobalAlloc::alloc_zeroed`](core::alloc::GlobalAlloc::alloc_zeroed)
     unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8;
-    #[inline]
+    /// # Safety
+    ///
+    /// as [`GlobalAlloc::dealloc`](core::alloc::GlobalAlloc::dealloc)
     unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout);
-    #[inline]
+    /// # Safety
+    ///
+    /// as [`GlobalAlloc::realloc`](core::alloc::GlobalAlloc::realloc)
     unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8;
+
+    /// live bytes and objects, if the allocator counts them
+    fn stats(&self) -> Option<allocator::AllocStats> {
+        None
+    }
+
+    /// with `hardened`, freed blocks may be parked in the quarantine and handed back late,
+    /// only for allocators that outlive every object and don't count live blocks
+    const QUARANTINE: bool = false;
 }
 
 pub type DefaultObjectAllocator = ();
 
 impl ObjectAllocator for DefaultObjectAllocator {
+    const QUARANTINE: bool = true;
+
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         unsafe { alloc::alloc::alloc(layout) }
     }
@@ -180,28 +203,308 @@ where
     }
 }
 
+/// Flag set in the strong count while [`impls::Object::final_release`] runs.
+///
+/// Upgrades see a dying object, only the hook can take new references through `&mut self`.
+pub(crate) const FINALIZING: u32 = 1 << 30;
+
+/// the strong count after an upgrade, `None` once it reached zero or is finalizing
+#[inline]
+fn checked_upgrade(n: u32) -> Option<u32> {
+    if n == 0 || n & FINALIZING != 0 {
+        return None;
+    }
+    Some(n + 1)
+}
+
+/// per-object borrow state used by the vtable thunks to detect reentrant calls
+///
+/// only consulted when [`impls::Object::CHECK_REENTRANCY`] is set
+#[derive(Debug, Default)]
+#[repr(transparent)]
+pub struct BorrowFlag(AtomicU32);
+
+impl BorrowFlag {
+    const EXCLUSIVE: u32 = u32::MAX;
+
+    pub const fn new() -> Self {
+        Self(AtomicU32::new(0))
+    }
+
+    pub fn is_borrowed(&self) -> bool {
+        self.0.load(Ordering::Relaxed) != 0
+    }
+
+    pub fn is_borrowed_mut(&self) -> bool {
+        self.0.load(Ordering::Relaxed) == Self::EXCLUSIVE
+    }
+
+    pub fn try_borrow(&self) -> Option<BorrowGuard<'_>> {
+        self.0
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |n| {
+                if n >= Self::EXCLUSIVE - 1 {
+                    None
+                } else {
+                    Some(n + 1)
+                }
+            })
+            .ok()?;
+        Some(BorrowGuard {
+            flag: Some(self),
+            exclusive: false,
+        })
+    }
+
+    pub fn try_borrow_mut(&self) -> Option<BorrowGuard<'_>> {
+        self.0
+            .compare_exchange(0, Self::EXCLUSIVE, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+        Some(BorrowGuard {
+            flag: Some(self),
+            exclusive: true,
+        })
+    }
+}
+
+/// releases a [`BorrowFlag`] borrow on drop
+#[derive(Debug)]
+#[must_use]
+pub struct BorrowGuard<'a> {
+    flag: Option<&'a BorrowFlag>,
+    exclusive: bool,
+}
+
+impl BorrowGuard<'_> {
+    /// a guard that tracks nothing, for objects without reentrancy checks
+    pub const fn unchecked() -> Self {
+        Self {
+            flag: None,
+            exclusive: false,
+        }
+    }
+}
+
+/// aborts a thunk that has no way to report a rejected borrow, it must not unwind
+#[doc(hidden)]
+#[cold]
+pub fn reentrant_call(method: &str) -> ! {
+    hardened::fatal(format_args!("reentrant call to {method}"))
+}
+
+impl Drop for BorrowGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(flag) = self.flag {
+            if self.exclusive {
+                flag.0.store(0, Ordering::Release);
+            } else {
+                flag.0.fetch_sub(1, Ordering::Release);
+            }
+        }
+    }
+}
+
+/// Mutable access lent by `get_mut_unique`, the object is marked [`FINALIZING`] meanwhile so
+/// that no weak reference upgrades to a second, shared, reference
+#[derive(Debug)]
+#[must_use]
+pub struct UniqueMut<'a, T> {
+    strong: &'a AtomicU32,
+    val: &'a mut T,
+}
+
+impl<'a, T> UniqueMut<'a, T> {
+    /// `None` unless `strong` is the only reference
+    fn new(strong: &'a AtomicU32, val: &'a mut T) -> Option<Self> {
+        strong
+            .compare_exchange(1, FINALIZING | 1, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+        Some(Self { strong, val })
+    }
+}
+
+impl<T> core::ops::Deref for UniqueMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.val
+    }
+}
+
+impl<T> DerefMut for UniqueMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.val
+    }
+}
+
+impl<T> Drop for UniqueMut<'_, T> {
+    fn drop(&mut self) {
+        // the value may have taken references to itself meanwhile, only the mark goes
+        self.strong.fetch_and(!FINALIZING, Ordering::Release);
+    }
+}
+
+/// the [`TypeId`] of `T` with its lifetimes erased, stored in the object headers for the downcasts,
+/// which only accept `'static` types
+fn type_id<T: ?Sized>() -> TypeId {
+    trait NonStaticAny {
+        fn type_id(&self) -> TypeId
+        where
+            Self: 'static;
+    }
+
+    impl<T: ?Sized> NonStaticAny for PhantomData<T> {
+        fn type_id(&self) -> TypeId
+        where
+            Self: 'static,
+        {
+            TypeId::of::<T>()
+        }
+    }
+
+    let p: &dyn NonStaticAny = &PhantomData::<T>;
+    // only the lifetime bound of the trait object changes, `type_id` never touches a `T`
+    unsafe { core::mem::transmute::<&dyn NonStaticAny, &(dyn NonStaticAny + 'static)>(p) }.type_id()
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct Object<T: impls::Object> {
     base: T::Interface,
+    ty: TypeId,
     allocator: T::Allocator,
     strong: AtomicU32,
+    borrow: BorrowFlag,
     val: ManuallyDrop<T>,
 }
 
 impl<T: impls::Object> Object<T> {
     unsafe fn Drop(this: *mut Self) {
         unsafe {
+            // keep the collector away while the count is borrowed by `FinalRelease`
+            #[cfg(feature = "gc")]
+            gc::unregister(this as _);
+            if !Self::FinalRelease(this) {
+                #[cfg(feature = "gc")]
+                gc::register(this as _, &(*this).strong, &Self::GC);
+                return;
+            }
+            #[cfg(feature = "track-objects")]
+            debug::untrack(this as _);
             ManuallyDrop::drop(&mut (*this).val);
+            Self::Free(this);
+        }
+    }
+
+    /// runs [`impls::Object::final_release`] under a temporary [`FINALIZING`] reference,
+    /// false if the hook resurrected the object
+    unsafe fn FinalRelease(this: *mut Self) -> bool {
+        unsafe {
+            atomic::fence(Ordering::Acquire);
+            (*this).strong.store(FINALIZING + 1, Ordering::Relaxed);
+            (*(*this).val).final_release();
+            let r = (*this).strong.fetch_sub(FINALIZING + 1, Ordering::Release);
+            hardened::check_release::<T>(r);
+            atomic::fence(Ordering::Acquire);
+            r == FINALIZING + 1
+        }
+    }
+
+    /// bookkeeping once the block is initialized, then [`impls::Object::final_construct`]
+    unsafe fn Constructed(this: *mut Self) {
+        module::lock();
+        #[cfg(feature = "track-objects")]
+        debug::track::<T>(
+            this as _,
+            unsafe { &*(*this).val },
+            unsafe { &(*this).strong },
+            unsafe { &(*this).borrow },
+        );
+        debug::trace_ref!(T, this, Object, Create, 1);
+        #[cfg(feature = "gc")]
+        gc::register(this as _, unsafe { &(*this).strong }, &Self::GC);
+        unsafe {
+            let this = ManuallyDrop::new(ObjectPtr(ComPtr::new(NonNull::new_unchecked(this))));
+            T::final_construct(&this);
+        }
+    }
+
+    /// frees the block without dropping the value
+    unsafe fn Free(this: *mut Self) {
+        unsafe {
+            #[cfg(feature = "hardened")]
+            if T::Allocator::QUARANTINE {
+                hardened::quarantine::<T::Interface>(this as _, Self::Dealloc);
+            } else {
+                Self::Dealloc(this as _);
+            }
+            #[cfg(not(feature = "hardened"))]
+            Self::Dealloc(this as _);
+        }
+        module::unlock();
+    }
+
+    unsafe fn Dealloc(this: *mut u8) {
+        unsafe {
+            let this = this as *mut Self;
             let allocator = ptr::read(&(*this).allocator);
             allocator.dealloc(this as _, Layout::new::<Self>());
         }
     }
+
+    /// moves the value out and frees the block, the strong count must already be zero
+    unsafe fn Take(this: *mut Self) -> T {
+        unsafe {
+            #[cfg(feature = "track-objects")]
+            debug::untrack(this as _);
+            #[cfg(feature = "gc")]
+            gc::unregister(this as _);
+            (*(*this).val).detach();
+            let val = ManuallyDrop::take(&mut (*this).val);
+            Self::Free(this);
+            val
+        }
+    }
+}
+
+#[cfg(feature = "gc")]
+impl<T: impls::Object> Object<T> {
+    const GC: gc::Hooks = gc::Hooks {
+        trace: |this, visitor| unsafe { (*(this as *const Self)).val.trace(visitor) },
+        final_release: |this| unsafe { (*(*(this as *mut Self)).val).final_release() },
+        // nothing may find the object through the registries once its value starts dropping
+        drop_value: |this| unsafe {
+            let this = this as *mut Self;
+            #[cfg(feature = "track-objects")]
+            debug::untrack(this as _);
+            gc::unregister(this as _);
+            ManuallyDrop::drop(&mut (*this).val)
+        },
+        release: |this| {
+            <Self as impls::RefCount>::Release(this as _);
+        },
+        free: |this| unsafe { Self::Free(this as _) },
+    };
 }
 
+#[allow(clippy::missing_safety_doc)]
 impl<T: impls::Object> Object<T> {
     pub unsafe fn GetStrongCount(this: *mut Self) -> u32 {
-        unsafe { (*this).strong.load(Ordering::Acquire) }
+        unsafe { (*this).strong.load(Ordering::Acquire) & !FINALIZING }
+    }
+
+    /// increments the strong count unless it already reached zero
+    pub unsafe fn TryUpgrade(this: *mut Self) -> bool {
+        unsafe {
+            let r =
+                (*this)
+                    .strong
+                    .fetch_update(Ordering::Acquire, Ordering::Relaxed, checked_upgrade);
+            if let Ok(n) = r {
+                hardened::check_add::<T>(n);
+                debug::trace_ref!(T, this, Object, Upgrade, n.wrapping_add(1));
+            }
+            r.is_ok()
+        }
     }
 
     pub unsafe fn FromValue(value: *mut T) -> *mut Self {
@@ -228,6 +531,7 @@ impl<T: impls::Object<Allocator = DefaultObjectAllocator>> Object<T>
 where
     T::Interface: details::Vtbl<Self>,
 {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(val: T) -> *mut T::Interface {
         Self::make(val) as _
     }
@@ -237,22 +541,33 @@ where
             let b = ().alloc(Layout::new::<Self>()) as *mut Self;
             b.write(Self {
                 base: T::Interface::new(<T::Interface as details::Vtbl<Self>>::vtbl()),
+                ty: type_id::<T>(),
                 allocator: (),
                 strong: AtomicU32::new(1),
+                borrow: BorrowFlag::new(),
                 val: ManuallyDrop::new(val),
             });
+            Self::Constructed(b);
             b as _
         }
     }
 
+    /// builds the value in place through `init`
+    ///
+    /// # Safety
+    ///
+    /// `init` must fully initialize the value behind the pointer it is given
     pub unsafe fn inplace(init: impl FnOnce(*mut T)) -> ObjectPtr<T> {
         unsafe {
             let b = ().alloc(Layout::new::<Self>()) as *mut Self;
             pmp!(b; .base).write(T::Interface::new(
                 <T::Interface as details::Vtbl<Self>>::vtbl(),
             ));
+            pmp!(b; .ty).write(type_id::<T>());
             pmp!(b; .strong).write(AtomicU32::new(1));
+            pmp!(b; .borrow).write(BorrowFlag::new());
             init(pmp!(b; .val) as *mut _);
+            Self::Constructed(b);
             ObjectPtr(ComPtr::new(NonNull::new_unchecked(b)))
         }
     }
@@ -271,25 +586,36 @@ where
             let b = allocator.alloc(Layout::new::<Self>()) as *mut Self;
             b.write(Self {
                 base: T::Interface::new(<T::Interface as details::Vtbl<Self>>::vtbl()),
-                allocator: allocator,
+                ty: type_id::<T>(),
+                allocator,
                 strong: AtomicU32::new(1),
+                borrow: BorrowFlag::new(),
                 val: ManuallyDrop::new(val),
             });
+            Self::Constructed(b);
             b as _
         }
     }
 
+    /// builds the value in place through `init`
+    ///
+    /// # Safety
+    ///
+    /// `init` must fully initialize the value behind the pointer it is given
     pub unsafe fn inplace_with(allocator: T::Allocator, init: impl FnOnce(*mut T)) -> ObjectPtr<T> {
         unsafe {
             let b = allocator.alloc(Layout::new::<Self>()) as *mut Self;
             pmp!(b; .base).write(T::Interface::new(
                 <T::Interface as details::Vtbl<Self>>::vtbl(),
             ));
+            pmp!(b; .ty).write(type_id::<T>());
             if size_of::<T::Allocator>() > 0 {
                 pmp!(b; .allocator).write(allocator);
             }
             pmp!(b; .strong).write(AtomicU32::new(1));
+            pmp!(b; .borrow).write(BorrowFlag::new());
             init(pmp!(b; .val) as *mut _);
+            Self::Constructed(b);
             ObjectPtr(ComPtr::new(NonNull::new_unchecked(b)))
         }
     }
@@ -325,7 +651,10 @@ impl<T: impls::Object> impls::ObjectBox for Object<T> {
     unsafe fn AddRef(this: *mut T::Interface) -> u32 {
         unsafe {
             let this = this as *mut Self;
-            (*this).strong.fetch_add(1, Ordering::Relaxed)
+            let r = (*this).strong.fetch_add(1, Ordering::Relaxed);
+            hardened::check_add::<T>(r);
+            debug::trace_ref!(T, this, Object, AddRef, r.wrapping_add(1));
+            r
         }
     }
 
@@ -334,18 +663,46 @@ impl<T: impls::Object> impls::ObjectBox for Object<T> {
         unsafe {
             let this = this as *mut Self;
             let r = (*this).strong.fetch_sub(1, Ordering::Release);
+            hardened::check_release::<T>(r);
+            debug::trace_ref!(T, this, Object, Release, r.wrapping_sub(1));
             if r == 1 {
                 Self::Drop(this);
             }
+            #[cfg(feature = "gc")]
+            if r > 1 {
+                gc::possible_root(this as _);
+            }
             r
         }
     }
+
+    #[inline(always)]
+    unsafe fn Borrow<'a>(this: *mut T::Interface) -> Option<BorrowGuard<'a>> {
+        if !T::CHECK_REENTRANCY {
+            return Some(BorrowGuard::unchecked());
+        }
+        unsafe { (*(this as *mut Self)).borrow.try_borrow() }
+    }
+
+    #[inline(always)]
+    unsafe fn BorrowMut<'a>(this: *mut T::Interface) -> Option<BorrowGuard<'a>> {
+        if !T::CHECK_REENTRANCY {
+            return Some(BorrowGuard::unchecked());
+        }
+        unsafe { (*(this as *mut Self)).borrow.try_borrow_mut() }
+    }
 }
 
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 impl<T: impls::Object> impls::RefCount for Object<T> {
     #[inline(always)]
     fn AddRef(this: *const Self) -> u32 {
-        unsafe { (*this).strong.fetch_add(1, Ordering::Relaxed) }
+        unsafe {
+            let r = (*this).strong.fetch_add(1, Ordering::Relaxed);
+            hardened::check_add::<T>(r);
+            debug::trace_ref!(T, this, Object, AddRef, r.wrapping_add(1));
+            r
+        }
     }
 
     #[inline(always)]
@@ -353,9 +710,15 @@ impl<T: impls::Object> impls::RefCount for Object<T> {
         unsafe {
             let this = this as *mut Self;
             let r = (*this).strong.fetch_sub(1, Ordering::Release);
+            hardened::check_release::<T>(r);
+            debug::trace_ref!(T, this, Object, Release, r.wrapping_sub(1));
             if r == 1 {
                 Self::Drop(this);
             }
+            #[cfg(feature = "gc")]
+            if r > 1 {
+                gc::possible_root(this as _);
+            }
             r
         }
     }
@@ -365,7 +728,7 @@ impl<T: impls::Object> Deref for Object<T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        &*self.val
+        &self.val
     }
 }
 
@@ -400,6 +763,9 @@ impl<T: impls::Object + Hash> Hash for Object<T> {
 pub struct ObjectPtr<T: impls::Object>(ComPtr<Object<T>>);
 
 impl<T: impls::Object> ObjectPtr<T> {
+    /// # Safety
+    ///
+    /// `this` must be the value of a live [`Object<T>`]
     pub unsafe fn clone_this(this: &mut T) -> Self {
         unsafe {
             let obj = Object::FromValue(this);
@@ -407,6 +773,9 @@ impl<T: impls::Object> ObjectPtr<T> {
         }
     }
 
+    /// # Safety
+    ///
+    /// `ptr` must point to the interface of a live [`Object<T>`]
     pub unsafe fn clone_from_ptr(ptr: *mut T::Interface) -> Self {
         unsafe { ObjectPtr(ComPtr::new_clone(NonNull::new_unchecked(ptr as _))) }
     }
@@ -439,31 +808,168 @@ where
 }
 
 impl<T: impls::RefCount> ComPtr<T> {
+    /// # Safety
+    ///
+    /// the pointer must be an [`Object<O>`], [`ComPtr::downcast`] checks it
     pub unsafe fn to_object<O: impls::Object<Interface = T>>(self) -> ObjectPtr<O> {
         unsafe { core::mem::transmute(self) }
     }
+
+    /// # Safety
+    ///
+    /// as [`ComPtr::to_object`]
     pub unsafe fn as_object<O: impls::Object<Interface = T>>(&self) -> &ObjectPtr<O> {
         unsafe { core::mem::transmute(self) }
     }
 }
 
+impl<T: impls::RefCount> ComPtr<T> {
+    /// the vtable pointer stored in the first slot of the interface
+    #[inline(always)]
+    fn raw_vtbl(&self) -> *const core::ffi::c_void {
+        unsafe { *(self.const_ptr() as *const *const core::ffi::c_void) }
+    }
+
+    /// the [`TypeId`] in the header, only valid once the vtable says this is a rust object
+    ///
+    /// `ty` directly follows `base` in both [`Object`] and [`WeakObject`], so its offset does not
+    /// depend on the value type
+    #[inline(always)]
+    unsafe fn header_ty(&self) -> TypeId {
+        let offset = size_of::<T>().next_multiple_of(align_of::<TypeId>());
+        unsafe {
+            (self.const_ptr() as *const u8)
+                .add(offset)
+                .cast::<TypeId>()
+                .read()
+        }
+    }
+
+    /// checks whether this pointer is an [`Object<O>`] created on the rust side
+    ///
+    /// the vtable only tells that it is some rust object, identical vtables of different types may
+    /// be merged by the optimizer, so the [`TypeId`] stored in the header is compared as well
+    pub fn is_object<O: impls::Object<Interface = T> + 'static>(&self) -> bool
+    where
+        T: details::Vtbl<Object<O>>,
+    {
+        ptr::eq(
+            self.raw_vtbl(),
+            <T as details::Vtbl<Object<O>>>::vtbl() as *const _ as *const core::ffi::c_void,
+        ) && unsafe { self.header_ty() } == TypeId::of::<O>()
+    }
+
+    /// checks whether this pointer is a [`WeakObject<O>`] created on the rust side,
+    /// like [`ComPtr::is_object`]
+    pub fn is_weak_object<O: impls::Object<Interface = T> + 'static>(&self) -> bool
+    where
+        T: details::Vtbl<WeakObject<O>>,
+    {
+        ptr::eq(
+            self.raw_vtbl(),
+            <T as details::Vtbl<WeakObject<O>>>::vtbl() as *const _ as *const core::ffi::c_void,
+        ) && unsafe { self.header_ty() } == TypeId::of::<O>()
+    }
+
+    pub fn downcast_ref<O: impls::Object<Interface = T> + 'static>(&self) -> Option<&ObjectPtr<O>>
+    where
+        T: details::Vtbl<Object<O>>,
+    {
+        if self.is_object::<O>() {
+            Some(unsafe { self.as_object() })
+        } else {
+            None
+        }
+    }
+
+    pub fn downcast<O: impls::Object<Interface = T> + 'static>(&self) -> Option<ObjectPtr<O>>
+    where
+        T: details::Vtbl<Object<O>>,
+    {
+        self.downcast_ref::<O>().cloned()
+    }
+
+    pub fn downcast_weak_ref<O: impls::Object<Interface = T> + 'static>(
+        &self,
+    ) -> Option<&WeakObjectPtr<O>>
+    where
+        T: details::Vtbl<WeakObject<O>>,
+    {
+        if self.is_weak_object::<O>() {
+            Some(unsafe { core::mem::transmute::<&Self, &WeakObjectPtr<O>>(self) })
+        } else {
+            None
+        }
+    }
+
+    pub fn downcast_weak<O: impls::Object<Interface = T> + 'static>(
+        &self,
+    ) -> Option<WeakObjectPtr<O>>
+    where
+        T: details::Vtbl<WeakObject<O>>,
+    {
+        self.downcast_weak_ref::<O>().cloned()
+    }
+}
+
 impl<T: impls::Object> ObjectPtr<T> {
-    pub unsafe fn get_mut(&self) -> &mut T {
-        unsafe { &mut self.0.get_mut().val }
+    pub fn strong_count(&self) -> u32 {
+        unsafe { Object::GetStrongCount(self.0.mut_ptr()) }
+    }
+
+    /// mutable access to the value, only when this is the only strong reference
+    ///
+    /// weak references, including the side table of a
+    /// [`WeakRefSource`](crate::weak_ref::WeakRefSource), fail to upgrade until the guard drops
+    pub fn get_mut_unique(&mut self) -> Option<UniqueMut<'_, T>> {
+        unsafe {
+            let ptr = self.0.mut_ptr();
+            UniqueMut::new(&(*ptr).strong, &mut (*ptr).val)
+        }
     }
 }
 
-impl<T: impls::Object> Deref for ObjectPtr<T> {
-    type Target = T;
+impl<T: impls::Object> ObjectPtr<T> {
+    /// takes the value out if this is the only strong reference, otherwise gives the pointer back
+    pub fn try_unwrap(self) -> Result<T, Self> {
+        unsafe {
+            let ptr = self.0.mut_ptr();
+            if (*ptr)
+                .strong
+                .compare_exchange(1, 0, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                return Err(self);
+            }
+            debug::trace_ref!(T, ptr, Object, Release, 0);
+            core::mem::forget(self);
+            Ok(Object::Take(ptr))
+        }
+    }
 
-    fn deref(&self) -> &Self::Target {
-        &*self.0.val
+    /// releases this reference and returns the value if it was the last one
+    pub fn into_inner(self) -> Option<T> {
+        unsafe {
+            let ptr = ManuallyDrop::new(self).0.mut_ptr();
+            let r = (*ptr).strong.fetch_sub(1, Ordering::Release);
+            hardened::check_release::<T>(r);
+            debug::trace_ref!(T, ptr, Object, Release, r.wrapping_sub(1));
+            if r != 1 {
+                #[cfg(feature = "gc")]
+                gc::possible_root(ptr as _);
+                return None;
+            }
+            core::sync::atomic::fence(Ordering::Acquire);
+            Some(Object::Take(ptr))
+        }
     }
 }
 
-impl<T: impls::Object> DerefMut for ObjectPtr<T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut *self.0.val
+impl<T: impls::Object> Deref for ObjectPtr<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0.val
     }
 }
 
@@ -477,24 +983,123 @@ impl<T: impls::Object> AsRef<T::Interface> for ObjectPtr<T> {
 #[derive(Debug)]
 pub struct WeakObject<T: impls::Object> {
     base: T::Interface,
+    ty: TypeId,
     allocator: T::Allocator,
     strong: AtomicU32,
     weak: AtomicU32,
+    borrow: BorrowFlag,
+    on_destroy: DestroyCallbacks,
     val: ManuallyDrop<T>,
 }
 
+/// Identifies a callback registered with [`WeakObjectPtr::on_destroy`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DestroyCookie(usize);
+
+struct DestroyList {
+    next: usize,
+    entries: Vec<(usize, Box<dyn FnOnce() + Send>)>,
+}
+
+/// lazily allocated so objects nobody watches only pay for the pointer
+struct DestroyCallbacks(SpinLock<Option<Box<DestroyList>>>);
+
+impl DestroyCallbacks {
+    const fn new() -> Self {
+        Self(SpinLock::new(None))
+    }
+}
+
+impl Debug for DestroyCallbacks {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let len = self.0.lock().as_ref().map_or(0, |list| list.entries.len());
+        f.debug_struct("DestroyCallbacks")
+            .field("len", &len)
+            .finish()
+    }
+}
+
 impl<T: impls::Object> WeakObject<T> {
     #[inline(never)]
     unsafe fn DropSlow(this: *mut Self) {
         unsafe {
+            if !Self::FinalRelease(this) {
+                return;
+            }
+            #[cfg(feature = "track-objects")]
+            debug::untrack(this as _);
             ManuallyDrop::drop(&mut (*this).val);
+            Self::NotifyDestroyed(this);
             Self::ReleaseWeak_(this);
         }
     }
 
+    /// runs [`impls::Object::final_release`] under a temporary [`FINALIZING`] reference,
+    /// false if the hook resurrected the object
+    unsafe fn FinalRelease(this: *mut Self) -> bool {
+        unsafe {
+            atomic::fence(Ordering::Acquire);
+            (*this).strong.store(FINALIZING + 1, Ordering::Relaxed);
+            (*(*this).val).final_release();
+            let r = (*this).strong.fetch_sub(FINALIZING + 1, Ordering::Release);
+            hardened::check_release::<T>(r);
+            atomic::fence(Ordering::Acquire);
+            r == FINALIZING + 1
+        }
+    }
+
+    /// bookkeeping once the block is initialized, then [`impls::Object::final_construct_weak`]
+    unsafe fn Constructed(this: *mut Self) {
+        module::lock();
+        #[cfg(feature = "track-objects")]
+        debug::track::<T>(
+            this as _,
+            unsafe { &*(*this).val },
+            unsafe { &(*this).strong },
+            unsafe { &(*this).borrow },
+        );
+        debug::trace_ref!(T, this, Object, Create, 1);
+        unsafe {
+            let this = ManuallyDrop::new(WeakObjectPtr(ComPtr::new(NonNull::new_unchecked(this))));
+            T::final_construct_weak(&this);
+        }
+    }
+
+    unsafe fn CancelOnDestroy(this: *mut Self, cookie: DestroyCookie) -> bool {
+        let removed = unsafe {
+            let mut list = (*this).on_destroy.0.lock();
+            let Some(list) = list.as_mut() else {
+                return false;
+            };
+            let Some(i) = list.entries.iter().position(|(id, _)| *id == cookie.0) else {
+                return false;
+            };
+            list.entries.remove(i)
+        };
+        // dropped outside the lock, the closure may own anything
+        drop(removed);
+        true
+    }
+
+    /// runs and clears the destruction callbacks
+    unsafe fn NotifyDestroyed(this: *mut Self) {
+        unsafe {
+            let list = (*this).on_destroy.0.lock().take();
+            if let Some(list) = list {
+                for (_, f) in list.entries {
+                    f();
+                }
+            }
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `this` must be a live block and the caller must own the weak reference it releases
     pub unsafe fn ReleaseWeak_(this: *mut Self) -> u32 {
         unsafe {
             let r = (*this).weak.fetch_sub(1, Ordering::Release);
+            hardened::check_release::<T>(r);
             if r == 1 {
                 Self::Drop(this);
             }
@@ -504,15 +1109,46 @@ impl<T: impls::Object> WeakObject<T> {
 
     unsafe fn Drop(this: *mut Self) {
         unsafe {
+            #[cfg(feature = "hardened")]
+            if T::Allocator::QUARANTINE {
+                hardened::quarantine::<T::Interface>(this as _, Self::Dealloc);
+            } else {
+                Self::Dealloc(this as _);
+            }
+            #[cfg(not(feature = "hardened"))]
+            Self::Dealloc(this as _);
+        }
+        module::unlock();
+    }
+
+    unsafe fn Dealloc(this: *mut u8) {
+        unsafe {
+            let this = this as *mut Self;
             let allocator = ptr::read(&(*this).allocator);
             allocator.dealloc(this as _, Layout::new::<Self>());
         }
     }
+
+    /// moves the value out and releases the weak reference held by the strong ones,
+    /// the strong count must already be zero
+    unsafe fn Take(this: *mut Self) -> T {
+        unsafe {
+            #[cfg(feature = "track-objects")]
+            debug::untrack(this as _);
+            (*(*this).val).detach();
+            let val = ManuallyDrop::take(&mut (*this).val);
+            Self::NotifyDestroyed(this);
+            Self::ReleaseWeak_(this);
+            val
+        }
+    }
 }
+
+#[allow(clippy::missing_safety_doc)]
 impl<T: impls::Object> WeakObject<T> {
     #[inline(always)]
     pub unsafe fn GetStrongCount(this: *mut Self) -> u32 {
-        unsafe { (*this).strong.load(Ordering::Acquire) }
+        unsafe { (*this).strong.load(Ordering::Acquire) & !FINALIZING }
     }
 
     #[inline(always)]
@@ -545,6 +1181,7 @@ impl<T: impls::Object<Allocator = DefaultObjectAllocator>> WeakObject<T>
 where
     T::Interface: details::Vtbl<Self>,
 {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(val: T) -> *mut T::Interface {
         Self::make(val) as _
     }
@@ -554,24 +1191,37 @@ where
             let b = ().alloc(Layout::new::<Self>()) as *mut Self;
             b.write(Self {
                 base: T::Interface::new(<T::Interface as details::Vtbl<Self>>::vtbl()),
+                ty: type_id::<T>(),
                 allocator: (),
                 strong: AtomicU32::new(1),
                 weak: AtomicU32::new(1),
+                borrow: BorrowFlag::new(),
+                on_destroy: DestroyCallbacks::new(),
                 val: ManuallyDrop::new(val),
             });
+            Self::Constructed(b);
             b as _
         }
     }
 
+    /// builds the value in place through `init`
+    ///
+    /// # Safety
+    ///
+    /// `init` must fully initialize the value behind the pointer it is given
     pub unsafe fn inplace(init: impl FnOnce(*mut T)) -> WeakObjectPtr<T> {
         unsafe {
             let b = ().alloc(Layout::new::<Self>()) as *mut Self;
             pmp!(b; .base).write(T::Interface::new(
                 <T::Interface as details::Vtbl<Self>>::vtbl(),
             ));
+            pmp!(b; .ty).write(type_id::<T>());
             pmp!(b; .strong).write(AtomicU32::new(1));
             pmp!(b; .weak).write(AtomicU32::new(1));
+            pmp!(b; .borrow).write(BorrowFlag::new());
+            pmp!(b; .on_destroy).write(DestroyCallbacks::new());
             init(pmp!(b; .val) as *mut _);
+            Self::Constructed(b);
             WeakObjectPtr(ComPtr::new(NonNull::new_unchecked(b)))
         }
     }
@@ -590,15 +1240,24 @@ where
             let b = allocator.alloc(Layout::new::<Self>()) as *mut Self;
             b.write(Self {
                 base: T::Interface::new(<T::Interface as details::Vtbl<Self>>::vtbl()),
+                ty: type_id::<T>(),
                 allocator,
                 strong: AtomicU32::new(1),
                 weak: AtomicU32::new(1),
+                borrow: BorrowFlag::new(),
+                on_destroy: DestroyCallbacks::new(),
                 val: ManuallyDrop::new(val),
             });
+            Self::Constructed(b);
             b as _
         }
     }
 
+    /// builds the value in place through `init`
+    ///
+    /// # Safety
+    ///
+    /// `init` must fully initialize the value behind the pointer it is given
     pub unsafe fn inplace_with(
         allocator: T::Allocator,
         init: impl FnOnce(*mut T),
@@ -608,12 +1267,16 @@ where
             pmp!(b; .base).write(T::Interface::new(
                 <T::Interface as details::Vtbl<Self>>::vtbl(),
             ));
+            pmp!(b; .ty).write(type_id::<T>());
             if size_of::<T::Allocator>() > 0 {
                 pmp!(b; .allocator).write(allocator);
             }
             pmp!(b; .strong).write(AtomicU32::new(1));
             pmp!(b; .weak).write(AtomicU32::new(1));
+            pmp!(b; .borrow).write(BorrowFlag::new());
+            pmp!(b; .on_destroy).write(DestroyCallbacks::new());
             init(pmp!(b; .val) as *mut _);
+            Self::Constructed(b);
             WeakObjectPtr(ComPtr::new(NonNull::new_unchecked(b)))
         }
     }
@@ -647,7 +1310,10 @@ impl<T: impls::Object> impls::ObjectBox for WeakObject<T> {
     unsafe fn AddRef(this: *mut T::Interface) -> u32 {
         unsafe {
             let this = this as *mut Self;
-            (*this).strong.fetch_add(1, Ordering::Relaxed)
+            let r = (*this).strong.fetch_add(1, Ordering::Relaxed);
+            hardened::check_add::<T>(r);
+            debug::trace_ref!(T, this, Object, AddRef, r.wrapping_add(1));
+            r
         }
     }
 
@@ -655,12 +1321,30 @@ impl<T: impls::Object> impls::ObjectBox for WeakObject<T> {
         unsafe {
             let this = this as *mut Self;
             let r = (*this).strong.fetch_sub(1, Ordering::Release);
+            hardened::check_release::<T>(r);
+            debug::trace_ref!(T, this, Object, Release, r.wrapping_sub(1));
             if r == 1 {
                 Self::DropSlow(this);
             }
             r
         }
     }
+
+    #[inline(always)]
+    unsafe fn Borrow<'a>(this: *mut T::Interface) -> Option<BorrowGuard<'a>> {
+        if !T::CHECK_REENTRANCY {
+            return Some(BorrowGuard::unchecked());
+        }
+        unsafe { (*(this as *mut Self)).borrow.try_borrow() }
+    }
+
+    #[inline(always)]
+    unsafe fn BorrowMut<'a>(this: *mut T::Interface) -> Option<BorrowGuard<'a>> {
+        if !T::CHECK_REENTRANCY {
+            return Some(BorrowGuard::unchecked());
+        }
+        unsafe { (*(this as *mut Self)).borrow.try_borrow_mut() }
+    }
 }
 
 impl<T: impls::Object> impls::ObjectBoxWeak for WeakObject<T>
@@ -670,30 +1354,36 @@ where
     unsafe fn AddRefWeak(this: *mut T::Interface) -> u32 {
         unsafe {
             let this = this as *mut Self;
-            (*this).weak.fetch_add(1, Ordering::Relaxed)
+            let r = (*this).weak.fetch_add(1, Ordering::Relaxed);
+            hardened::check_add::<T>(r);
+            debug::trace_ref!(T, this, Object, AddRefWeak, r.wrapping_add(1));
+            r
         }
     }
 
     unsafe fn ReleaseWeak(this: *mut T::Interface) -> u32 {
-        unsafe { Self::ReleaseWeak_(this as _) }
+        unsafe {
+            let r = Self::ReleaseWeak_(this as _);
+            debug::trace_ref!(T, this, Object, ReleaseWeak, r.wrapping_sub(1));
+            r
+        }
     }
 
     unsafe fn TryUpgrade(this: *mut T::Interface) -> bool {
         unsafe {
             let this = this as *mut Self;
 
-            #[inline]
-            fn checked_increment(n: u32) -> Option<u32> {
-                if n == 0 {
-                    return None;
-                }
-                Some(n + 1)
+            let r =
+                (*this)
+                    .strong
+                    .fetch_update(Ordering::Acquire, Ordering::Relaxed, checked_upgrade);
+
+            if let Ok(n) = r {
+                hardened::check_add::<T>(n);
+                debug::trace_ref!(T, this, Object, Upgrade, n.wrapping_add(1));
             }
 
-            (*this)
-                .strong
-                .fetch_update(Ordering::Acquire, Ordering::Relaxed, checked_increment)
-                .is_ok()
+            r.is_ok()
         }
     }
 
@@ -717,14 +1407,22 @@ where
     }
 }
 
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 impl<T: impls::Object> impls::RefCount for WeakObject<T> {
     fn AddRef(this: *const Self) -> u32 {
-        unsafe { (*this).strong.fetch_add(1, Ordering::Relaxed) }
+        unsafe {
+            let r = (*this).strong.fetch_add(1, Ordering::Relaxed);
+            hardened::check_add::<T>(r);
+            debug::trace_ref!(T, this, Object, AddRef, r.wrapping_add(1));
+            r
+        }
     }
 
     fn Release(this: *const Self) -> u32 {
         unsafe {
             let r = (*this).strong.fetch_sub(1, Ordering::Release);
+            hardened::check_release::<T>(r);
+            debug::trace_ref!(T, this, Object, Release, r.wrapping_sub(1));
             if r == 1 {
                 Self::DropSlow(this as _);
             }
@@ -733,29 +1431,36 @@ impl<T: impls::Object> impls::RefCount for WeakObject<T> {
     }
 }
 
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 impl<T: impls::Object> impls::WeakRefCount for WeakObject<T> {
     fn AddRefWeak(this: *const Self) -> u32 {
-        unsafe { (*this).weak.fetch_add(1, Ordering::Relaxed) }
+        unsafe {
+            let r = (*this).weak.fetch_add(1, Ordering::Relaxed);
+            hardened::check_add::<T>(r);
+            debug::trace_ref!(T, this, Object, AddRefWeak, r.wrapping_add(1));
+            r
+        }
     }
 
     fn ReleaseWeak(this: *const Self) -> u32 {
-        unsafe { Self::ReleaseWeak_(this as _) }
+        unsafe {
+            let r = Self::ReleaseWeak_(this as _);
+            debug::trace_ref!(T, this, Object, ReleaseWeak, r.wrapping_sub(1));
+            r
+        }
     }
 
     fn TryUpgrade(this: *const Self) -> bool {
-        #[inline]
-        fn checked_increment(n: u32) -> Option<u32> {
-            if n == 0 {
-                return None;
-            }
-            Some(n + 1)
-        }
-
         unsafe {
-            (*this)
-                .strong
-                .fetch_update(Ordering::Acquire, Ordering::Relaxed, checked_increment)
-                .is_ok()
+            let r =
+                (*this)
+                    .strong
+                    .fetch_update(Ordering::Acquire, Ordering::Relaxed, checked_upgrade);
+            if let Ok(n) = r {
+                hardened::check_add::<T>(n);
+                debug::trace_ref!(T, this, Object, Upgrade, n.wrapping_add(1));
+            }
+            r.is_ok()
         }
     }
 }
@@ -764,7 +1469,7 @@ impl<T: impls::Object> Deref for WeakObject<T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        &*self.val
+        &self.val
     }
 }
 
@@ -794,10 +1499,19 @@ impl<T: impls::Object + Hash> Hash for WeakObject<T> {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(transparent)]
 pub struct WeakObjectPtr<T: impls::Object>(ComPtr<WeakObject<T>>);
 
+impl<T: impls::Object> Clone for WeakObjectPtr<T>
+where
+    T::Interface: RefCount,
+{
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
 impl<T: impls::Object> WeakObjectPtr<T>
 where
     T::Interface: RefCount,
@@ -811,11 +1525,68 @@ where
     }
 }
 
+impl<T: impls::Object> WeakObjectPtr<T> {
+    pub fn strong_count(&self) -> u32 {
+        unsafe { WeakObject::GetStrongCount(self.0.mut_ptr()) }
+    }
+
+    pub fn weak_count(&self) -> u32 {
+        unsafe { WeakObject::GetWeakCount(self.0.mut_ptr()) - 1 }
+    }
+
+    /// mutable access to the value, only when this is the only strong reference and no weak references exist
+    pub fn get_mut_unique(&mut self) -> Option<UniqueMut<'_, T>> {
+        if self.weak_count() != 0 {
+            return None;
+        }
+        unsafe {
+            let ptr = self.0.mut_ptr();
+            UniqueMut::new(&(*ptr).strong, &mut (*ptr).val)
+        }
+    }
+}
+
+impl<T: impls::Object> WeakObjectPtr<T> {
+    /// takes the value out if this is the only strong reference, otherwise gives the pointer back
+    ///
+    /// outstanding weak references stay valid but can no longer be upgraded
+    pub fn try_unwrap(self) -> Result<T, Self> {
+        unsafe {
+            let ptr = self.0.mut_ptr();
+            if (*ptr)
+                .strong
+                .compare_exchange(1, 0, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                return Err(self);
+            }
+            debug::trace_ref!(T, ptr, Object, Release, 0);
+            core::mem::forget(self);
+            Ok(WeakObject::Take(ptr))
+        }
+    }
+
+    /// releases this reference and returns the value if it was the last strong one
+    pub fn into_inner(self) -> Option<T> {
+        unsafe {
+            let ptr = ManuallyDrop::new(self).0.mut_ptr();
+            let r = (*ptr).strong.fetch_sub(1, Ordering::Release);
+            hardened::check_release::<T>(r);
+            debug::trace_ref!(T, ptr, Object, Release, r.wrapping_sub(1));
+            if r != 1 {
+                return None;
+            }
+            core::sync::atomic::fence(Ordering::Acquire);
+            Some(WeakObject::Take(ptr))
+        }
+    }
+}
+
 impl<T: impls::Object> Deref for WeakObjectPtr<T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        &*self.0.val
+        &self.0.val
     }
 }
 
@@ -825,7 +1596,44 @@ impl<T: impls::Object> AsRef<T::Interface> for WeakObjectPtr<T> {
     }
 }
 
-#[derive(Debug)]
+impl<T: impls::Object> WeakObjectPtr<T> {
+    pub fn as_com(&self) -> &ComPtr<T::Interface>
+    where
+        T::Interface: RefCount,
+    {
+        unsafe { core::mem::transmute(self) }
+    }
+
+    pub fn downgrade(&self) -> WeakObjectWeak<T> {
+        WeakObjectWeak(self.0.downgrade())
+    }
+
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.0.ptr() == other.0.ptr()
+    }
+
+    /// Registers `f` to run once the object is destroyed, after the value has been dropped
+    /// or moved out. Weak references can no longer be upgraded by then.
+    pub fn on_destroy(&self, f: impl FnOnce() + Send + 'static) -> DestroyCookie {
+        let mut list = self.0.on_destroy.0.lock();
+        let list = list.get_or_insert_with(|| {
+            Box::new(DestroyList {
+                next: 0,
+                entries: Vec::new(),
+            })
+        });
+        let id = list.next;
+        list.next += 1;
+        list.entries.push((id, Box::new(f)));
+        DestroyCookie(id)
+    }
+
+    /// Unregisters a destruction callback, false if it already ran or was removed
+    pub fn cancel_on_destroy(&self, cookie: DestroyCookie) -> bool {
+        unsafe { WeakObject::CancelOnDestroy(self.0.ptr().as_ptr(), cookie) }
+    }
+}
+
 #[repr(transparent)]
 pub struct WeakObjectWeak<T: impls::Object>(ComWeak<WeakObject<T>>);
 
@@ -837,3 +1645,320 @@ where
         Self(self.0.clone())
     }
 }
+
+impl<T: impls::Object> Debug for WeakObjectWeak<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("WeakObjectWeak")
+            .field(&self.0.ptr())
+            .finish()
+    }
+}
+
+impl<T: impls::Object> WeakObjectWeak<T> {
+    pub fn upgrade(&self) -> Option<WeakObjectPtr<T>> {
+        self.0.upgrade().map(WeakObjectPtr)
+    }
+
+    pub fn strong_count(&self) -> u32 {
+        unsafe { WeakObject::GetStrongCount(self.0.ptr().as_ptr()) }
+    }
+
+    /// number of weak references, zero once the value has been dropped
+    pub fn weak_count(&self) -> u32 {
+        if self.strong_count() == 0 {
+            return 0;
+        }
+        unsafe { WeakObject::GetWeakCount(self.0.ptr().as_ptr()).saturating_sub(1) }
+    }
+
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.0.ptr() == other.0.ptr()
+    }
+
+    /// [`WeakObjectPtr::cancel_on_destroy`] without needing the object alive
+    pub fn cancel_on_destroy(&self, cookie: DestroyCookie) -> bool {
+        unsafe { WeakObject::CancelOnDestroy(self.0.ptr().as_ptr(), cookie) }
+    }
+
+    pub fn leak(self) -> *mut T::Interface {
+        self.0.leak() as _
+    }
+
+    pub fn to_com(self) -> ComWeak<T::Interface>
+    where
+        T::Interface: WeakRefCount,
+    {
+        unsafe { core::mem::transmute(self) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::sync::Arc;
+    use core::sync::atomic::AtomicBool;
+
+    use crate::{object::*, sync::SpinLock, *};
+
+    #[object(IUnknown)]
+    #[derive(Debug)]
+    pub struct Foo {
+        a: u32,
+    }
+
+    #[object(IUnknown)]
+    #[derive(Debug)]
+    pub struct Bar {
+        a: u64,
+        b: u64,
+    }
+
+    #[object(IWeak)]
+    #[derive(Debug)]
+    pub struct Baz {
+        a: u32,
+    }
+
+    impl impls::IWeak for Baz {}
+
+    /// same layout and vtable code as `Foo`, only the type differs
+    #[object(IUnknown)]
+    #[derive(Debug)]
+    pub struct Qux {
+        b: u32,
+    }
+
+    #[test]
+    fn downcast() {
+        let foo = Foo { a: 1 }.make_com();
+        let bar = Bar { a: 2, b: 3 }.make_com();
+        assert_eq!(foo.downcast::<Foo>().unwrap().a, 1);
+        assert!(foo.downcast::<Bar>().is_none());
+        assert!(bar.downcast_ref::<Foo>().is_none());
+        assert_eq!(bar.downcast_ref::<Bar>().unwrap().b, 3);
+
+        let qux = Qux { b: 5 }.make_com();
+        assert!(!foo.is_object::<Qux>());
+        assert!(qux.downcast::<Foo>().is_none());
+        assert_eq!(qux.downcast::<Qux>().unwrap().b, 5);
+
+        let baz = Baz { a: 4 }.make_com_weak();
+        assert!(baz.is_weak_object::<Baz>());
+        assert_eq!(baz.downcast_weak::<Baz>().unwrap().a, 4);
+    }
+
+    #[object(IUnknown, check_reentrancy)]
+    #[derive(Debug)]
+    pub struct Checked {
+        a: u32,
+    }
+
+    #[object(IUnknown, final_construct, final_release)]
+    #[derive(Debug)]
+    pub struct Hooked {
+        constructed: AtomicBool,
+        released: Arc<AtomicU32>,
+    }
+
+    impl Hooked {
+        fn on_final_construct(this: &ObjectPtr<Self>) {
+            this.constructed.store(true, Ordering::Relaxed);
+        }
+
+        fn on_final_release(&mut self) {
+            // still reachable, taking and dropping a reference must not free it again
+            drop(unsafe { ObjectPtr::clone_this(self) });
+            self.released.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn lifecycle_hooks() {
+        let released = Arc::new(AtomicU32::new(0));
+        let a = Hooked {
+            constructed: AtomicBool::new(false),
+            released: released.clone(),
+        }
+        .make_object();
+        assert!(a.constructed.load(Ordering::Relaxed));
+        let b = a.clone();
+        drop(a);
+        assert_eq!(released.load(Ordering::Relaxed), 0);
+        drop(b);
+        assert_eq!(released.load(Ordering::Relaxed), 1);
+        assert_eq!(Arc::strong_count(&released), 1);
+    }
+
+    #[object(IWeak, final_construct_weak, final_release)]
+    pub struct Dying {
+        this: SpinLock<Option<WeakObjectWeak<Dying>>>,
+        upgraded: Arc<AtomicBool>,
+    }
+
+    impl impls::IWeak for Dying {}
+
+    impl Dying {
+        fn on_final_construct_weak(this: &WeakObjectPtr<Self>) {
+            *this.this.lock() = Some(this.downgrade());
+        }
+
+        fn on_final_release(&mut self) {
+            // what a concurrent upgrade would see
+            let this = self.this.get_mut().take().unwrap();
+            self.upgraded
+                .store(this.upgrade().is_some(), Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn no_upgrade_while_finalizing() {
+        let upgraded = Arc::new(AtomicBool::new(true));
+        let a = Dying {
+            this: SpinLock::new(None),
+            upgraded: upgraded.clone(),
+        }
+        .make_object_weak();
+        let weak = a.downgrade();
+        drop(a);
+        assert!(!upgraded.load(Ordering::Relaxed));
+        assert!(weak.upgrade().is_none());
+        assert_eq!(Arc::strong_count(&upgraded), 1);
+    }
+
+    #[test]
+    fn on_destroy() {
+        let fired = Arc::new(AtomicU32::new(0));
+        let a = Baz { a: 1 }.make_object_weak();
+        let f = fired.clone();
+        a.on_destroy(move || _ = f.fetch_add(1, Ordering::Relaxed));
+        let f = fired.clone();
+        let cookie = a.on_destroy(move || _ = f.fetch_add(10, Ordering::Relaxed));
+        let weak = a.downgrade();
+        assert!(weak.cancel_on_destroy(cookie));
+        assert!(!a.cancel_on_destroy(cookie));
+        drop(a);
+        assert_eq!(fired.load(Ordering::Relaxed), 1);
+        assert!(weak.upgrade().is_none());
+
+        let a = Baz { a: 2 }.make_object_weak();
+        let f = fired.clone();
+        a.on_destroy(move || _ = f.fetch_add(100, Ordering::Relaxed));
+        assert_eq!(a.try_unwrap().unwrap().a, 2);
+        assert_eq!(fired.load(Ordering::Relaxed), 101);
+        assert_eq!(Arc::strong_count(&fired), 1);
+    }
+
+    #[test]
+    fn get_mut_unique() {
+        let mut a = Foo { a: 1 }.make_object();
+        a.get_mut_unique().unwrap().a = 2;
+        let b = a.clone();
+        assert!(a.get_mut_unique().is_none());
+        drop(b);
+        assert_eq!(a.get_mut_unique().unwrap().a, 2);
+    }
+
+    #[test]
+    fn try_unwrap() {
+        let a = Foo { a: 1 }.make_object();
+        let b = a.clone();
+        let a = a.try_unwrap().unwrap_err();
+        assert!(b.into_inner().is_none());
+        assert_eq!(a.try_unwrap().unwrap().a, 1);
+
+        let a = Baz { a: 2 }.make_object_weak();
+        let weak = a.downgrade();
+        assert_eq!(a.try_unwrap().unwrap().a, 2);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn weak_object_weak() {
+        let a = Baz { a: 1 }.make_object_weak();
+        let w = a.downgrade();
+        let w2 = w.clone();
+        assert_eq!(w.strong_count(), 1);
+        assert_eq!(w.weak_count(), 2);
+        assert!(w.ptr_eq(&w2));
+        let b = w.upgrade().unwrap();
+        assert!(a.ptr_eq(&b));
+        assert_eq!(b.strong_count(), 2);
+        drop((a, b));
+        assert!(w.upgrade().is_none());
+        assert_eq!(w.strong_count(), 0);
+        assert_eq!(w2.weak_count(), 0);
+    }
+
+    mod reenter {
+        use crate::{Guid, HResult, IUnknown, Interface};
+
+        #[cocom::interface("0e6b3c42-95d1-4a7f-b8e3-6c2f1d0a5b97")]
+        pub trait IReenter: IUnknown {
+            fn Poke(&self, again: *const IReenter) -> HResult;
+        }
+
+        test_details! {
+            IReenter: IUnknown {
+                fn Poke(&mut self, again: *const IReenter) -> HResult;
+            }
+        }
+
+        pub mod impls {
+            use crate::HResult;
+            pub use crate::impls::*;
+
+            pub trait IReenter: IUnknown {
+                fn Poke(&mut self, again: *const super::IReenter) -> HResult;
+            }
+        }
+    }
+
+    #[object(reenter::IReenter, check_reentrancy)]
+    #[derive(Debug)]
+    pub struct Poked {
+        pokes: u32,
+    }
+
+    impl reenter::impls::IReenter for Poked {
+        fn Poke(&mut self, again: *const reenter::IReenter) -> HResult {
+            self.pokes += 1;
+            if again.is_null() {
+                return HResult::ok();
+            }
+            unsafe { (*again).Poke(ptr::null()) }
+        }
+    }
+
+    #[test]
+    fn reentrant_thunk_call() {
+        let a = Poked { pokes: 0 }.make_object();
+        let b = Poked { pokes: 0 }.make_com();
+        let this = a.as_com().const_ptr();
+        assert_eq!(a.as_com().Poke(b.const_ptr()), HResult::ok());
+        assert_eq!(
+            a.as_com().Poke(this),
+            HResult::from(HResultE::IllegalMethodCall)
+        );
+        assert_eq!(a.pokes, 2);
+    }
+
+    #[test]
+    fn reentrancy() {
+        let a = Checked { a: 1 }.make_com();
+        unsafe {
+            let this = a.mut_ptr();
+            let outer = Object::<Checked>::BorrowMut(this).unwrap();
+            assert!(Object::<Checked>::BorrowMut(this).is_none());
+            assert!(Object::<Checked>::Borrow(this).is_none());
+            drop(outer);
+            let shared = Object::<Checked>::Borrow(this).unwrap();
+            assert!(Object::<Checked>::Borrow(this).is_some());
+            assert!(Object::<Checked>::BorrowMut(this).is_none());
+            drop(shared);
+            assert!(Object::<Checked>::BorrowMut(this).is_some());
+
+            let b = Foo { a: 1 }.make_com();
+            let _outer = Object::<Foo>::BorrowMut(b.mut_ptr()).unwrap();
+            assert!(Object::<Foo>::BorrowMut(b.mut_ptr()).is_some());
+        }
+    }
+}
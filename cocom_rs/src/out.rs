@@ -0,0 +1,147 @@
+//! `Out` and `OutComPtr`, out parameters the thunk already checked for null
+//!
+//! The vtable slot keeps the raw pointer; the thunk answers a null one with
+//! [`HResultE::Pointer`](crate::HResultE::Pointer) before the implementation is called.
+
+use core::{fmt::Debug, marker::PhantomData, ptr::NonNull};
+
+use crate::{ComPtr, impls};
+
+/// A non null `*mut T` out parameter, written at most once
+#[repr(transparent)]
+pub struct Out<'a, T> {
+    ptr: NonNull<T>,
+    _p: PhantomData<&'a mut T>,
+}
+
+unsafe impl<T: Send> Send for Out<'_, T> {}
+unsafe impl<T: Sync> Sync for Out<'_, T> {}
+
+impl<'a, T> Out<'a, T> {
+    /// `None` for a null `ptr`
+    ///
+    /// # Safety
+    /// `ptr` must be null or valid for writes for `'a`
+    pub unsafe fn new(ptr: *mut T) -> Option<Self> {
+        NonNull::new(ptr).map(|ptr| Self {
+            ptr,
+            _p: PhantomData,
+        })
+    }
+
+    pub const fn as_ptr(&self) -> *mut T {
+        self.ptr.as_ptr()
+    }
+
+    /// does not drop what was there, the caller's storage may be uninitialized
+    pub fn write(self, value: T) -> &'a mut T {
+        unsafe {
+            self.ptr.write(value);
+            &mut *self.ptr.as_ptr()
+        }
+    }
+}
+
+impl<'a, T> From<&'a mut T> for Out<'a, T> {
+    fn from(value: &'a mut T) -> Self {
+        Self {
+            ptr: value.into(),
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<T> Debug for Out<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("Out").field(&self.ptr).finish()
+    }
+}
+
+/// A non null `*mut *mut I` out parameter that receives one reference, null until written
+#[repr(transparent)]
+pub struct OutComPtr<'a, I: impls::RefCount> {
+    ptr: NonNull<*mut I>,
+    _p: PhantomData<&'a mut Option<ComPtr<I>>>,
+}
+
+unsafe impl<I: impls::RefCount> Send for OutComPtr<'_, I> {}
+unsafe impl<I: impls::RefCount> Sync for OutComPtr<'_, I> {}
+
+impl<'a, I: impls::RefCount> OutComPtr<'a, I> {
+    /// `None` for a null `ptr`, otherwise clears `*ptr` so every return leaves null or a reference
+    ///
+    /// # Safety
+    /// `ptr` must be null or valid for writes for `'a`
+    pub unsafe fn new(ptr: *mut *mut I) -> Option<Self> {
+        let ptr = NonNull::new(ptr)?;
+        unsafe { ptr.write(core::ptr::null_mut()) };
+        Some(Self {
+            ptr,
+            _p: PhantomData,
+        })
+    }
+
+    /// for calls that write a reference themselves, like `QueryInterface`
+    pub const fn as_ptr(&self) -> *mut *mut I {
+        self.ptr.as_ptr()
+    }
+
+    /// hands the reference to the caller, `None` writes null
+    pub fn write(self, value: impl Into<Option<ComPtr<I>>>) {
+        let value = value.into().map_or(core::ptr::null_mut(), ComPtr::leak);
+        unsafe { self.ptr.write(value) };
+    }
+}
+
+impl<'a, I: impls::RefCount> From<&'a mut Option<ComPtr<I>>> for OutComPtr<'a, I> {
+    /// drops the old value, `Option<ComPtr<I>>` has the layout of `*mut I`
+    fn from(value: &'a mut Option<ComPtr<I>>) -> Self {
+        *value = None;
+        Self {
+            ptr: NonNull::from(value).cast(),
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<I: impls::RefCount> Debug for OutComPtr<'_, I> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("OutComPtr").field(&self.ptr).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::ptr;
+
+    use crate::{async_op::*, *};
+
+    #[test]
+    fn write() {
+        let mut v = 0u32;
+        *Out::from(&mut v).write(3) += 1;
+        assert_eq!(v, 4);
+        assert!(unsafe { Out::<u32>::new(ptr::null_mut()) }.is_none());
+
+        let op = AsyncOperation::from_future(async { Ok(()) });
+        let obj = op.query_interface::<IUnknown>().unwrap();
+        let mut slot = Some(obj.clone());
+        OutComPtr::from(&mut slot).write(None);
+        assert!(slot.is_none());
+        OutComPtr::from(&mut slot).write(obj.clone());
+        assert_eq!(slot.map(|p| p.ptr()), Some(obj.ptr()));
+    }
+
+    #[test]
+    fn null_out_is_rejected_by_the_thunk() {
+        let op = AsyncOperation::from_future(async { Ok(()) });
+        assert_eq!(
+            op.GetResult(ptr::null_mut()),
+            HResult::from(HResultE::Pointer)
+        );
+
+        let mut out = ptr::NonNull::<IUnknown>::dangling().as_ptr();
+        assert_eq!(op.GetResult(&mut out), HResult::ok());
+        assert!(out.is_null());
+    }
+}
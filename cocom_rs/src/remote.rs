@@ -0,0 +1,461 @@
+//! Calling objects that live in another process, enabled by the `std` feature.
+//!
+//! A [`Server`] exports objects over a [`Transport`] and a [`Client`] hands out proxies for
+//! them. Proxies and stubs are per interface: an interface opts in by implementing
+//! [`RemoteInterface`] (generated by the Rust output with `Remote` enabled) and both processes
+//! [`register`] it. Parameters cross the connection through [`Param`]: [`Pod`] values, `bool`,
+//! `HResult`, pointers to [`Pod`] values, `ComPtr<I>` and `*mut *mut I` out parameters as
+//! remote references.
+//!
+//! Remote references follow the distributed refcount rules: each reference the server hands
+//! out is one remote reference, a proxy keeps all references it received for the same object
+//! and returns them in a single `Release` once its local count drops to zero. `AddRef` and
+//! `Release` on a proxy never cross the connection. When the client goes away the server
+//! releases everything it still held for it.
+//!
+//! Calls are synchronous and one way: the server cannot call back into the client, so passing
+//! an object that is not a proxy from the same connection fails with `NotImpl`. A call that
+//! cannot complete returns the failure from `HResult` methods and is dropped for `()` ones;
+//! methods returning anything else abort the process, see [`Ret`].
+
+mod proxy;
+mod stub;
+mod wire;
+
+pub use proxy::*;
+pub use stub::*;
+pub use wire::*;
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::ffi::c_void;
+use std::{
+    io::{self, Read, Write},
+    sync::Mutex,
+};
+
+use crate::{ComPtr, ComResult, Guid, HResultE, IUnknown, Interface, impls};
+
+/// An interface that can be called through a [`Client`] proxy
+///
+/// # Safety
+/// `PROXY_VTBL` must forward every slot with [`proxy_call`] using this interface's GUID and
+/// the method's index among the interface's own methods, and `stub` must decode the same
+/// parameter types for the same index.
+pub unsafe trait RemoteInterface: Interface + impls::RefCount {
+    /// vtable of the proxy, parent slots come from the parent's proxy vtable
+    const PROXY_VTBL: Self::VitualTable;
+    /// [`RemoteInterface::PROXY_VTBL`] as a reference usable from const contexts
+    const PROXY_VTBL_REF: &'static Self::VitualTable;
+
+    /// performs call `method` of this interface, not counting parent methods, on `this`
+    fn stub(this: &Self, method: u32, call: &mut StubCall) -> ComResult<()>;
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// registry
+
+type StubFn = fn(obj: &ComPtr<IUnknown>, method: u32, call: &mut StubCall) -> ComResult<()>;
+
+#[derive(Clone, Copy)]
+struct Registered {
+    proxy_vtbl: *const c_void,
+    stub: StubFn,
+}
+
+unsafe impl Send for Registered {}
+
+static INTERFACES: Mutex<BTreeMap<Guid, Registered>> = Mutex::new(BTreeMap::new());
+
+/// Makes `I` available to proxies and stubs of every connection in this process
+pub fn register<I: RemoteInterface>() {
+    fn stub<I: RemoteInterface>(
+        obj: &ComPtr<IUnknown>,
+        method: u32,
+        call: &mut StubCall,
+    ) -> ComResult<()> {
+        let this = obj.query_interface::<I>()?;
+        I::stub(&this, method, call)
+    }
+
+    INTERFACES.lock().unwrap().insert(
+        I::GUID,
+        Registered {
+            proxy_vtbl: I::PROXY_VTBL_REF as *const I::VitualTable as _,
+            stub: stub::<I>,
+        },
+    );
+}
+
+fn registered(iid: &Guid) -> Option<Registered> {
+    if *iid == IUnknown::GUID {
+        return Some(Registered {
+            proxy_vtbl: <IUnknown as RemoteInterface>::PROXY_VTBL_REF as *const _ as _,
+            stub: |_, _, _| Err(HResultE::NotImpl.into()),
+        });
+    }
+    INTERFACES.lock().unwrap().get(iid).copied()
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// transport
+
+/// Carries whole frames between a client and a server
+pub trait Transport: Send {
+    fn send(&mut self, frame: &[u8]) -> io::Result<()>;
+    /// next frame, `None` once the peer closed the connection
+    fn recv(&mut self) -> io::Result<Option<Vec<u8>>>;
+}
+
+/// frames larger than this are rejected as corrupt
+pub const MAX_FRAME: usize = 64 << 20;
+
+/// Length prefixed frames over a byte stream, e.g. a pair of pipes or a socket
+#[derive(Debug)]
+pub struct StreamTransport<R, W> {
+    reader: R,
+    writer: W,
+}
+
+impl<R: Read + Send, W: Write + Send> StreamTransport<R, W> {
+    /// for a helper process: `new(child.stdout, child.stdin)` on one side,
+    /// `new(io::stdin(), io::stdout())` on the other
+    pub fn new(reader: R, writer: W) -> Self {
+        Self { reader, writer }
+    }
+}
+
+#[cfg(unix)]
+impl StreamTransport<std::os::unix::net::UnixStream, std::os::unix::net::UnixStream> {
+    pub fn unix(stream: std::os::unix::net::UnixStream) -> io::Result<Self> {
+        Ok(Self::new(stream.try_clone()?, stream))
+    }
+}
+
+impl<R: Read + Send, W: Write + Send> Transport for StreamTransport<R, W> {
+    fn send(&mut self, frame: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(frame.len())
+            .ok()
+            .filter(|&len| len as usize <= MAX_FRAME)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "frame too large"))?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(frame)?;
+        self.writer.flush()
+    }
+
+    fn recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut len = [0u8; 4];
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_FRAME {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "frame too large",
+            ));
+        }
+        let mut frame = alloc::vec![0; len];
+        self.reader.read_exact(&mut frame)?;
+        Ok(Some(frame))
+    }
+}
+
+/// request kinds, the first byte of every client frame
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    /// `iid` -> object id holding one new remote reference
+    Root = 0,
+    /// `id, iid, method, args` -> return value and out parameters
+    Call = 1,
+    /// `id, iid` -> status only
+    QueryInterface = 2,
+    /// `id, count` -> status only
+    Release = 3,
+}
+
+impl Op {
+    fn from_u8(op: u8) -> Option<Self> {
+        Some(match op {
+            0 => Op::Root,
+            1 => Op::Call,
+            2 => Op::QueryInterface,
+            3 => Op::Release,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::{
+        ptr,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+    use std::{os::unix::net::UnixStream, thread};
+
+    use self::calc::{ICalc, impls};
+    use crate::{object::*, remote::*, *};
+
+    mod calc {
+        use crate::{
+            ComPtr, ComResult, Guid, HResult, HResultE, IUnknown, Interface,
+            remote::{RemoteInterface, StubCall, proxy_call},
+        };
+
+        #[cocom::interface("d1a7e3c2-6b4f-4e8a-9c05-3f2b7a1e6d90")]
+        pub trait ICalc: IUnknown {
+            fn Add(&self, a: i32, b: i32) -> i32;
+            fn Divide(&self, a: i32, b: i32, out: *mut i32) -> HResult;
+            fn Child(&self, base: i32, out: *mut *mut ICalc) -> HResult;
+            fn Sum(&self, other: ComPtr<ICalc>) -> i32;
+            fn Base(&self) -> i32;
+        }
+
+        test_details! {
+            ICalc: IUnknown {
+                fn Add(&self, a: i32, b: i32) -> i32;
+                fn Divide(&self, a: i32, b: i32, out: *mut i32 => Out) -> HResult;
+                fn Child(&self, base: i32, out: *mut *mut ICalc => OutComPtr) -> HResult;
+                fn Sum(&self, other: ComPtr<ICalc>) -> i32;
+                fn Base(&self) -> i32;
+            }
+            ;
+
+            unsafe extern "C" fn p_Add(this: *const ICalc, a: i32, b: i32) -> i32 {
+                unsafe { proxy_call(this as _, &ICalc::GUID, 0, (a, b)) }
+            }
+
+            unsafe extern "C" fn p_Divide(
+                this: *const ICalc,
+                a: i32,
+                b: i32,
+                out: *mut i32,
+            ) -> HResult {
+                unsafe { proxy_call(this as _, &ICalc::GUID, 1, (a, b, out)) }
+            }
+
+            unsafe extern "C" fn p_Child(
+                this: *const ICalc,
+                base: i32,
+                out: *mut *mut ICalc,
+            ) -> HResult {
+                unsafe { proxy_call(this as _, &ICalc::GUID, 2, (base, out)) }
+            }
+
+            unsafe extern "C" fn p_Sum(this: *const ICalc, other: ComPtr<ICalc>) -> i32 {
+                unsafe { proxy_call(this as _, &ICalc::GUID, 3, (other,)) }
+            }
+
+            unsafe extern "C" fn p_Base(this: *const ICalc) -> i32 {
+                unsafe { proxy_call(this as _, &ICalc::GUID, 4, ()) }
+            }
+
+            unsafe impl RemoteInterface for ICalc {
+                const PROXY_VTBL: VitualTable_ICalc = VitualTable_ICalc {
+                    b: <IUnknown as RemoteInterface>::PROXY_VTBL,
+                    f_Add: p_Add,
+                    f_Divide: p_Divide,
+                    f_Child: p_Child,
+                    f_Sum: p_Sum,
+                    f_Base: p_Base,
+                };
+                const PROXY_VTBL_REF: &'static VitualTable_ICalc = &Self::PROXY_VTBL;
+
+                fn stub(this: &Self, method: u32, call: &mut StubCall) -> ComResult<()> {
+                    match method {
+                        0 => call.invoke(|(a, b)| this.Add(a, b)),
+                        1 => call.invoke(|(a, b, out)| this.Divide(a, b, out)),
+                        2 => call.invoke(|(base, out)| this.Child(base, out)),
+                        3 => call.invoke(|(other,)| this.Sum(other)),
+                        4 => call.invoke(|()| this.Base()),
+                        _ => Err(HResultE::NotImpl.into()),
+                    }
+                }
+            }
+        }
+
+        pub mod impls {
+            pub use crate::impls::*;
+            use crate::{ComPtr, HResult, Out, OutComPtr};
+
+            pub trait ICalc: IUnknown {
+                fn Add(&self, a: i32, b: i32) -> i32;
+                fn Divide(&self, a: i32, b: i32, out: Out<i32>) -> HResult;
+                fn Child(&self, base: i32, out: OutComPtr<super::ICalc>) -> HResult;
+                fn Sum(&self, other: ComPtr<super::ICalc>) -> i32;
+                fn Base(&self) -> i32;
+            }
+        }
+    }
+
+    #[object(ICalc)]
+    pub struct Calc {
+        base: i32,
+        /// objects alive in the test that created this one
+        live: &'static AtomicUsize,
+    }
+
+    impl Calc {
+        fn make(base: i32, live: &'static AtomicUsize) -> ComPtr<ICalc> {
+            live.fetch_add(1, Ordering::SeqCst);
+            Calc { base, live }.make_com()
+        }
+    }
+
+    impl Drop for Calc {
+        fn drop(&mut self) {
+            self.live.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    impl impls::ICalc for Calc {
+        fn Add(&self, a: i32, b: i32) -> i32 {
+            self.base + a + b
+        }
+
+        fn Divide(&self, a: i32, b: i32, out: Out<i32>) -> HResult {
+            if b == 0 {
+                return HResultE::InvalidArg.into();
+            }
+            out.write(a / b);
+            HResultE::Ok.into()
+        }
+
+        fn Child(&self, base: i32, out: OutComPtr<ICalc>) -> HResult {
+            out.write(Calc::make(base, self.live));
+            HResultE::Ok.into()
+        }
+
+        fn Sum(&self, other: ComPtr<ICalc>) -> i32 {
+            self.base + other.Base()
+        }
+
+        fn Base(&self) -> i32 {
+            self.base
+        }
+    }
+
+    fn refs(p: &ComPtr<ICalc>) -> u32 {
+        p.AddRef();
+        p.Release() - 1
+    }
+
+    fn connect(root: &ComPtr<ICalc>) -> (Client, UnixStream, thread::JoinHandle<()>) {
+        register::<ICalc>();
+        let (a, b) = UnixStream::pair().unwrap();
+        let server = Server::new(root).unwrap();
+        let thread = thread::spawn(move || {
+            let mut transport = StreamTransport::unix(b).unwrap();
+            server.serve(&mut transport).unwrap();
+        });
+        let stream = a.try_clone().unwrap();
+        (
+            Client::new(StreamTransport::unix(a).unwrap()),
+            stream,
+            thread,
+        )
+    }
+
+    #[test]
+    fn calls_and_references() {
+        static LIVE: AtomicUsize = AtomicUsize::new(0);
+        let root = Calc::make(10, &LIVE);
+        let (client, _, server) = connect(&root);
+
+        let calc = client.root::<ICalc>().unwrap();
+        assert_eq!(calc.Add(1, 2), 13);
+        let mut q = 0;
+        assert_eq!(calc.Divide(7, 2, &mut q), HResult::ok());
+        assert_eq!(q, 3);
+        assert_eq!(
+            calc.Divide(7, 0, &mut q),
+            HResult::from(HResultE::InvalidArg)
+        );
+
+        let mut child = ptr::null_mut();
+        assert_eq!(calc.Child(5, &mut child), HResult::ok());
+        let child = unsafe { ComPtr::create(child) }.unwrap();
+        assert_eq!(child.Base(), 5);
+        assert_eq!(calc.Sum(child.clone()), 15);
+        assert_eq!(LIVE.load(Ordering::SeqCst), 2);
+        assert_eq!(client.proxies(), 2);
+
+        // the same object comes back as the same proxy
+        let again = client.root::<ICalc>().unwrap();
+        assert_eq!(again.const_ptr(), calc.const_ptr());
+        let unk = calc.query_interface::<IUnknown>().unwrap();
+        assert_eq!(unk.query_interface::<ICalc>().unwrap().Base(), 10);
+        assert_eq!(
+            calc.query_interface::<IWeak>().unwrap_err(),
+            HResult::from(HResultE::NoInterface)
+        );
+
+        // dropping the last proxy returns every remote reference at once
+        drop(child);
+        assert_eq!(LIVE.load(Ordering::SeqCst), 1);
+        assert_eq!(client.proxies(), 1);
+        drop((calc, again, unk));
+        assert_eq!(client.proxies(), 0);
+
+        drop(client);
+        server.join().unwrap();
+        assert_eq!(refs(&root), 1);
+        drop(root);
+        assert_eq!(LIVE.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn disconnect() {
+        static LIVE: AtomicUsize = AtomicUsize::new(0);
+        let root = Calc::make(1, &LIVE);
+        let (client, stream, server) = connect(&root);
+        let calc = client.root::<ICalc>().unwrap();
+        let mut q = 0;
+        assert_eq!(calc.Divide(4, 2, &mut q), HResult::ok());
+
+        stream.shutdown(std::net::Shutdown::Both).unwrap();
+        server.join().unwrap();
+        assert_eq!(refs(&root), 1);
+        assert_eq!(
+            calc.Divide(4, 2, &mut q),
+            HResult::from(HResultE::Disconnected)
+        );
+        assert_eq!(
+            client.root::<ICalc>().unwrap_err().code(),
+            HResult::from(HResultE::Disconnected)
+        );
+    }
+
+    #[test]
+    fn disconnect_aborts_pod_return() {
+        const CHILD: &str = "COCOM_REMOTE_ABORT_CHILD";
+        if std::env::var_os(CHILD).is_some() {
+            static LIVE: AtomicUsize = AtomicUsize::new(0);
+            let root = Calc::make(1, &LIVE);
+            let (client, stream, server) = connect(&root);
+            let calc = client.root::<ICalc>().unwrap();
+            stream.shutdown(std::net::Shutdown::Both).unwrap();
+            server.join().unwrap();
+            calc.Add(1, 2);
+            unreachable!();
+        }
+
+        // the abort takes the whole process down, so the call runs in a copy of this test
+        let out = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "remote::test::disconnect_aborts_pod_return",
+                "--nocapture",
+            ])
+            .env(CHILD, "1")
+            .output()
+            .unwrap();
+        assert!(!out.status.success());
+        let stderr = std::string::String::from_utf8_lossy(&out.stderr);
+        assert!(
+            stderr.contains("remote call returning i32 failed with"),
+            "{stderr}"
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec::Vec};
+use core::{
+    ffi::c_void,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicU32, Ordering},
+};
+use std::sync::Mutex;
+
+use super::{
+    Args, Op, Reader, RemoteInterface, Ret, Side, StubCall, Transport, Writer, registered,
+};
+use crate::{
+    ComPtr, ComResult, Guid, HResult, HResultE, IUnknown, Interface, details::VitualTable_IUnknown,
+};
+
+/// answered by proxies only, hands out the proxy manager instead of an interface
+const IID_PROXY_MANAGER: Guid = Guid::from_str("6f2c9a41-5e0b-4d7e-b3a8-1c94e07d5a62").unwrap();
+
+/// Client end of a connection
+pub(super) struct Connection {
+    transport: Mutex<Box<dyn Transport>>,
+    /// live proxy managers by remote object id
+    proxies: Mutex<BTreeMap<u64, usize>>,
+}
+
+impl Connection {
+    /// one request and its reply, a failed status becomes the error
+    fn request(&self, frame: &[u8]) -> ComResult<Vec<u8>> {
+        let mut transport = self.transport.lock().unwrap();
+        let disconnected = || HResult::from(HResultE::Disconnected);
+        transport.send(frame).map_err(|_| disconnected())?;
+        let reply = transport
+            .recv()
+            .map_err(|_| disconnected())?
+            .ok_or_else(disconnected)?;
+        if reply.len() < 4 {
+            return Err(HResultE::InvalidData.into());
+        }
+        let status = HResult::new(i32::from_ne_bytes(reply[..4].try_into().unwrap()));
+        if status.is_failure() {
+            return Err(status.into());
+        }
+        Ok(reply)
+    }
+
+    /// Takes over one remote reference to `id` as a new local reference to its `I` proxy
+    pub(super) fn adopt<I: RemoteInterface>(this: &Arc<Self>, id: u64) -> ComResult<ComPtr<I>> {
+        let manager = {
+            let mut proxies = this.proxies.lock().unwrap();
+            let existing = proxies.get(&id).map(|&m| m as *const ProxyManager);
+            // a manager whose count already reached zero is on its way out, replace it
+            match existing.filter(|&m| unsafe { (*m).try_add_ref() }) {
+                Some(m) => {
+                    unsafe { (*m).remote_refs.fetch_add(1, Ordering::Relaxed) };
+                    m
+                }
+                None => {
+                    let m = Box::into_raw(Box::new(ProxyManager {
+                        id,
+                        conn: this.clone(),
+                        strong: AtomicU32::new(1),
+                        remote_refs: AtomicU32::new(1),
+                        interfaces: Mutex::new(Vec::new()),
+                    }));
+                    proxies.insert(id, m as usize);
+                    m
+                }
+            }
+        };
+        unsafe {
+            match (*manager).interface(&I::GUID, I::PROXY_VTBL_REF as *const _ as _, false) {
+                Ok(p) => Ok(ComPtr::new(p.cast())),
+                Err(e) => {
+                    ProxyManager::release(manager);
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    /// the remote id of a proxy from this connection
+    pub(super) fn proxy_id(self: &Arc<Self>, obj: &IUnknown) -> ComResult<u64> {
+        let mut out = ptr::null_mut();
+        if obj
+            .QueryInterface(&IID_PROXY_MANAGER, &mut out)
+            .is_failure()
+        {
+            return Err(HResultE::NotImpl.into());
+        }
+        let manager = out as *const ProxyManager;
+        let id = unsafe {
+            let same = Arc::ptr_eq(&(*manager).conn, self);
+            let id = (*manager).id;
+            ProxyManager::release(manager);
+            if !same {
+                return Err(HResultE::NotImpl.into());
+            }
+            id
+        };
+        Ok(id)
+    }
+}
+
+/// The client side of one remote object, shared by its interface proxies
+struct ProxyManager {
+    id: u64,
+    conn: Arc<Connection>,
+    /// local references over all interface proxies
+    strong: AtomicU32,
+    /// remote references received for this object, returned together
+    remote_refs: AtomicU32,
+    /// boxed, handed out pointers must stay put while the list grows
+    #[allow(clippy::vec_box)]
+    interfaces: Mutex<Vec<Box<InterfaceProxy>>>,
+}
+
+/// What a proxied interface pointer points to
+#[repr(C)]
+struct InterfaceProxy {
+    vtbl: *const c_void,
+    iid: Guid,
+    manager: *const ProxyManager,
+}
+
+impl ProxyManager {
+    fn try_add_ref(&self) -> bool {
+        self.strong
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |n| {
+                (n != 0).then(|| n + 1)
+            })
+            .is_ok()
+    }
+
+    unsafe fn release(this: *const Self) -> u32 {
+        unsafe {
+            let r = (*this).strong.fetch_sub(1, Ordering::Release);
+            if r != 1 {
+                return r;
+            }
+            core::sync::atomic::fence(Ordering::Acquire);
+            let this = Box::from_raw(this as *mut Self);
+            {
+                let mut proxies = this.conn.proxies.lock().unwrap();
+                if proxies.get(&this.id) == Some(&(&*this as *const Self as usize)) {
+                    proxies.remove(&this.id);
+                }
+            }
+            let mut w = Writer::new(Side::Client(&this.conn));
+            w.pod(&(Op::Release as u8));
+            w.pod(&this.id);
+            w.pod(&this.remote_refs.load(Ordering::Relaxed));
+            // nothing to do about a lost connection, the server drops the references then
+            _ = this.conn.request(&w.finish());
+            r
+        }
+    }
+
+    /// The proxy for `iid`, created with `vtbl` if missing. Takes no reference, `add_ref`
+    /// adds one for an existing proxy.
+    unsafe fn interface(
+        &self,
+        iid: &Guid,
+        vtbl: *const c_void,
+        add_ref: bool,
+    ) -> ComResult<NonNull<InterfaceProxy>> {
+        let mut interfaces = self.interfaces.lock().unwrap();
+        if let Some(p) = interfaces.iter().find(|p| p.iid == *iid) {
+            if add_ref {
+                self.strong.fetch_add(1, Ordering::Relaxed);
+            }
+            return Ok(NonNull::from(&**p));
+        }
+        let p = Box::new(InterfaceProxy {
+            vtbl,
+            iid: *iid,
+            manager: self,
+        });
+        let ptr = NonNull::from(&*p);
+        interfaces.push(p);
+        if add_ref {
+            self.strong.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(ptr)
+    }
+
+    fn query_interface(&self, iid: &Guid) -> ComResult<NonNull<InterfaceProxy>> {
+        let known = self
+            .interfaces
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|p| p.iid == *iid);
+        if !known {
+            if registered(iid).is_none() {
+                return Err(HResultE::NoInterface.into());
+            }
+            let mut w = Writer::new(Side::Client(&self.conn));
+            w.pod(&(Op::QueryInterface as u8));
+            w.pod(&self.id);
+            w.pod(iid);
+            self.conn.request(&w.finish())?;
+        }
+        let vtbl = registered(iid).ok_or(HResult::from(HResultE::NoInterface))?;
+        unsafe { self.interface(iid, vtbl.proxy_vtbl, true) }
+    }
+}
+
+unsafe fn manager<'a>(this: *const c_void) -> &'a ProxyManager {
+    unsafe { &*(*(this as *const InterfaceProxy)).manager }
+}
+
+unsafe extern "C" fn proxy_query_interface(
+    this: *const IUnknown,
+    iid: *const Guid,
+    out: *mut *mut c_void,
+) -> HResult {
+    unsafe {
+        if iid.is_null() || out.is_null() {
+            return HResultE::Pointer.into();
+        }
+        *out = ptr::null_mut();
+        let manager = manager(this as _);
+        if *iid == IID_PROXY_MANAGER {
+            manager.strong.fetch_add(1, Ordering::Relaxed);
+            *out = manager as *const ProxyManager as _;
+            return HResultE::Ok.into();
+        }
+        match manager.query_interface(&*iid) {
+            Ok(p) => {
+                *out = p.as_ptr() as _;
+                HResultE::Ok.into()
+            }
+            Err(e) => e.code(),
+        }
+    }
+}
+
+unsafe extern "C" fn proxy_add_ref(this: *const IUnknown) -> u32 {
+    unsafe { manager(this as _).strong.fetch_add(1, Ordering::Relaxed) }
+}
+
+unsafe extern "C" fn proxy_release(this: *const IUnknown) -> u32 {
+    unsafe { ProxyManager::release(manager(this as _)) }
+}
+
+unsafe impl RemoteInterface for IUnknown {
+    const PROXY_VTBL: VitualTable_IUnknown = VitualTable_IUnknown {
+        f_QueryInterface: proxy_query_interface,
+        f_AddRef: proxy_add_ref,
+        f_Release: proxy_release,
+    };
+    const PROXY_VTBL_REF: &'static VitualTable_IUnknown = &Self::PROXY_VTBL;
+
+    fn stub(this: &Self, method: u32, call: &mut StubCall) -> ComResult<()> {
+        Err(HResultE::NotImpl.into())
+    }
+}
+
+/// Body of every proxy vtable slot: sends call `method` of interface `iid` and decodes the
+/// reply, a call that cannot complete returns [`Ret::failed`]
+///
+/// # Safety
+/// `this` must be an interface proxy handed out by a [`Client`]
+pub unsafe fn proxy_call<A: Args, R: Ret>(
+    this: *const c_void,
+    iid: &Guid,
+    method: u32,
+    args: A,
+) -> R {
+    let manager = unsafe { manager(this) };
+    let call = || -> ComResult<R> {
+        let mut w = Writer::new(Side::Client(&manager.conn));
+        w.pod(&(Op::Call as u8));
+        w.pod(&manager.id);
+        w.pod(iid);
+        w.pod(&method);
+        args.write_in(&mut w)?;
+        let reply = manager.conn.request(&w.finish())?;
+        let mut r = Reader::new(&reply[4..], Side::Client(&manager.conn));
+        let ret = R::read_ret(&mut r)?;
+        args.read_out(&mut r)?;
+        Ok(ret)
+    };
+    call().unwrap_or_else(|e| R::failed(e.code()))
+}
+
+/// Client end of a connection to a [`Server`](super::Server), cheap to clone
+#[derive(Clone)]
+pub struct Client {
+    conn: Arc<Connection>,
+}
+
+impl Client {
+    pub fn new(transport: impl Transport + 'static) -> Self {
+        Self {
+            conn: Arc::new(Connection {
+                transport: Mutex::new(Box::new(transport)),
+                proxies: Mutex::new(BTreeMap::new()),
+            }),
+        }
+    }
+
+    /// a proxy for the server's root object as an `I`
+    pub fn root<I: RemoteInterface>(&self) -> ComResult<ComPtr<I>> {
+        let mut w = Writer::new(Side::Client(&self.conn));
+        w.pod(&(Op::Root as u8));
+        w.pod(&I::GUID);
+        let reply = self.conn.request(&w.finish())?;
+        let id = Reader::new(&reply[4..], Side::Client(&self.conn)).pod()?;
+        Connection::adopt(&self.conn, id)
+    }
+
+    /// number of remote objects this client currently holds proxies for
+    pub fn proxies(&self) -> usize {
+        self.conn.proxies.lock().unwrap().len()
+    }
+}
+
+impl core::fmt::Debug for Client {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Client")
+            .field("proxies", &self.proxies())
+            .finish()
+    }
+}
@@ -0,0 +1,173 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::cell::RefCell;
+use std::io;
+
+use super::{Args, Op, Reader, Ret, Side, Transport, Writer, registered};
+use crate::{ComPtr, ComResult, Guid, HResult, HResultE, IUnknown, Interface, impls};
+
+struct Export {
+    obj: ComPtr<IUnknown>,
+    /// remote references the client holds
+    refs: u32,
+}
+
+/// Objects one client holds remote references to, keyed by id and by identity
+#[derive(Default)]
+pub(super) struct Exports {
+    next: u64,
+    objects: BTreeMap<u64, Export>,
+    ids: BTreeMap<usize, u64>,
+}
+
+impl Exports {
+    /// hands out one more remote reference to `obj`, which must be its `IUnknown` identity
+    pub(super) fn export(&mut self, obj: &ComPtr<IUnknown>) -> u64 {
+        let identity = obj.const_ptr() as usize;
+        if let Some(&id) = self.ids.get(&identity) {
+            self.objects.get_mut(&id).unwrap().refs += 1;
+            return id;
+        }
+        self.next += 1;
+        let id = self.next;
+        self.ids.insert(identity, id);
+        self.objects.insert(
+            id,
+            Export {
+                obj: obj.clone(),
+                refs: 1,
+            },
+        );
+        id
+    }
+
+    pub(super) fn get(&self, id: u64) -> ComResult<&ComPtr<IUnknown>> {
+        match self.objects.get(&id) {
+            Some(export) => Ok(&export.obj),
+            None => Err(HResultE::InvalidData.into()),
+        }
+    }
+
+    /// drops `count` remote references, returns the object once the last one is gone
+    fn release(&mut self, id: u64, count: u32) -> ComResult<Option<ComPtr<IUnknown>>> {
+        let export = self
+            .objects
+            .get_mut(&id)
+            .ok_or(HResult::from(HResultE::InvalidData))?;
+        if count == 0 || count > export.refs {
+            return Err(HResultE::InvalidData.into());
+        }
+        export.refs -= count;
+        if export.refs > 0 {
+            return Ok(None);
+        }
+        let export = self.objects.remove(&id).unwrap();
+        self.ids.remove(&(export.obj.const_ptr() as usize));
+        Ok(Some(export.obj))
+    }
+}
+
+/// Decoding and encoding side of one call, handed to [`RemoteInterface::stub`](super::RemoteInterface::stub)
+pub struct StubCall<'a> {
+    args: Reader<'a>,
+    out: Writer<'a>,
+}
+
+impl StubCall<'_> {
+    /// decodes the arguments, calls `f` and encodes its return value and out parameters
+    pub fn invoke<A: Args, R: Ret>(&mut self, f: impl FnOnce(A) -> R) -> ComResult<()> {
+        let mut locals = A::read_in(&mut self.args)?;
+        let r = f(A::args(&mut locals));
+        r.write_ret(&mut self.out)?;
+        A::write_out(locals, &mut self.out)
+    }
+}
+
+/// Serves calls on the objects reachable from a root object
+#[derive(Debug, Clone)]
+pub struct Server {
+    root: ComPtr<IUnknown>,
+}
+
+impl Server {
+    pub fn new<I: impls::RefCount>(root: &ComPtr<I>) -> ComResult<Self> {
+        Ok(Self {
+            root: root.query_interface()?,
+        })
+    }
+
+    /// Answers requests until the client disconnects, then releases every reference it held
+    pub fn serve(&self, transport: &mut dyn Transport) -> io::Result<()> {
+        let exports = RefCell::new(Exports::default());
+        while let Some(frame) = transport.recv()? {
+            let mut out = Writer::new(Side::Server(&exports));
+            out.pod(&0i32);
+            let status = match self.dispatch(&frame, &exports, &mut out) {
+                Ok(()) => HResult::ok(),
+                Err(e) => e.code(),
+            };
+            let mut reply = out.finish();
+            if status.is_failure() {
+                reply.truncate(4);
+            }
+            reply[..4].copy_from_slice(&status.value.to_ne_bytes());
+            transport.send(&reply)?;
+        }
+        Ok(())
+    }
+
+    fn dispatch(
+        &self,
+        frame: &[u8],
+        exports: &RefCell<Exports>,
+        out: &mut Writer,
+    ) -> ComResult<()> {
+        let mut r = Reader::new(frame, Side::Server(exports));
+        let op = Op::from_u8(r.pod()?).ok_or(HResult::from(HResultE::InvalidData))?;
+        match op {
+            Op::Root => {
+                let iid: Guid = r.pod()?;
+                self.query(&self.root, &iid)?;
+                let id = exports.borrow_mut().export(&self.root);
+                out.pod(&id);
+            }
+            Op::Call => {
+                let id: u64 = r.pod()?;
+                let iid: Guid = r.pod()?;
+                let method: u32 = r.pod()?;
+                let obj = exports.borrow().get(id)?.clone();
+                let stub = registered(&iid).ok_or(HResult::from(HResultE::NoInterface))?;
+                let mut call = StubCall {
+                    args: r,
+                    out: Writer::new(Side::Server(exports)),
+                };
+                (stub.stub)(&obj, method, &mut call)?;
+                out.bytes(&call.out.finish());
+            }
+            Op::QueryInterface => {
+                let id: u64 = r.pod()?;
+                let iid: Guid = r.pod()?;
+                let obj = exports.borrow().get(id)?.clone();
+                self.query(&obj, &iid)?;
+            }
+            Op::Release => {
+                let id: u64 = r.pod()?;
+                let count: u32 = r.pod()?;
+                // the object may run its destructor, not while the table is borrowed
+                let released = exports.borrow_mut().release(id, count)?;
+                drop(released);
+            }
+        }
+        Ok(())
+    }
+
+    /// whether `obj` implements `iid` and this process can stub it
+    fn query(&self, obj: &ComPtr<IUnknown>, iid: &Guid) -> ComResult<()> {
+        if registered(iid).is_none() {
+            return Err(HResultE::NoInterface.into());
+        }
+        let mut out = core::ptr::null_mut();
+        obj.QueryInterface(iid, &mut out).to_result()?;
+        drop(unsafe { ComPtr::create(out as *mut IUnknown) });
+        Ok(())
+    }
+}
@@ -0,0 +1,461 @@
+use alloc::{sync::Arc, vec::Vec};
+use core::{cell::RefCell, mem::size_of, ptr};
+
+use super::{Connection, Exports, RemoteInterface};
+use crate::{
+    ComPtr, ComResult, Guid, HResult, HResultE, IUnknown,
+    types::{B8, B32, Char8, Char16, Char32},
+};
+
+/// which end of the connection a value is encoded or decoded on
+#[derive(Clone, Copy)]
+pub(super) enum Side<'a> {
+    Client(&'a Arc<Connection>),
+    Server(&'a RefCell<Exports>),
+}
+
+/// Encodes call data in native layout, both processes run on the same machine
+pub struct Writer<'a> {
+    buf: Vec<u8>,
+    side: Side<'a>,
+}
+
+impl<'a> Writer<'a> {
+    pub(super) fn new(side: Side<'a>) -> Self {
+        Self {
+            buf: Vec::new(),
+            side,
+        }
+    }
+
+    pub(super) fn side(&self) -> Side<'a> {
+        self.side
+    }
+
+    pub(super) fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn pod<T: Pod>(&mut self, value: &T) {
+        let bytes =
+            unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+        self.bytes(bytes);
+    }
+}
+
+/// Decodes what a [`Writer`] produced, running short fails with `InvalidData`
+pub struct Reader<'a> {
+    data: &'a [u8],
+    side: Side<'a>,
+}
+
+impl<'a> Reader<'a> {
+    pub(super) fn new(data: &'a [u8], side: Side<'a>) -> Self {
+        Self { data, side }
+    }
+
+    pub(super) fn side(&self) -> Side<'a> {
+        self.side
+    }
+
+    pub fn bytes(&mut self, len: usize) -> ComResult<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(HResultE::InvalidData.into());
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    pub fn pod<T: Pod>(&mut self) -> ComResult<T> {
+        let bytes = self.bytes(size_of::<T>())?;
+        Ok(unsafe { ptr::read_unaligned(bytes.as_ptr() as *const T) })
+    }
+
+    fn flag(&mut self) -> ComResult<bool> {
+        match self.pod::<u8>()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(HResultE::InvalidData.into()),
+        }
+    }
+}
+
+/// Plain data copied byte for byte
+///
+/// # Safety
+/// Every bit pattern must be a valid value, the type must not have padding bytes and must not
+/// own or point to anything.
+pub unsafe trait Pod: Copy + Send + 'static {}
+
+macro_rules! pod {
+    ($($t:ty),*) => {
+        $(unsafe impl Pod for $t {})*
+    };
+}
+
+pod!(
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, Guid, B8, B32, Char8,
+    Char16, Char32
+);
+
+/// A parameter type of a remote call.
+///
+/// The caller encodes it with `write_in`, the callee rebuilds a `Local` from that and passes
+/// `arg` of it to the real method, then encodes what the caller needs back with `write_out`,
+/// which the caller applies with `read_out`.
+///
+/// # Safety
+/// `arg` may point into `Local`, which stays in place until `write_out`.
+pub unsafe trait Param: Sized {
+    type Local;
+
+    fn write_in(&self, w: &mut Writer) -> ComResult<()>;
+    fn read_in(r: &mut Reader) -> ComResult<Self::Local>;
+    fn arg(local: &mut Self::Local) -> Self;
+
+    fn write_out(local: Self::Local, w: &mut Writer) -> ComResult<()> {
+        Ok(())
+    }
+
+    fn read_out(&self, r: &mut Reader) -> ComResult<()> {
+        Ok(())
+    }
+}
+
+/// A return type of a remote call
+///
+/// Only `HResult` and `()` can report a call that did not complete, e.g. after a disconnect.
+/// For any other type the proxy aborts the process, as it runs in an `extern "C"` frame.
+pub trait Ret: Sized {
+    fn write_ret(self, w: &mut Writer) -> ComResult<()>;
+    fn read_ret(r: &mut Reader) -> ComResult<Self>;
+
+    /// what a proxy returns when the call could not be completed
+    fn failed(hr: HResult) -> Self {
+        crate::hardened::fatal(format_args!(
+            "remote call returning {} failed with {hr:?}",
+            core::any::type_name::<Self>()
+        ))
+    }
+}
+
+unsafe impl<T: Pod> Param for T {
+    type Local = T;
+
+    fn write_in(&self, w: &mut Writer) -> ComResult<()> {
+        w.pod(self);
+        Ok(())
+    }
+
+    fn read_in(r: &mut Reader) -> ComResult<T> {
+        r.pod()
+    }
+
+    fn arg(local: &mut T) -> Self {
+        *local
+    }
+}
+
+impl<T: Pod> Ret for T {
+    fn write_ret(self, w: &mut Writer) -> ComResult<()> {
+        w.pod(&self);
+        Ok(())
+    }
+
+    fn read_ret(r: &mut Reader) -> ComResult<Self> {
+        r.pod()
+    }
+}
+
+unsafe impl Param for bool {
+    type Local = bool;
+
+    fn write_in(&self, w: &mut Writer) -> ComResult<()> {
+        w.pod(&(*self as u8));
+        Ok(())
+    }
+
+    fn read_in(r: &mut Reader) -> ComResult<bool> {
+        r.flag()
+    }
+
+    fn arg(local: &mut bool) -> Self {
+        *local
+    }
+}
+
+impl Ret for bool {
+    fn write_ret(self, w: &mut Writer) -> ComResult<()> {
+        self.write_in(w)
+    }
+
+    fn read_ret(r: &mut Reader) -> ComResult<Self> {
+        r.flag()
+    }
+}
+
+unsafe impl Param for HResult {
+    type Local = HResult;
+
+    fn write_in(&self, w: &mut Writer) -> ComResult<()> {
+        w.pod(&self.value);
+        Ok(())
+    }
+
+    fn read_in(r: &mut Reader) -> ComResult<HResult> {
+        Ok(HResult::new(r.pod()?))
+    }
+
+    fn arg(local: &mut HResult) -> Self {
+        *local
+    }
+}
+
+impl Ret for HResult {
+    fn write_ret(self, w: &mut Writer) -> ComResult<()> {
+        self.write_in(w)
+    }
+
+    fn read_ret(r: &mut Reader) -> ComResult<Self> {
+        HResult::read_in(r)
+    }
+
+    fn failed(hr: HResult) -> Self {
+        hr
+    }
+}
+
+impl Ret for () {
+    fn write_ret(self, w: &mut Writer) -> ComResult<()> {
+        Ok(())
+    }
+
+    fn read_ret(r: &mut Reader) -> ComResult<Self> {
+        Ok(())
+    }
+
+    fn failed(hr: HResult) -> Self {}
+}
+
+/// in parameter, null is passed on as null
+unsafe impl<T: Pod> Param for *const T {
+    type Local = Option<T>;
+
+    fn write_in(&self, w: &mut Writer) -> ComResult<()> {
+        match unsafe { self.as_ref() } {
+            Some(v) => {
+                w.pod(&1u8);
+                w.pod(v);
+            }
+            None => w.pod(&0u8),
+        }
+        Ok(())
+    }
+
+    fn read_in(r: &mut Reader) -> ComResult<Option<T>> {
+        Ok(if r.flag()? { Some(r.pod()?) } else { None })
+    }
+
+    fn arg(local: &mut Option<T>) -> Self {
+        match local {
+            Some(v) => v,
+            None => ptr::null(),
+        }
+    }
+}
+
+/// in-out parameter, the callee's final value is copied back
+unsafe impl<T: Pod> Param for *mut T {
+    type Local = Option<T>;
+
+    fn write_in(&self, w: &mut Writer) -> ComResult<()> {
+        (*self as *const T).write_in(w)
+    }
+
+    fn read_in(r: &mut Reader) -> ComResult<Option<T>> {
+        <*const T>::read_in(r)
+    }
+
+    fn arg(local: &mut Option<T>) -> Self {
+        match local {
+            Some(v) => v,
+            None => ptr::null_mut(),
+        }
+    }
+
+    fn write_out(local: Option<T>, w: &mut Writer) -> ComResult<()> {
+        if let Some(v) = local {
+            w.pod(&v);
+        }
+        Ok(())
+    }
+
+    fn read_out(&self, r: &mut Reader) -> ComResult<()> {
+        if !self.is_null() {
+            unsafe { self.write(r.pod()?) };
+        }
+        Ok(())
+    }
+}
+
+/// remote reference, the callee receives the reference the caller passed
+unsafe impl<I: RemoteInterface> Param for ComPtr<I> {
+    type Local = Option<ComPtr<I>>;
+
+    fn write_in(&self, w: &mut Writer) -> ComResult<()> {
+        let Side::Client(conn) = w.side() else {
+            return Err(HResultE::Unexpected.into());
+        };
+        let id = conn.proxy_id(self.as_unknown())?;
+        w.pod(&id);
+        Ok(())
+    }
+
+    fn read_in(r: &mut Reader) -> ComResult<Option<ComPtr<I>>> {
+        let Side::Server(exports) = r.side() else {
+            return Err(HResultE::Unexpected.into());
+        };
+        let id: u64 = r.pod()?;
+        Ok(Some(exports.borrow().get(id)?.query_interface::<I>()?))
+    }
+
+    fn arg(local: &mut Option<ComPtr<I>>) -> Self {
+        local.take().unwrap()
+    }
+}
+
+impl<I: RemoteInterface> Ret for ComPtr<I> {
+    fn write_ret(self, w: &mut Writer) -> ComResult<()> {
+        let Side::Server(exports) = w.side() else {
+            return Err(HResultE::Unexpected.into());
+        };
+        let id = exports
+            .borrow_mut()
+            .export(&self.query_interface::<IUnknown>()?);
+        w.pod(&id);
+        Ok(())
+    }
+
+    fn read_ret(r: &mut Reader) -> ComResult<Self> {
+        let Side::Client(conn) = r.side() else {
+            return Err(HResultE::Unexpected.into());
+        };
+        let id: u64 = r.pod()?;
+        if id == 0 {
+            return Err(HResultE::InvalidData.into());
+        }
+        Connection::adopt(conn, id)
+    }
+}
+
+/// out parameter receiving a remote reference, null stays null
+unsafe impl<I: RemoteInterface> Param for *mut *mut I {
+    type Local = Option<*mut I>;
+
+    fn write_in(&self, w: &mut Writer) -> ComResult<()> {
+        w.pod(&(!self.is_null() as u8));
+        Ok(())
+    }
+
+    fn read_in(r: &mut Reader) -> ComResult<Option<*mut I>> {
+        Ok(r.flag()?.then(ptr::null_mut))
+    }
+
+    fn arg(local: &mut Option<*mut I>) -> Self {
+        match local {
+            Some(p) => p,
+            None => ptr::null_mut(),
+        }
+    }
+
+    fn write_out(local: Option<*mut I>, w: &mut Writer) -> ComResult<()> {
+        let Some(p) = local else {
+            return Ok(());
+        };
+        let Side::Server(exports) = w.side() else {
+            return Err(HResultE::Unexpected.into());
+        };
+        let id = match unsafe { ComPtr::create(p) } {
+            Some(p) => exports
+                .borrow_mut()
+                .export(&p.query_interface::<IUnknown>()?),
+            None => 0,
+        };
+        w.pod(&id);
+        Ok(())
+    }
+
+    fn read_out(&self, r: &mut Reader) -> ComResult<()> {
+        if self.is_null() {
+            return Ok(());
+        }
+        let Side::Client(conn) = r.side() else {
+            return Err(HResultE::Unexpected.into());
+        };
+        let id: u64 = r.pod()?;
+        let p = match id {
+            0 => ptr::null_mut(),
+            id => Connection::adopt::<I>(conn, id)?.leak(),
+        };
+        unsafe { self.write(p) };
+        Ok(())
+    }
+}
+
+/// The parameters of one method as a tuple
+pub trait Args: Sized {
+    type Locals;
+
+    fn write_in(&self, w: &mut Writer) -> ComResult<()>;
+    fn read_in(r: &mut Reader) -> ComResult<Self::Locals>;
+    fn args(locals: &mut Self::Locals) -> Self;
+    fn write_out(locals: Self::Locals, w: &mut Writer) -> ComResult<()>;
+    fn read_out(&self, r: &mut Reader) -> ComResult<()>;
+}
+
+macro_rules! args {
+    ($($p:ident $i:tt),*) => {
+        impl<$($p: Param),*> Args for ($($p,)*) {
+            type Locals = ($($p::Local,)*);
+
+            fn write_in(&self, w: &mut Writer) -> ComResult<()> {
+                $(self.$i.write_in(w)?;)*
+                Ok(())
+            }
+
+            fn read_in(r: &mut Reader) -> ComResult<Self::Locals> {
+                Ok(($($p::read_in(r)?,)*))
+            }
+
+            #[allow(clippy::unused_unit)]
+            fn args(locals: &mut Self::Locals) -> Self {
+                ($($p::arg(&mut locals.$i),)*)
+            }
+
+            fn write_out(locals: Self::Locals, w: &mut Writer) -> ComResult<()> {
+                $($p::write_out(locals.$i, w)?;)*
+                Ok(())
+            }
+
+            fn read_out(&self, r: &mut Reader) -> ComResult<()> {
+                $(self.$i.read_out(r)?;)*
+                Ok(())
+            }
+        }
+    };
+}
+
+args!();
+args!(A 0);
+args!(A 0, B 1);
+args!(A 0, B 1, C 2);
+args!(A 0, B 1, C 2, D 3);
+args!(A 0, B 1, C 2, D 3, E 4);
+args!(A 0, B 1, C 2, D 3, E 4, F 5);
+args!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+args!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
@@ -0,0 +1,341 @@
+use core::{
+    cell::UnsafeCell,
+    fmt::{self, Debug},
+    marker::{PhantomData, PhantomPinned},
+    mem::{self, ManuallyDrop},
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{
+    Interface,
+    com_ptr::ComPtr,
+    details::Vtbl,
+    hardened,
+    impls::{self, ObjectBox},
+    object::{BorrowFlag, BorrowGuard},
+};
+
+/// A COM object living in a local variable instead of an allocation.
+///
+/// Meant for callbacks that only live for one call, e.g. a sink passed into a native `Visit`
+/// method. The interface is handed out as `&I` borrowed from the box, so it cannot outlive the
+/// scope; callees may still `AddRef` it, but must `Release` before returning. Dropping the box
+/// while references are outstanding aborts the process, they would point into a dead stack slot.
+/// Borrows are always tracked, whatever [`impls::Object::CHECK_REENTRANCY`] says, so
+/// [`ScopedObject::with`] never overlaps a `&mut self` method.
+#[repr(C)]
+pub struct ScopedObject<'a, T: impls::Object + 'a> {
+    base: T::Interface,
+    strong: AtomicU32,
+    borrow: BorrowFlag,
+    val: UnsafeCell<ManuallyDrop<T>>,
+    _p: PhantomData<&'a ()>,
+    _pin: PhantomPinned,
+}
+
+unsafe impl<'a, T: impls::Object + Send + Sync + 'a> Sync for ScopedObject<'a, T> {}
+
+impl<'a, T: impls::Object + 'a> ScopedObject<'a, T>
+where
+    T::Interface: Vtbl<Self>,
+{
+    pub fn new(val: T) -> Self {
+        Self {
+            base: T::Interface::new(<T::Interface as Vtbl<Self>>::vtbl()),
+            strong: AtomicU32::new(1),
+            borrow: BorrowFlag::new(),
+            val: UnsafeCell::new(ManuallyDrop::new(val)),
+            _p: PhantomData,
+            _pin: PhantomPinned,
+        }
+    }
+}
+
+impl<'a, T: impls::Object + 'a> ScopedObject<'a, T> {
+    #[inline(always)]
+    pub fn as_interface(&self) -> &T::Interface {
+        &self.base
+    }
+
+    #[inline(always)]
+    pub fn as_ptr(&self) -> *mut T::Interface {
+        &self.base as *const _ as *mut _
+    }
+
+    /// references taken by callees on top of the one owned by the box
+    #[inline(always)]
+    pub fn outstanding(&self) -> u32 {
+        self.strong.load(Ordering::Acquire) - 1
+    }
+
+    /// shared access to the value, `None` while a `&mut self` method runs
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        let _guard = self.borrow.try_borrow()?;
+        Some(f(unsafe { &*self.val.get() }))
+    }
+
+    /// Returns the value, aborting if references are outstanding
+    pub fn into_inner(self) -> T {
+        let this = ManuallyDrop::new(self);
+        this.check_released();
+        unsafe { ManuallyDrop::take(&mut *this.val.get()) }
+    }
+
+    #[inline(always)]
+    fn check_released(&self) {
+        if self.outstanding() != 0 {
+            hardened::fatal(format_args!(
+                "ScopedObject<{}> still has {} outstanding references at the end of its scope",
+                core::any::type_name::<T>(),
+                self.outstanding(),
+            ));
+        }
+    }
+}
+
+impl<'a, T: impls::Object + 'a> Drop for ScopedObject<'a, T> {
+    fn drop(&mut self) {
+        self.check_released();
+        let val = self.val.get_mut();
+        val.final_release();
+        unsafe { ManuallyDrop::drop(val) }
+    }
+}
+
+impl<'a, T: impls::Object + Debug + 'a> Debug for ScopedObject<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("ScopedObject");
+        d.field("strong", &self.strong);
+        match self.borrow.try_borrow() {
+            Some(_guard) => d.field("val", unsafe { &**self.val.get() }),
+            None => d.field("val", &format_args!("<borrowed>")),
+        };
+        d.finish()
+    }
+}
+
+impl<'a, T: impls::Object + 'a> ObjectBox for ScopedObject<'a, T> {
+    type Object = T;
+
+    #[inline(always)]
+    unsafe fn GetObject(this: *mut T::Interface) -> *mut T {
+        unsafe { (*(this as *mut Self)).val.get() as *mut T }
+    }
+
+    #[inline(always)]
+    unsafe fn AddRef(this: *mut T::Interface) -> u32 {
+        unsafe {
+            let this = this as *mut Self;
+            (*this).strong.fetch_add(1, Ordering::Relaxed)
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn Release(this: *mut T::Interface) -> u32 {
+        unsafe {
+            let this = this as *mut Self;
+            let r = (*this).strong.fetch_sub(1, Ordering::Release);
+            debug_assert!(r > 1, "ScopedObject released below its baseline");
+            r
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn Borrow<'b>(this: *mut T::Interface) -> Option<BorrowGuard<'b>> {
+        unsafe { (*(this as *mut Self)).borrow.try_borrow() }
+    }
+
+    #[inline(always)]
+    unsafe fn BorrowMut<'b>(this: *mut T::Interface) -> Option<BorrowGuard<'b>> {
+        unsafe { (*(this as *mut Self)).borrow.try_borrow_mut() }
+    }
+}
+
+/// A process-lifetime COM object that can be placed in a `static`.
+///
+/// The vtable is built at compile time and AddRef/Release are no-ops, so handing out the
+/// interface never touches a refcount and the value is never dropped.
+/// Borrows are always tracked, whatever [`impls::Object::CHECK_REENTRANCY`] says, since every
+/// thread shares the one value: a call that would overlap a `&mut self` method is rejected.
+#[repr(C)]
+pub struct StaticObject<T: impls::Object> {
+    base: T::Interface,
+    borrow: BorrowFlag,
+    val: UnsafeCell<T>,
+}
+
+unsafe impl<T: impls::Object + Send + Sync> Sync for StaticObject<T> {}
+unsafe impl<T: impls::Object + Send> Send for StaticObject<T> {}
+
+impl<T: impls::Object> StaticObject<T>
+where
+    T::Interface: Vtbl<Self>,
+{
+    pub const fn new(val: T) -> Self {
+        /// interface structs are a lone vtable pointer, [`Interface::new`](crate::Interface::new)
+        /// just isn't callable in const
+        union Cast<V: 'static, I> {
+            v_ptr: &'static V,
+            base: ManuallyDrop<I>,
+        }
+        const {
+            assert!(mem::size_of::<T::Interface>() == mem::size_of::<*const ()>());
+        }
+        let base = unsafe {
+            Cast::<_, T::Interface> {
+                v_ptr: <T::Interface as Vtbl<Self>>::VTBL_REF,
+            }
+            .base
+        };
+        Self {
+            base: ManuallyDrop::into_inner(base),
+            borrow: BorrowFlag::new(),
+            val: UnsafeCell::new(val),
+        }
+    }
+}
+
+impl<T: impls::Object> StaticObject<T> {
+    #[inline(always)]
+    pub fn as_interface(&'static self) -> &'static T::Interface {
+        &self.base
+    }
+
+    #[inline(always)]
+    pub fn as_ptr(&'static self) -> *mut T::Interface {
+        &self.base as *const _ as *mut _
+    }
+
+    /// A `ComPtr` to the object; the refcount is a no-op so this never frees anything
+    #[inline(always)]
+    pub fn to_com(&'static self) -> ComPtr<T::Interface>
+    where
+        T::Interface: impls::RefCount,
+    {
+        unsafe { ComPtr::new(NonNull::new_unchecked(self.as_ptr())) }
+    }
+
+    /// shared access to the value, `None` while a `&mut self` method runs
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        let _guard = self.borrow.try_borrow()?;
+        Some(f(unsafe { &*self.val.get() }))
+    }
+}
+
+impl<T: impls::Object + Debug> Debug for StaticObject<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("StaticObject");
+        d.field("ptr", &ptr::from_ref(&self.base));
+        match self.borrow.try_borrow() {
+            Some(_guard) => d.field("val", unsafe { &*self.val.get() }),
+            None => d.field("val", &format_args!("<borrowed>")),
+        };
+        d.finish()
+    }
+}
+
+impl<T: impls::Object> ObjectBox for StaticObject<T> {
+    type Object = T;
+
+    #[inline(always)]
+    unsafe fn GetObject(this: *mut T::Interface) -> *mut T {
+        unsafe { (*(this as *mut Self)).val.get() }
+    }
+
+    #[inline(always)]
+    unsafe fn AddRef(_this: *mut T::Interface) -> u32 {
+        1
+    }
+
+    #[inline(always)]
+    unsafe fn Release(_this: *mut T::Interface) -> u32 {
+        1
+    }
+
+    #[inline(always)]
+    unsafe fn Borrow<'a>(this: *mut T::Interface) -> Option<BorrowGuard<'a>> {
+        unsafe { (*(this as *mut Self)).borrow.try_borrow() }
+    }
+
+    #[inline(always)]
+    unsafe fn BorrowMut<'a>(this: *mut T::Interface) -> Option<BorrowGuard<'a>> {
+        unsafe { (*(this as *mut Self)).borrow.try_borrow_mut() }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::cell::Cell;
+
+    use crate::{object::*, scoped::*, *};
+
+    #[object(IUnknown)]
+    #[derive(Debug)]
+    struct Sink<'a> {
+        hits: &'a Cell<u32>,
+    }
+
+    impl Drop for Sink<'_> {
+        fn drop(&mut self) {
+            self.hits.set(self.hits.get() + 100);
+        }
+    }
+
+    fn visit(sink: &IUnknown) {
+        let held = unsafe { ComPtr::new_clone(NonNull::from(sink)) };
+        let unk: ComPtr<IUnknown> = held.query_interface().unwrap();
+        drop((held, unk));
+    }
+
+    #[test]
+    fn scoped() {
+        let hits = Cell::new(0);
+        {
+            let sink = ScopedObject::new(Sink { hits: &hits });
+            visit(sink.as_interface());
+            assert_eq!(sink.outstanding(), 0);
+            sink.with(|s| s.hits.set(1)).unwrap();
+        }
+        assert_eq!(hits.get(), 101);
+    }
+
+    #[test]
+    fn scoped_outstanding() {
+        let hits = Cell::new(0);
+        let sink = ScopedObject::new(Sink { hits: &hits });
+        let held = unsafe { ComPtr::new_clone(NonNull::from(sink.as_interface())) };
+        assert_eq!(sink.outstanding(), 1);
+        let guard = unsafe { ScopedObject::<Sink>::BorrowMut(sink.as_ptr()) }.unwrap();
+        assert_eq!(sink.with(|s| s.hits.get()), None);
+        drop(guard);
+        drop(held);
+        assert_eq!(sink.into_inner().hits.get(), 0);
+    }
+
+    #[object(IUnknown)]
+    #[derive(Debug)]
+    struct Singleton {
+        a: u32,
+    }
+
+    static SINGLETON: StaticObject<Singleton> = StaticObject::new(Singleton { a: 7 });
+
+    #[test]
+    fn static_object() {
+        let a = SINGLETON.to_com();
+        let b: ComPtr<IUnknown> = a.query_interface().unwrap();
+        drop(a);
+        drop(b.clone());
+        drop(b);
+        assert_eq!(SINGLETON.with(|s| s.a), Some(7));
+        let guard = unsafe { StaticObject::<Singleton>::BorrowMut(SINGLETON.as_ptr()) }.unwrap();
+        assert_eq!(SINGLETON.with(|s| s.a), None);
+        assert!(unsafe { StaticObject::<Singleton>::Borrow(SINGLETON.as_ptr()) }.is_none());
+        drop(guard);
+        assert!(ptr::eq(
+            SINGLETON.as_interface(),
+            SINGLETON.to_com().const_ptr()
+        ));
+    }
+}
@@ -0,0 +1,195 @@
+//! `NSpan` and `NRoSpan`, a pointer plus a `usize` length, borrowed for `'a`
+
+use alloc::vec::Vec;
+use core::{fmt::Debug, marker::PhantomData, ptr};
+
+/// A mutable span, `&'a mut [T]` across the ABI. A null pointer reads as empty.
+#[repr(C)]
+pub struct NSpan<'a, T> {
+    data: *mut T,
+    size: usize,
+    _p: PhantomData<&'a mut [T]>,
+}
+
+/// A read only span, `&'a [T]` across the ABI. A null pointer reads as empty.
+#[repr(C)]
+pub struct NRoSpan<'a, T> {
+    data: *const T,
+    size: usize,
+    _p: PhantomData<&'a [T]>,
+}
+
+unsafe impl<T: Send> Send for NSpan<'_, T> {}
+unsafe impl<T: Sync> Sync for NSpan<'_, T> {}
+unsafe impl<T: Sync> Send for NRoSpan<'_, T> {}
+unsafe impl<T: Sync> Sync for NRoSpan<'_, T> {}
+
+impl<'a, T> NSpan<'a, T> {
+    /// # Safety
+    /// `data` must be null or valid for reads and writes of `size` elements for `'a`
+    pub const unsafe fn from_raw_parts(data: *mut T, size: usize) -> Self {
+        Self {
+            data,
+            size,
+            _p: PhantomData,
+        }
+    }
+
+    pub const fn data(&self) -> *mut T {
+        self.data
+    }
+
+    pub const fn len(&self) -> usize {
+        self.size
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub const fn is_null(&self) -> bool {
+        self.data.is_null()
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        if self.data.is_null() {
+            return &[];
+        }
+        unsafe { core::slice::from_raw_parts(self.data, self.size) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        if self.data.is_null() {
+            return &mut [];
+        }
+        unsafe { core::slice::from_raw_parts_mut(self.data, self.size) }
+    }
+
+    pub fn into_slice(self) -> &'a mut [T] {
+        if self.data.is_null() {
+            return &mut [];
+        }
+        unsafe { core::slice::from_raw_parts_mut(self.data, self.size) }
+    }
+}
+
+impl<'a, T> NRoSpan<'a, T> {
+    /// # Safety
+    /// `data` must be null or valid for reads of `size` elements for `'a`
+    pub const unsafe fn from_raw_parts(data: *const T, size: usize) -> Self {
+        Self {
+            data,
+            size,
+            _p: PhantomData,
+        }
+    }
+
+    pub const fn data(&self) -> *const T {
+        self.data
+    }
+
+    pub const fn len(&self) -> usize {
+        self.size
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub const fn is_null(&self) -> bool {
+        self.data.is_null()
+    }
+
+    pub fn as_slice(&self) -> &'a [T] {
+        if self.data.is_null() {
+            return &[];
+        }
+        unsafe { core::slice::from_raw_parts(self.data, self.size) }
+    }
+}
+
+impl<T> Default for NSpan<'_, T> {
+    fn default() -> Self {
+        unsafe { Self::from_raw_parts(ptr::null_mut(), 0) }
+    }
+}
+
+impl<T> Default for NRoSpan<'_, T> {
+    fn default() -> Self {
+        unsafe { Self::from_raw_parts(ptr::null(), 0) }
+    }
+}
+
+impl<T> Clone for NRoSpan<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for NRoSpan<'_, T> {}
+
+impl<T: Debug> Debug for NSpan<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+impl<T: Debug> Debug for NRoSpan<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+impl<'a, T> From<&'a mut [T]> for NSpan<'a, T> {
+    fn from(value: &'a mut [T]) -> Self {
+        unsafe { Self::from_raw_parts(value.as_mut_ptr(), value.len()) }
+    }
+}
+
+impl<'a, T> From<&'a mut Vec<T>> for NSpan<'a, T> {
+    fn from(value: &'a mut Vec<T>) -> Self {
+        value.as_mut_slice().into()
+    }
+}
+
+impl<'a, T, const N: usize> From<&'a mut [T; N]> for NSpan<'a, T> {
+    fn from(value: &'a mut [T; N]) -> Self {
+        value.as_mut_slice().into()
+    }
+}
+
+impl<'a, T> From<NSpan<'a, T>> for &'a mut [T] {
+    fn from(value: NSpan<'a, T>) -> Self {
+        value.into_slice()
+    }
+}
+
+impl<'a, T> From<&'a [T]> for NRoSpan<'a, T> {
+    fn from(value: &'a [T]) -> Self {
+        unsafe { Self::from_raw_parts(value.as_ptr(), value.len()) }
+    }
+}
+
+impl<'a, T> From<&'a Vec<T>> for NRoSpan<'a, T> {
+    fn from(value: &'a Vec<T>) -> Self {
+        value.as_slice().into()
+    }
+}
+
+impl<'a, T, const N: usize> From<&'a [T; N]> for NRoSpan<'a, T> {
+    fn from(value: &'a [T; N]) -> Self {
+        value.as_slice().into()
+    }
+}
+
+impl<'a, T> From<NSpan<'a, T>> for NRoSpan<'a, T> {
+    fn from(value: NSpan<'a, T>) -> Self {
+        unsafe { Self::from_raw_parts(value.data, value.size) }
+    }
+}
+
+impl<'a, T> From<NRoSpan<'a, T>> for &'a [T] {
+    fn from(value: NRoSpan<'a, T>) -> Self {
+        value.as_slice()
+    }
+}
@@ -0,0 +1,485 @@
+//! `Str8`, `Str16` and `StrAny`, borrowed strings with a `u32` length, as the C# side lays
+//! them out. A null pointer reads as empty; nothing is null terminated.
+
+use alloc::{string::String, vec::Vec};
+use core::{ffi::c_void, fmt::Debug, marker::PhantomData, ptr, str::Utf8Error};
+
+use crate::{HResult, HResultE};
+
+fn size(len: usize) -> u32 {
+    u32::try_from(len).expect("string longer than u32::MAX")
+}
+
+/// utf-8 code units, not checked until read as `str`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Str8<'a> {
+    data: *const u8,
+    size: u32,
+    _p: PhantomData<&'a [u8]>,
+}
+
+/// utf-16 code units
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Str16<'a> {
+    data: *const u16,
+    size: u32,
+    _p: PhantomData<&'a [u16]>,
+}
+
+/// a plain `u8` on the ABI, a [`StrAny`] of any other kind is neither string
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StrKind(pub u8);
+
+impl StrKind {
+    pub const STR8: Self = Self(0);
+    pub const STR16: Self = Self(1);
+}
+
+/// Either a [`Str8`] or a [`Str16`], told apart by its [`StrKind`]
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct StrAny<'a> {
+    data: *const c_void,
+    size: u32,
+    kind: StrKind,
+    _p: PhantomData<&'a [u8]>,
+}
+
+unsafe impl Send for Str8<'_> {}
+unsafe impl Sync for Str8<'_> {}
+unsafe impl Send for Str16<'_> {}
+unsafe impl Sync for Str16<'_> {}
+unsafe impl Send for StrAny<'_> {}
+unsafe impl Sync for StrAny<'_> {}
+
+impl<'a> Str8<'a> {
+    /// # Safety
+    /// `data` must be null or valid for reads of `size` bytes for `'a`
+    pub const unsafe fn from_raw_parts(data: *const u8, size: u32) -> Self {
+        Self {
+            data,
+            size,
+            _p: PhantomData,
+        }
+    }
+
+    pub const fn data(&self) -> *const u8 {
+        self.data
+    }
+
+    pub const fn len(&self) -> usize {
+        self.size as usize
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub const fn is_null(&self) -> bool {
+        self.data.is_null()
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        if self.data.is_null() {
+            return &[];
+        }
+        unsafe { core::slice::from_raw_parts(self.data, self.size as usize) }
+    }
+
+    pub fn to_str(&self) -> Result<&'a str, Utf8Error> {
+        core::str::from_utf8(self.as_bytes())
+    }
+}
+
+impl<'a> Str16<'a> {
+    /// # Safety
+    /// `data` must be null or valid for reads of `size` code units for `'a`
+    pub const unsafe fn from_raw_parts(data: *const u16, size: u32) -> Self {
+        Self {
+            data,
+            size,
+            _p: PhantomData,
+        }
+    }
+
+    pub const fn data(&self) -> *const u16 {
+        self.data
+    }
+
+    pub const fn len(&self) -> usize {
+        self.size as usize
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub const fn is_null(&self) -> bool {
+        self.data.is_null()
+    }
+
+    pub fn as_slice(&self) -> &'a [u16] {
+        if self.data.is_null() {
+            return &[];
+        }
+        unsafe { core::slice::from_raw_parts(self.data, self.size as usize) }
+    }
+}
+
+impl<'a> StrAny<'a> {
+    /// # Safety
+    /// `data` must be null or valid for reads of `size` code units of `kind` for `'a`
+    pub const unsafe fn from_raw_parts(data: *const c_void, size: u32, kind: StrKind) -> Self {
+        Self {
+            data,
+            size,
+            kind,
+            _p: PhantomData,
+        }
+    }
+
+    pub const fn data(&self) -> *const c_void {
+        self.data
+    }
+
+    /// in code units of [`kind`](Self::kind)
+    pub const fn len(&self) -> usize {
+        self.size as usize
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub const fn is_null(&self) -> bool {
+        self.data.is_null()
+    }
+
+    pub const fn kind(&self) -> StrKind {
+        self.kind
+    }
+
+    pub fn as_str8(&self) -> Option<Str8<'a>> {
+        (self.kind == StrKind::STR8)
+            .then(|| unsafe { Str8::from_raw_parts(self.data.cast(), self.size) })
+    }
+
+    pub fn as_str16(&self) -> Option<Str16<'a>> {
+        (self.kind == StrKind::STR16)
+            .then(|| unsafe { Str16::from_raw_parts(self.data.cast(), self.size) })
+    }
+}
+
+impl Default for Str8<'_> {
+    fn default() -> Self {
+        unsafe { Self::from_raw_parts(ptr::null(), 0) }
+    }
+}
+
+impl Default for Str16<'_> {
+    fn default() -> Self {
+        unsafe { Self::from_raw_parts(ptr::null(), 0) }
+    }
+}
+
+impl Default for StrAny<'_> {
+    fn default() -> Self {
+        Str8::default().into()
+    }
+}
+
+impl Debug for Str8<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.to_str() {
+            Ok(s) => s.fmt(f),
+            Err(_) => self.as_bytes().fmt(f),
+        }
+    }
+}
+
+impl Debug for Str16<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        String::from_utf16_lossy(self.as_slice()).fmt(f)
+    }
+}
+
+impl Debug for StrAny<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match (self.as_str8(), self.as_str16()) {
+            (Some(s), _) => s.fmt(f),
+            (_, Some(s)) => s.fmt(f),
+            _ => f
+                .debug_struct("StrAny")
+                .field("kind", &self.kind.0)
+                .field("len", &self.size)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Str8<'a> {
+    fn from(value: &'a str) -> Self {
+        value.as_bytes().into()
+    }
+}
+
+impl<'a> From<&'a String> for Str8<'a> {
+    fn from(value: &'a String) -> Self {
+        value.as_str().into()
+    }
+}
+
+impl<'a> From<&'a [u8]> for Str8<'a> {
+    fn from(value: &'a [u8]) -> Self {
+        unsafe { Self::from_raw_parts(value.as_ptr(), size(value.len())) }
+    }
+}
+
+impl<'a> From<Str8<'a>> for &'a [u8] {
+    fn from(value: Str8<'a>) -> Self {
+        value.as_bytes()
+    }
+}
+
+impl<'a> TryFrom<Str8<'a>> for &'a str {
+    type Error = Utf8Error;
+
+    fn try_from(value: Str8<'a>) -> Result<Self, Self::Error> {
+        value.to_str()
+    }
+}
+
+/// fails with `InvalidData` for invalid utf-8
+impl TryFrom<Str8<'_>> for String {
+    type Error = HResult;
+
+    fn try_from(value: Str8<'_>) -> Result<Self, Self::Error> {
+        match value.to_str() {
+            Ok(s) => Ok(s.into()),
+            Err(_) => Err(HResultE::InvalidData.into()),
+        }
+    }
+}
+
+impl<'a> From<&'a [u16]> for Str16<'a> {
+    fn from(value: &'a [u16]) -> Self {
+        unsafe { Self::from_raw_parts(value.as_ptr(), size(value.len())) }
+    }
+}
+
+impl<'a> From<&'a Vec<u16>> for Str16<'a> {
+    fn from(value: &'a Vec<u16>) -> Self {
+        value.as_slice().into()
+    }
+}
+
+impl<'a, const N: usize> From<&'a [u16; N]> for Str16<'a> {
+    fn from(value: &'a [u16; N]) -> Self {
+        value.as_slice().into()
+    }
+}
+
+impl<'a> From<Str16<'a>> for &'a [u16] {
+    fn from(value: Str16<'a>) -> Self {
+        value.as_slice()
+    }
+}
+
+/// fails with `InvalidData` for unpaired surrogates
+impl TryFrom<Str16<'_>> for String {
+    type Error = HResult;
+
+    fn try_from(value: Str16<'_>) -> Result<Self, Self::Error> {
+        String::from_utf16(value.as_slice()).map_err(|_| HResultE::InvalidData.into())
+    }
+}
+
+impl<'a> From<Str8<'a>> for StrAny<'a> {
+    fn from(value: Str8<'a>) -> Self {
+        unsafe { Self::from_raw_parts(value.data.cast(), value.size, StrKind::STR8) }
+    }
+}
+
+impl<'a> From<Str16<'a>> for StrAny<'a> {
+    fn from(value: Str16<'a>) -> Self {
+        unsafe { Self::from_raw_parts(value.data.cast(), value.size, StrKind::STR16) }
+    }
+}
+
+impl<'a> From<&'a str> for StrAny<'a> {
+    fn from(value: &'a str) -> Self {
+        Str8::from(value).into()
+    }
+}
+
+impl<'a> From<&'a String> for StrAny<'a> {
+    fn from(value: &'a String) -> Self {
+        Str8::from(value).into()
+    }
+}
+
+impl<'a> From<&'a [u16]> for StrAny<'a> {
+    fn from(value: &'a [u16]) -> Self {
+        Str16::from(value).into()
+    }
+}
+
+impl<'a> From<&'a Vec<u16>> for StrAny<'a> {
+    fn from(value: &'a Vec<u16>) -> Self {
+        Str16::from(value).into()
+    }
+}
+
+/// fails with `InvalidData` for text that does not decode or a kind that is neither string
+impl TryFrom<StrAny<'_>> for String {
+    type Error = HResult;
+
+    fn try_from(value: StrAny<'_>) -> Result<Self, Self::Error> {
+        match (value.as_str8(), value.as_str16()) {
+            (Some(s), _) => s.try_into(),
+            (_, Some(s)) => s.try_into(),
+            _ => Err(HResultE::InvalidData.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::{string::String, vec::Vec};
+    use core::mem::{align_of, offset_of, size_of};
+
+    use self::text::{IText, impls};
+    use crate::{object::*, *};
+
+    mod text {
+        use crate::{Guid, HResult, IUnknown, Interface, NRoSpan, NSpan, Str8, Str16, StrAny};
+
+        #[cocom::interface("2b9e4d17-6a3c-4f81-b5d0-8c7e1a2f3d64")]
+        pub trait IText: IUnknown {
+            fn Len8(&self, s: Str8) -> u32;
+            fn Len16(&self, s: Str16) -> u32;
+            fn Kind(&self, s: StrAny) -> u8;
+            fn Sum(&self, v: NRoSpan<u32>) -> u32;
+            fn Fill(&self, v: NSpan<u8>, b: u8);
+        }
+
+        test_details! {
+            IText: IUnknown {
+                fn Len8(&self, s: Str8) -> u32;
+                fn Len16(&self, s: Str16) -> u32;
+                fn Kind(&self, s: StrAny) -> u8;
+                fn Sum(&self, v: NRoSpan<u32>) -> u32;
+                fn Fill(&self, v: NSpan<u8>, b: u8);
+            }
+        }
+
+        pub mod impls {
+            pub use crate::impls::*;
+            use crate::{NRoSpan, NSpan, Str8, Str16, StrAny};
+
+            pub trait IText: IUnknown {
+                fn Len8(&self, s: Str8) -> u32;
+                fn Len16(&self, s: Str16) -> u32;
+                fn Kind(&self, s: StrAny) -> u8;
+                fn Sum(&self, v: NRoSpan<u32>) -> u32;
+                fn Fill(&self, v: NSpan<u8>, b: u8);
+            }
+        }
+    }
+
+    #[object(IText)]
+    struct Text;
+
+    impl impls::IText for Text {
+        fn Len8(&self, s: Str8) -> u32 {
+            s.to_str().map_or(u32::MAX, |s| s.chars().count() as u32)
+        }
+
+        fn Len16(&self, s: Str16) -> u32 {
+            String::try_from(s).map_or(u32::MAX, |s| s.chars().count() as u32)
+        }
+
+        fn Kind(&self, s: StrAny) -> u8 {
+            s.kind().0
+        }
+
+        fn Sum(&self, v: NRoSpan<u32>) -> u32 {
+            v.as_slice().iter().sum()
+        }
+
+        fn Fill(&self, v: NSpan<u8>, b: u8) {
+            v.into_slice().fill(b)
+        }
+    }
+
+    #[test]
+    fn layout() {
+        let ptr = size_of::<usize>();
+        assert_eq!(size_of::<NSpan<u16>>(), 2 * ptr);
+        assert_eq!(size_of::<NRoSpan<u16>>(), 2 * ptr);
+        assert_eq!(size_of::<Str8>(), 2 * ptr);
+        assert_eq!(offset_of!(Str16, size), ptr);
+        assert_eq!(offset_of!(StrAny, kind), ptr + 4);
+        assert_eq!(align_of::<StrAny>(), align_of::<usize>());
+        assert_eq!(size_of::<StrKind>(), 1);
+    }
+
+    #[test]
+    fn conversions() {
+        let s = Str8::from("héllo");
+        assert_eq!(s.len(), 6);
+        assert_eq!(<&str>::try_from(s), Ok("héllo"));
+        assert!(Str8::from(&[0xffu8][..]).to_str().is_err());
+        assert_eq!(
+            String::try_from(Str8::from(&[0xffu8][..])),
+            Err(HResultE::InvalidData.into())
+        );
+        assert_eq!(Str8::default().as_bytes(), b"");
+
+        let wide = "wide".encode_utf16().collect::<Vec<_>>();
+        let s = Str16::from(&wide);
+        assert_eq!(<&[u16]>::from(s), &wide[..]);
+        assert_eq!(String::try_from(s).as_deref(), Ok("wide"));
+        assert!(String::try_from(Str16::from(&[0xd800u16][..])).is_err());
+
+        let any = StrAny::from(&wide);
+        assert_eq!(any.kind(), StrKind::STR16);
+        assert!(any.as_str8().is_none());
+        assert_eq!(
+            String::try_from(StrAny::from("narrow")).as_deref(),
+            Ok("narrow")
+        );
+        let foreign = unsafe { StrAny::from_raw_parts(core::ptr::null(), 3, StrKind(7)) };
+        assert_eq!(
+            alloc::format!("{foreign:?}"),
+            "StrAny { kind: 7, len: 3, .. }"
+        );
+        assert_eq!(String::try_from(foreign), Err(HResultE::InvalidData.into()));
+
+        let mut buf = [1u8, 2, 3];
+        let span = NSpan::from(&mut buf);
+        assert_eq!(NRoSpan::from(span).as_slice(), &[1, 2, 3]);
+        assert!(NRoSpan::<u8>::default().as_slice().is_empty());
+    }
+
+    #[test]
+    fn facades_take_rust_types() {
+        let text = Text.make_com();
+        assert_eq!(text.Len8("héllo"), 5);
+        let owned = String::from("abc");
+        assert_eq!(text.Len8(&owned), 3);
+        let wide = "wide".encode_utf16().collect::<Vec<_>>();
+        assert_eq!(text.Len16(&wide[..]), 4);
+        assert_eq!(text.Kind("x"), StrKind::STR8.0);
+        assert_eq!(text.Kind(&wide), StrKind::STR16.0);
+        assert_eq!(text.Sum(&[1, 2, 3][..]), 6);
+        let values = alloc::vec![4, 5];
+        assert_eq!(text.Sum(&values), 9);
+        let mut buf = [0u8; 4];
+        text.Fill(&mut buf, 7);
+        assert_eq!(buf, [7; 4]);
+    }
+}
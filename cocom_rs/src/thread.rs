@@ -0,0 +1,721 @@
+//! Thread affine objects, enabled by the `std` feature.
+//!
+//! Every thread gets a [`Dispatcher`], a queue of tasks that only that thread runs, when it
+//! calls [`Dispatcher::pump`] or [`Dispatcher::run_until`]. A [`ThreadBound`] can be sent
+//! anywhere but only dereferenced on the thread that created it; other threads
+//! [`run`](ThreadBound::run) closures on it through the owner's dispatcher. For interfaces,
+//! [`ThreadBound::proxy`] hands out a `ComPtr<I>` whose methods post every call to the owner
+//! thread and block until it returns. The proxy vtables are emitted by `#[interface]`.
+//!
+//! A thread waiting for a call keeps running its own queue, so the owner may call back into
+//! objects of the waiting thread. A call to a thread that never pumps blocks forever, and once
+//! a thread exits, calls into it fail with `Disconnected` and values bound to it are leaked.
+//! Proxy methods report that failure only through `HResult` and `()` returns, any other
+//! return type aborts the process, as the proxy runs in an `extern "C"` frame.
+
+use alloc::{boxed::Box, collections::VecDeque, string::String, sync::Arc};
+use core::{
+    ffi::c_void,
+    fmt,
+    mem::ManuallyDrop,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+use std::{
+    panic::AssertUnwindSafe,
+    sync::{Condvar, Mutex},
+    thread::{self, ThreadId},
+};
+
+use crate::{
+    ComError, ComPtr, ComResult, Guid, HResult, HResultE, IUnknown, IWeak, Interface, IntoHResult,
+    details::{VitualTable_IUnknown, VitualTable_IWeak},
+    impls,
+    types::B8,
+};
+
+type Task = Box<dyn FnOnce() + Send>;
+
+struct Queue {
+    thread: ThreadId,
+    state: Mutex<State>,
+    ready: Condvar,
+}
+
+struct State {
+    tasks: VecDeque<Task>,
+    /// the owner thread exited, nothing runs tasks anymore
+    closed: bool,
+}
+
+impl Queue {
+    fn new() -> Self {
+        Self {
+            thread: thread::current().id(),
+            state: Mutex::new(State {
+                tasks: VecDeque::new(),
+                closed: false,
+            }),
+            ready: Condvar::new(),
+        }
+    }
+
+    /// the queue of the current thread, or a private one while its thread locals are gone
+    fn current() -> Arc<Self> {
+        CURRENT
+            .try_with(|c| c.0.queue.clone())
+            .unwrap_or_else(|_| Arc::new(Queue::new()))
+    }
+
+    fn push(&self, task: Task) -> Result<(), Task> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Err(task);
+        }
+        state.tasks.push_back(task);
+        self.ready.notify_all();
+        Ok(())
+    }
+
+    fn wake(&self) {
+        let _state = self.state.lock().unwrap();
+        self.ready.notify_all();
+    }
+
+    fn run_until(&self, mut done: impl FnMut() -> bool) -> usize {
+        let mut n = 0;
+        loop {
+            let task = {
+                let mut state = self.state.lock().unwrap();
+                loop {
+                    if done() {
+                        return n;
+                    }
+                    if let Some(task) = state.tasks.pop_front() {
+                        break task;
+                    }
+                    state = self.ready.wait(state).unwrap();
+                }
+            };
+            task();
+            n += 1;
+        }
+    }
+
+    fn pump(&self) -> usize {
+        let mut n = 0;
+        while let Some(task) = self.state.lock().unwrap().tasks.pop_front() {
+            task();
+            n += 1;
+        }
+        n
+    }
+}
+
+/// runs what is left and closes the queue when its thread exits
+struct Current(Dispatcher);
+
+impl Drop for Current {
+    fn drop(&mut self) {
+        let queue = &self.0.queue;
+        loop {
+            let task = {
+                let mut state = queue.state.lock().unwrap();
+                match state.tasks.pop_front() {
+                    Some(task) => task,
+                    None => {
+                        state.closed = true;
+                        return;
+                    }
+                }
+            };
+            task();
+        }
+    }
+}
+
+std::thread_local! {
+    static CURRENT: Current = Current(Dispatcher {
+        queue: Arc::new(Queue::new()),
+    });
+}
+
+/// The task queue of one thread, cheap to clone
+#[derive(Clone)]
+pub struct Dispatcher {
+    queue: Arc<Queue>,
+}
+
+impl Dispatcher {
+    /// the dispatcher of the calling thread
+    pub fn current() -> Self {
+        CURRENT.with(|c| c.0.clone())
+    }
+
+    pub fn thread(&self) -> ThreadId {
+        self.queue.thread
+    }
+
+    pub fn is_current(&self) -> bool {
+        self.queue.thread == thread::current().id()
+    }
+
+    /// Queues `f` to run on the dispatcher's thread, fails with `Disconnected` once it exited
+    pub fn post(&self, f: impl FnOnce() + Send + 'static) -> ComResult<()> {
+        self.queue
+            .push(Box::new(f))
+            .map_err(|_| HResultE::Disconnected.into())
+    }
+
+    /// Runs `f` on the dispatcher's thread and waits for its result, running the calling
+    /// thread's own tasks meanwhile. Fails with `Disconnected` once the thread exited and with
+    /// `Fail` if `f` panicked.
+    pub fn send<R: Send>(&self, f: impl FnOnce() -> R + Send) -> ComResult<R> {
+        fn caught<R>(r: std::thread::Result<R>) -> ComResult<R> {
+            r.map_err(|payload| {
+                let msg = payload
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("cross-thread call panicked");
+                ComError::new(HResultE::Fail, msg)
+            })
+        }
+
+        if self.is_current() {
+            return caught(std::panic::catch_unwind(AssertUnwindSafe(f)));
+        }
+        struct Completion<R> {
+            result: Mutex<Option<std::thread::Result<R>>>,
+            finished: AtomicBool,
+            waiter: Arc<Queue>,
+        }
+        /// finishes the call even when the task is dropped without running
+        struct Signal<R>(Arc<Completion<R>>);
+        impl<R> Drop for Signal<R> {
+            fn drop(&mut self) {
+                self.0.finished.store(true, Ordering::Release);
+                self.0.waiter.wake();
+            }
+        }
+
+        let completion = Arc::new(Completion {
+            result: Mutex::new(None),
+            finished: AtomicBool::new(false),
+            waiter: Queue::current(),
+        });
+        let signal = Signal(completion.clone());
+        let task: Box<dyn FnOnce() + Send + '_> = Box::new(move || {
+            let signal = signal;
+            let r = std::panic::catch_unwind(AssertUnwindSafe(f));
+            *signal.0.result.lock().unwrap() = Some(r);
+        });
+        // the task borrows from this frame, which waits below until it ran or was dropped
+        let task = unsafe { core::mem::transmute::<Box<dyn FnOnce() + Send + '_>, Task>(task) };
+        if self.queue.push(task).is_err() {
+            return Err(HResultE::Disconnected.into());
+        }
+        completion
+            .waiter
+            .run_until(|| completion.finished.load(Ordering::Acquire));
+        let r = completion.result.lock().unwrap().take();
+        match r {
+            Some(r) => caught(r),
+            None => Err(HResultE::Fail.into()),
+        }
+    }
+
+    /// Runs the tasks queued for the calling thread, returns how many ran
+    pub fn pump() -> usize {
+        Queue::current().pump()
+    }
+
+    /// Runs the calling thread's tasks as they arrive until `done` returns true, which is
+    /// checked before each task. Returns how many tasks ran.
+    pub fn run_until(done: impl FnMut() -> bool) -> usize {
+        Queue::current().run_until(done)
+    }
+}
+
+impl fmt::Debug for Dispatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dispatcher")
+            .field("thread", &self.queue.thread)
+            .finish()
+    }
+}
+
+/// moves values that are only touched while their owner waits
+struct AssertSend<T>(T);
+
+unsafe impl<T> Send for AssertSend<T> {}
+
+impl<T> AssertSend<T> {
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// ThreadBound
+
+/// A value that can be sent to other threads but only used on the thread that created it.
+///
+/// Dropping it elsewhere posts the drop to the owner thread.
+pub struct ThreadBound<T: 'static> {
+    value: ManuallyDrop<T>,
+    owner: Dispatcher,
+}
+
+unsafe impl<T: 'static> Send for ThreadBound<T> {}
+unsafe impl<T: 'static> Sync for ThreadBound<T> {}
+
+impl<T: 'static> ThreadBound<T> {
+    /// binds `value` to the calling thread
+    pub fn new(value: T) -> Self {
+        Self::with_owner(value, Dispatcher::current())
+    }
+
+    fn with_owner(value: T, owner: Dispatcher) -> Self {
+        Self {
+            value: ManuallyDrop::new(value),
+            owner,
+        }
+    }
+
+    pub fn dispatcher(&self) -> &Dispatcher {
+        &self.owner
+    }
+
+    pub fn is_owner(&self) -> bool {
+        self.owner.is_current()
+    }
+
+    /// the value, on the owner thread only
+    pub fn get(&self) -> Option<&T> {
+        self.is_owner().then_some(&*self.value)
+    }
+
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        self.is_owner().then_some(&mut *self.value)
+    }
+
+    /// unwraps the value on the owner thread, gives it back elsewhere
+    pub fn into_inner(self) -> Result<T, Self> {
+        if !self.is_owner() {
+            return Err(self);
+        }
+        let mut this = ManuallyDrop::new(self);
+        unsafe {
+            ptr::drop_in_place(&mut this.owner);
+            Ok(ManuallyDrop::take(&mut this.value))
+        }
+    }
+
+    /// Calls `f` with the value on the owner thread and waits for the result, see
+    /// [`Dispatcher::send`]
+    pub fn run<R: Send>(&self, f: impl FnOnce(&T) -> R + Send) -> ComResult<R> {
+        let value = AssertSend(&*self.value as *const T);
+        self.owner.send(move || f(unsafe { &*value.into_inner() }))
+    }
+}
+
+impl<T: 'static> Drop for ThreadBound<T> {
+    fn drop(&mut self) {
+        if self.is_owner() {
+            unsafe { ManuallyDrop::drop(&mut self.value) };
+            return;
+        }
+        let value = AssertSend(unsafe { ManuallyDrop::take(&mut self.value) });
+        // a value whose thread is gone cannot be dropped anywhere, it leaks
+        if let Err(task) = self
+            .owner
+            .queue
+            .push(Box::new(move || drop(value.into_inner())))
+        {
+            core::mem::forget(task);
+        }
+    }
+}
+
+impl<T: 'static> fmt::Debug for ThreadBound<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThreadBound")
+            .field("thread", &self.owner.thread())
+            .finish()
+    }
+}
+
+impl<I: ThreadInterface> ThreadBound<ComPtr<I>> {
+    /// A proxy whose calls run on the owner thread, usable from any thread
+    pub fn proxy(&self) -> ComResult<ComPtr<I>> {
+        let target = self.run(|p| p.clone())?;
+        let target =
+            unsafe { ComPtr::<IUnknown>::new(NonNull::new_unchecked(target.leak()).cast()) };
+        let proxy = Box::new(ThreadProxy {
+            vtbl: I::THREAD_VTBL_REF as *const I::VitualTable as _,
+            strong: AtomicU32::new(1),
+            weak: AtomicU32::new(1),
+            target: ManuallyDrop::new(ThreadBound::with_owner(target, self.owner.clone())),
+            implements: I::implements,
+        });
+        Ok(unsafe { ComPtr::new(NonNull::from(Box::leak(proxy)).cast()) })
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// proxy
+
+/// An interface whose calls can be forwarded to another thread, implemented by `#[interface]`
+///
+/// # Safety
+/// `THREAD_VTBL` must forward every slot with [`ThreadProxy::call`] to the same method.
+pub unsafe trait ThreadInterface: Interface + impls::RefCount {
+    const THREAD_VTBL: Self::VitualTable;
+    /// [`ThreadInterface::THREAD_VTBL`] as a reference usable from const contexts
+    const THREAD_VTBL_REF: &'static Self::VitualTable;
+
+    /// whether `iid` is this interface or one of its parents
+    fn implements(iid: &Guid) -> bool;
+}
+
+/// What a thread proxy interface pointer points to
+#[repr(C)]
+pub struct ThreadProxy {
+    vtbl: *const c_void,
+    strong: AtomicU32,
+    /// weak references plus one for all strong ones
+    weak: AtomicU32,
+    /// dropped with the last strong reference
+    target: ManuallyDrop<ThreadBound<ComPtr<IUnknown>>>,
+    implements: fn(&Guid) -> bool,
+}
+
+impl ThreadProxy {
+    /// Body of every thread proxy vtable slot: calls `f` with the target on its thread
+    ///
+    /// # Safety
+    /// `this` must be a thread proxy whose target implements `I`
+    pub unsafe fn call<I: Interface, R>(
+        this: *const c_void,
+        f: impl FnOnce(&I) -> R,
+    ) -> ComResult<R> {
+        let this = unsafe { &*(this as *const Self) };
+        let target = AssertSend(this.target.value.const_ptr() as *const I);
+        let f = AssertSend(f);
+        this.target
+            .owner
+            .send(move || AssertSend(f.into_inner()(unsafe { &*target.into_inner() })))
+            .map(AssertSend::into_inner)
+    }
+
+    unsafe fn release_weak(this: *const Self) -> u32 {
+        unsafe {
+            let r = (*this).weak.fetch_sub(1, Ordering::Release);
+            if r == 1 {
+                core::sync::atomic::fence(Ordering::Acquire);
+                drop(Box::from_raw(this as *mut Self));
+            }
+            r
+        }
+    }
+}
+
+unsafe extern "C" fn thread_query_interface(
+    this: *const IUnknown,
+    iid: *const Guid,
+    out: *mut *mut c_void,
+) -> HResult {
+    unsafe {
+        if iid.is_null() || out.is_null() {
+            return HResultE::Pointer.into();
+        }
+        let proxy = &*(this as *const ThreadProxy);
+        if !(proxy.implements)(&*iid) {
+            *out = ptr::null_mut();
+            return HResultE::NoInterface.into();
+        }
+        proxy.strong.fetch_add(1, Ordering::Relaxed);
+        *out = this as _;
+        HResultE::Ok.into()
+    }
+}
+
+unsafe extern "C" fn thread_add_ref(this: *const IUnknown) -> u32 {
+    unsafe {
+        (*(this as *const ThreadProxy))
+            .strong
+            .fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+unsafe extern "C" fn thread_release(this: *const IUnknown) -> u32 {
+    unsafe {
+        let this = this as *mut ThreadProxy;
+        let r = (*this).strong.fetch_sub(1, Ordering::Release);
+        if r == 1 {
+            core::sync::atomic::fence(Ordering::Acquire);
+            ManuallyDrop::drop(&mut (*this).target);
+            ThreadProxy::release_weak(this);
+        }
+        r
+    }
+}
+
+unsafe extern "C" fn thread_add_ref_weak(this: *const IWeak) -> u32 {
+    unsafe {
+        (*(this as *const ThreadProxy))
+            .weak
+            .fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+unsafe extern "C" fn thread_release_weak(this: *const IWeak) -> u32 {
+    unsafe { ThreadProxy::release_weak(this as _) }
+}
+
+unsafe extern "C" fn thread_try_upgrade(this: *const IWeak) -> B8 {
+    unsafe {
+        (*(this as *const ThreadProxy))
+            .strong
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |n| {
+                (n != 0).then(|| n + 1)
+            })
+            .is_ok()
+            .into()
+    }
+}
+
+unsafe impl ThreadInterface for IUnknown {
+    const THREAD_VTBL: VitualTable_IUnknown = VitualTable_IUnknown {
+        f_QueryInterface: thread_query_interface,
+        f_AddRef: thread_add_ref,
+        f_Release: thread_release,
+    };
+    const THREAD_VTBL_REF: &'static VitualTable_IUnknown = &Self::THREAD_VTBL;
+
+    fn implements(iid: &Guid) -> bool {
+        *iid == IUnknown::GUID
+    }
+}
+
+unsafe impl ThreadInterface for IWeak {
+    const THREAD_VTBL: VitualTable_IWeak = VitualTable_IWeak {
+        b: IUnknown::THREAD_VTBL,
+        f_AddRefWeak: thread_add_ref_weak,
+        f_ReleaseWeak: thread_release_weak,
+        f_TryUpgrade: thread_try_upgrade,
+    };
+    const THREAD_VTBL_REF: &'static VitualTable_IWeak = &Self::THREAD_VTBL;
+
+    fn implements(iid: &Guid) -> bool {
+        *iid == IWeak::GUID || IUnknown::implements(iid)
+    }
+}
+
+#[doc(hidden)]
+pub mod __private {
+    use super::*;
+
+    /// what a thread proxy slot returns when the call could not run
+    pub struct Failed<R>(ComError, core::marker::PhantomData<R>);
+
+    impl<R> Failed<R> {
+        pub fn new(e: ComError) -> Self {
+            Self(e, core::marker::PhantomData)
+        }
+    }
+
+    /// `HResult` and `()` report the failure
+    pub trait ViaReport {
+        type Ret;
+        fn failed(&self) -> Self::Ret;
+    }
+
+    impl ViaReport for &Failed<HResult> {
+        type Ret = HResult;
+        fn failed(&self) -> HResult {
+            self.0.clone().into_hresult()
+        }
+    }
+
+    impl ViaReport for &Failed<()> {
+        type Ret = ();
+        fn failed(&self) {}
+    }
+
+    /// other types cannot, the proxy slot must not unwind so this aborts
+    pub trait ViaAbort<R> {
+        fn failed(&self) -> R;
+    }
+
+    impl<R> ViaAbort<R> for Failed<R> {
+        fn failed(&self) -> R {
+            crate::hardened::fatal(format_args!(
+                "cross-thread call returning {} failed with {:?}",
+                core::any::type_name::<R>(),
+                self.0
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::cell::Cell;
+    use std::{sync::mpsc, thread};
+
+    use self::counter::{ICounter, impls};
+    use crate::{object::*, thread::*, *};
+
+    mod counter {
+        use crate::{Guid, HResult, IUnknown, Interface};
+
+        #[cocom::interface("7c3e5b10-2a4d-4f6e-8b91-0d5c4a7e9f13")]
+        pub trait ICounter: IUnknown {
+            fn Add(&self, n: u32) -> u32;
+            fn OnOwner(&self) -> bool;
+        }
+
+        test_details! {
+            ICounter: IUnknown {
+                fn Add(&self, n: u32) -> u32;
+                fn OnOwner(&self) -> bool;
+            }
+        }
+
+        pub mod impls {
+            pub use crate::impls::*;
+
+            pub trait ICounter: IUnknown {
+                fn Add(&self, n: u32) -> u32;
+                fn OnOwner(&self) -> bool;
+            }
+        }
+    }
+
+    /// not `Sync`, only usable on its thread
+    #[object(ICounter)]
+    pub struct Counter {
+        n: Cell<u32>,
+        owner: thread::ThreadId,
+    }
+
+    impl impls::ICounter for Counter {
+        fn Add(&self, n: u32) -> u32 {
+            self.n.set(self.n.get() + n);
+            self.n.get()
+        }
+
+        fn OnOwner(&self) -> bool {
+            thread::current().id() == self.owner
+        }
+    }
+
+    #[test]
+    fn same_thread() {
+        let bound = ThreadBound::new(Cell::new(1));
+        assert!(bound.is_owner());
+        bound.get().unwrap().set(2);
+        assert_eq!(bound.run(|c| c.get()), Ok(2));
+        assert_eq!(bound.into_inner().unwrap().get(), 2);
+    }
+
+    #[test]
+    fn proxy_calls_run_on_owner() {
+        let (tx, rx) = mpsc::channel();
+        let owner = thread::spawn(move || {
+            let counter = Counter {
+                n: Cell::new(0),
+                owner: thread::current().id(),
+            }
+            .make_com();
+            let stop = Arc::new(AtomicBool::new(false));
+            let bound = (
+                ThreadBound::new(counter),
+                ThreadBound::new(Cell::new(7u32)),
+                ThreadBound::new(stop.clone()),
+            );
+            tx.send(bound).unwrap();
+            Dispatcher::run_until(|| stop.load(Ordering::Relaxed));
+        });
+        let (bound, cell, stop) = rx.recv().unwrap();
+        assert!(bound.get().is_none());
+        assert_eq!(bound.run(|c| c.OnOwner()), Ok(true));
+
+        let proxy = bound.proxy().unwrap();
+        assert_eq!(proxy.Add(2), 2);
+        let other = proxy.clone();
+        let t = thread::spawn(move || other.Add(3));
+        assert_eq!(t.join().unwrap(), 5);
+        assert!(proxy.OnOwner());
+
+        let unk = proxy.query_interface::<IUnknown>().unwrap();
+        assert_eq!(unk.const_ptr() as *const (), proxy.const_ptr() as *const ());
+        assert_eq!(
+            proxy.query_interface::<IWeak>().unwrap_err(),
+            HResult::from(HResultE::NoInterface)
+        );
+        drop((unk, proxy, bound));
+
+        stop.run(|stop| stop.store(true, Ordering::Relaxed))
+            .unwrap();
+        owner.join().unwrap();
+        assert_eq!(cell.run(|c| c.get()), Err(HResultE::Disconnected.into()));
+    }
+
+    #[test]
+    fn panic_is_reported() {
+        let (tx, rx) = mpsc::channel();
+        let owner = thread::spawn(move || {
+            let stop = Arc::new(AtomicBool::new(false));
+            tx.send(ThreadBound::new(stop.clone())).unwrap();
+            Dispatcher::run_until(|| stop.load(Ordering::Relaxed));
+        });
+        let stop = rx.recv().unwrap();
+        let e = stop.run(|_| -> u32 { panic!("boom") }).unwrap_err();
+        assert_eq!(e.code(), HResult::from(HResultE::Fail));
+        assert_eq!(e.description(), "boom");
+        let e = Dispatcher::current().send(|| panic!("here")).unwrap_err();
+        assert_eq!(e.code(), HResult::from(HResultE::Fail));
+
+        stop.run(|stop| stop.store(true, Ordering::Relaxed))
+            .unwrap();
+        owner.join().unwrap();
+    }
+
+    #[test]
+    fn disconnect_aborts_pod_return() {
+        const CHILD: &str = "COCOM_THREAD_ABORT_CHILD";
+        if std::env::var_os(CHILD).is_some() {
+            let (tx, rx) = mpsc::channel();
+            let owner = thread::spawn(move || {
+                let counter = Counter {
+                    n: Cell::new(0),
+                    owner: thread::current().id(),
+                }
+                .make_com();
+                tx.send(ThreadBound::new(counter).proxy().unwrap()).unwrap();
+            });
+            let proxy = rx.recv().unwrap();
+            owner.join().unwrap();
+            proxy.Add(1);
+            unreachable!();
+        }
+
+        // the abort takes the whole process down, so the call runs in a copy of this test
+        let out = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "thread::test::disconnect_aborts_pod_return",
+                "--nocapture",
+            ])
+            .env(CHILD, "1")
+            .output()
+            .unwrap();
+        assert!(!out.status.success());
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(
+            stderr.contains("cross-thread call returning u32 failed with"),
+            "{stderr}"
+        );
+    }
+}
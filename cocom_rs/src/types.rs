@@ -0,0 +1,183 @@
+//! Primitive ABI types of `CoCom.Types.h` that have no exact Rust counterpart.
+//!
+//! Every bit pattern is a valid value, unlike `bool` and `char`, so whatever the other side
+//! writes can be read safely and checked afterwards.
+
+use core::hash::{Hash, Hasher};
+
+/// `Coplt::b8`, an `int8_t` where any non zero value is true
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct B8(pub i8);
+
+/// `Coplt::b32`, an `int32_t` where any non zero value is true
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct B32(pub i32);
+
+/// `Coplt::char8`, one utf-8 code unit
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Char8(pub u8);
+
+/// `Coplt::char16`, one utf-16 code unit; `wchar_t` on windows, `char16_t` elsewhere
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Char16(pub u16);
+
+/// `Coplt::char32`, one utf-32 code unit, not checked to be a scalar value; `char32_t` on
+/// windows, `wchar_t` elsewhere
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Char32(pub u32);
+
+const _: () = {
+    use core::mem::{align_of, size_of};
+    assert!(size_of::<B8>() == 1 && align_of::<B8>() == 1);
+    assert!(size_of::<B32>() == 4 && align_of::<B32>() == 4);
+    assert!(size_of::<Char8>() == 1 && align_of::<Char8>() == 1);
+    assert!(size_of::<Char16>() == 2 && align_of::<Char16>() == 2);
+    assert!(size_of::<Char32>() == 4 && align_of::<Char32>() == 4);
+};
+
+macro_rules! boolean {
+    ($($t:ident($i:ty)),*) => { $(
+        impl $t {
+            pub const FALSE: Self = Self(0);
+            pub const TRUE: Self = Self(1);
+
+            pub const fn new(value: bool) -> Self {
+                Self(value as $i)
+            }
+
+            pub const fn get(self) -> bool {
+                self.0 != 0
+            }
+        }
+
+        /// compares truth, not the stored value
+        impl PartialEq for $t {
+            fn eq(&self, other: &Self) -> bool {
+                self.get() == other.get()
+            }
+        }
+
+        impl Eq for $t {}
+
+        impl Hash for $t {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.get().hash(state)
+            }
+        }
+
+        impl From<bool> for $t {
+            fn from(value: bool) -> Self {
+                Self::new(value)
+            }
+        }
+
+        impl From<$t> for bool {
+            fn from(value: $t) -> Self {
+                value.get()
+            }
+        }
+    )* };
+}
+
+boolean!(B8(i8), B32(i32));
+
+macro_rules! code_unit {
+    ($($t:ident($u:ty)),*) => { $(
+        impl From<$u> for $t {
+            fn from(value: $u) -> Self {
+                Self(value)
+            }
+        }
+
+        impl From<$t> for $u {
+            fn from(value: $t) -> Self {
+                value.0
+            }
+        }
+    )* };
+}
+
+code_unit!(Char8(u8), Char16(u16), Char32(u32));
+
+/// a lone code unit is a `char` only in the ascii range
+impl TryFrom<Char8> for char {
+    type Error = Char8;
+
+    fn try_from(value: Char8) -> Result<Self, Self::Error> {
+        if value.0.is_ascii() {
+            Ok(value.0 as char)
+        } else {
+            Err(value)
+        }
+    }
+}
+
+/// surrogates are not `char`s
+impl TryFrom<Char16> for char {
+    type Error = Char16;
+
+    fn try_from(value: Char16) -> Result<Self, Self::Error> {
+        char::from_u32(value.0 as u32).ok_or(value)
+    }
+}
+
+impl TryFrom<Char32> for char {
+    type Error = Char32;
+
+    fn try_from(value: Char32) -> Result<Self, Self::Error> {
+        char::from_u32(value.0).ok_or(value)
+    }
+}
+
+impl From<char> for Char32 {
+    fn from(value: char) -> Self {
+        Self(value as u32)
+    }
+}
+
+/// fails for chars outside the basic multilingual plane
+impl TryFrom<char> for Char16 {
+    type Error = char;
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        u16::try_from(value as u32).map(Self).map_err(|_| value)
+    }
+}
+
+impl TryFrom<char> for Char8 {
+    type Error = char;
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        if value.is_ascii() {
+            Ok(Self(value as u8))
+        } else {
+            Err(value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        assert!(bool::from(B8(-1)));
+        assert_eq!(B8(2), B8::TRUE);
+        assert_ne!(B32(0), B32::TRUE);
+        assert_eq!(B32::from(true).0, 1);
+
+        assert_eq!(char::try_from(Char8(b'a')), Ok('a'));
+        assert!(char::try_from(Char8(0xc3)).is_err());
+        assert_eq!(char::try_from(Char16(0x00e9)), Ok('é'));
+        assert!(char::try_from(Char16(0xd800)).is_err());
+        assert_eq!(Char16::try_from('😀'), Err('😀'));
+        assert_eq!(char::try_from(Char32::from('😀')), Ok('😀'));
+        assert_eq!(u16::from(Char16::from(7u16)), 7);
+    }
+}
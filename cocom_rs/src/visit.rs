@@ -0,0 +1,163 @@
+//! Discovering the strong references an object holds.
+//!
+//! Under the `track-objects` or `gc` feature, `#[object]` structs get an
+//! [`impls::Object::trace`] that visits every field implementing [`Trace`]; fields of other types, including generic ones without a `Trace` bound, are
+//! skipped. A missed edge only ever makes an object look externally referenced.
+
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{
+    com_ptr::ComPtr,
+    impls,
+    object::{ObjectPtr, WeakObjectPtr},
+    sync::SpinLock,
+};
+
+/// Receives the interface pointer of every strong reference found
+pub trait Visitor {
+    fn visit(&mut self, target: *const ());
+}
+
+impl<F: FnMut(*const ())> Visitor for F {
+    fn visit(&mut self, target: *const ()) {
+        self(target)
+    }
+}
+
+/// A value that may own strong COM references
+pub trait Trace {
+    fn trace(&self, visitor: &mut dyn Visitor);
+}
+
+impl<T: impls::RefCount> Trace for ComPtr<T> {
+    fn trace(&self, visitor: &mut dyn Visitor) {
+        visitor.visit(self.const_ptr() as _);
+    }
+}
+
+impl<T: impls::Object> Trace for ObjectPtr<T> {
+    fn trace(&self, visitor: &mut dyn Visitor) {
+        visitor.visit(self.as_ref() as *const T::Interface as _);
+    }
+}
+
+impl<T: impls::Object> Trace for WeakObjectPtr<T> {
+    fn trace(&self, visitor: &mut dyn Visitor) {
+        visitor.visit(self.as_ref() as *const T::Interface as _);
+    }
+}
+
+impl<T: Trace> Trace for SpinLock<T> {
+    fn trace(&self, visitor: &mut dyn Visitor) {
+        self.lock().trace(visitor);
+    }
+}
+
+impl<T: Trace> Trace for Option<T> {
+    fn trace(&self, visitor: &mut dyn Visitor) {
+        if let Some(v) = self {
+            v.trace(visitor);
+        }
+    }
+}
+
+impl<T: Trace + ?Sized> Trace for Box<T> {
+    fn trace(&self, visitor: &mut dyn Visitor) {
+        (**self).trace(visitor);
+    }
+}
+
+impl<T: Trace> Trace for [T] {
+    fn trace(&self, visitor: &mut dyn Visitor) {
+        for v in self {
+            v.trace(visitor);
+        }
+    }
+}
+
+impl<T: Trace, const N: usize> Trace for [T; N] {
+    fn trace(&self, visitor: &mut dyn Visitor) {
+        self.as_slice().trace(visitor);
+    }
+}
+
+impl<T: Trace> Trace for Vec<T> {
+    fn trace(&self, visitor: &mut dyn Visitor) {
+        self.as_slice().trace(visitor);
+    }
+}
+
+/// Autoref dispatch used by `#[object]`, not a public API
+#[doc(hidden)]
+pub mod __private {
+    use super::*;
+
+    pub struct TraceField<'a, T: ?Sized>(pub &'a T);
+
+    pub trait ViaTrace {
+        fn trace_field(&self, visitor: &mut dyn Visitor);
+    }
+
+    impl<T: Trace + ?Sized> ViaTrace for &TraceField<'_, T> {
+        fn trace_field(&self, visitor: &mut dyn Visitor) {
+            self.0.trace(visitor);
+        }
+    }
+
+    pub trait ViaNothing {
+        fn trace_field(&self, _visitor: &mut dyn Visitor) {}
+    }
+
+    impl<T: ?Sized> ViaNothing for TraceField<'_, T> {}
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec::Vec;
+
+    use crate::{object::*, visit::*, *};
+
+    #[object(IUnknown)]
+    #[derive(Debug)]
+    struct Leaf {
+        a: u32,
+    }
+
+    #[object(IUnknown)]
+    #[derive(Debug)]
+    struct Holder {
+        a: u32,
+        one: ComPtr<IUnknown>,
+        maybe: Option<ObjectPtr<Leaf>>,
+        many: Vec<ComPtr<IUnknown>>,
+        weak: ComWeak<IWeak>,
+    }
+
+    #[object(IWeak)]
+    #[derive(Debug)]
+    struct Target {
+        a: u32,
+    }
+
+    impl impls::IWeak for Target {}
+
+    #[test]
+    #[cfg(any(feature = "track-objects", feature = "gc"))]
+    fn object_fields() {
+        let leaf = Leaf { a: 1 }.make_object();
+        let one = Leaf { a: 2 }.make_com();
+        let target = Target { a: 3 }.make_com_weak();
+        let holder = Holder {
+            a: 0,
+            one: one.clone(),
+            maybe: Some(leaf.clone()),
+            many: alloc::vec![one.clone(), one.clone()],
+            weak: target.downgrade(),
+        };
+        let mut seen = Vec::new();
+        impls::Object::trace(&holder, &mut |p| seen.push(p));
+        let one = one.const_ptr() as *const ();
+        let leaf = leaf.as_com().const_ptr() as *const ();
+        assert_eq!(seen, [one, leaf, one, one]);
+    }
+}
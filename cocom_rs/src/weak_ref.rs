@@ -0,0 +1,499 @@
+use core::{
+    fmt::Debug,
+    marker::PhantomData,
+    ptr::{self, NonNull},
+};
+
+use crate::{
+    ComPtr, ComWeak, Guid, HResult, HResultE, IUnknown, IWeak, Interface, OutComPtr, object,
+    object::{MakeObject, Object, ObjectPtr},
+    sync::SpinLock,
+};
+
+#[cocom::interface("00000037-0000-0000-c000-000000000046")]
+pub trait IWeakReference: IUnknown {
+    fn Resolve(&self, iid: *const Guid, out: *mut *mut IUnknown) -> HResult;
+}
+
+#[cocom::interface("00000038-0000-0000-c000-000000000046")]
+pub trait IWeakReferenceSource: IUnknown {
+    fn GetWeakReference(&self, out: *mut *mut IWeakReference) -> HResult;
+}
+
+pub mod details {
+    use super::*;
+    pub use crate::details::*;
+
+    struct VT<T, V, O>(core::marker::PhantomData<(T, V, O)>);
+
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct VitualTable_IWeakReference {
+        b: <IUnknown as Interface>::VitualTable,
+
+        pub f_Resolve: unsafe extern "C" fn(
+            this: *const IWeakReference,
+            iid: *const Guid,
+            out: *mut *mut IUnknown,
+        ) -> HResult,
+    }
+
+    impl<T: impls::IWeakReference + impls::Object, O: impls::ObjectBox<Object = T>>
+        VT<T, IWeakReference, O>
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        pub const VTBL: VitualTable_IWeakReference = VitualTable_IWeakReference {
+            b: <IUnknown as Vtbl<O>>::VTBL,
+            f_Resolve: Self::f_Resolve,
+        };
+
+        unsafe extern "C" fn f_Resolve(
+            this: *const IWeakReference,
+            iid: *const Guid,
+            out: *mut *mut IUnknown,
+        ) -> HResult {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    return HResultE::IllegalMethodCall.into();
+                };
+                let Some(out) = OutComPtr::new(out) else {
+                    return HResultE::Pointer.into();
+                };
+                (*O::GetObject(this as _)).Resolve(iid, out)
+            }
+        }
+    }
+
+    impl<T: impls::IWeakReference + impls::Object, O: impls::ObjectBox<Object = T>> Vtbl<O>
+        for IWeakReference
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        const VTBL: <IWeakReference as Interface>::VitualTable = VT::<T, IWeakReference, O>::VTBL;
+        const VTBL_REF: &'static <IWeakReference as Interface>::VitualTable =
+            &<Self as Vtbl<O>>::VTBL;
+
+        fn vtbl() -> &'static Self::VitualTable {
+            &<Self as Vtbl<O>>::VTBL
+        }
+    }
+
+    impl<T: impls::IWeakReference + impls::Object, O: impls::ObjectBox<Object = T>>
+        QuIn<IWeakReference, T, O> for IWeakReference
+    {
+        #[inline(always)]
+        unsafe fn QueryInterface(
+            this: *mut T,
+            guid: Guid,
+            out: *mut *mut core::ffi::c_void,
+        ) -> HResult {
+            unsafe {
+                static GUID: Guid = IWeakReference::GUID;
+                if guid == GUID {
+                    *out = this as _;
+                    O::AddRef(this as _);
+                    return HResultE::Ok.into();
+                }
+                <IUnknown as QuIn<IUnknown, T, O>>::QueryInterface(this, guid, out)
+            }
+        }
+    }
+
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct VitualTable_IWeakReferenceSource {
+        b: <IUnknown as Interface>::VitualTable,
+
+        pub f_GetWeakReference: unsafe extern "C" fn(
+            this: *const IWeakReferenceSource,
+            out: *mut *mut IWeakReference,
+        ) -> HResult,
+    }
+
+    impl<T: impls::IWeakReferenceSource + impls::Object, O: impls::ObjectBox<Object = T>>
+        VT<T, IWeakReferenceSource, O>
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        pub const VTBL: VitualTable_IWeakReferenceSource = VitualTable_IWeakReferenceSource {
+            b: <IUnknown as Vtbl<O>>::VTBL,
+            f_GetWeakReference: Self::f_GetWeakReference,
+        };
+
+        unsafe extern "C" fn f_GetWeakReference(
+            this: *const IWeakReferenceSource,
+            out: *mut *mut IWeakReference,
+        ) -> HResult {
+            unsafe {
+                let Some(_guard) = O::Borrow(this as _) else {
+                    return HResultE::IllegalMethodCall.into();
+                };
+                let Some(out) = OutComPtr::new(out) else {
+                    return HResultE::Pointer.into();
+                };
+                (*O::GetObject(this as _)).GetWeakReference(out)
+            }
+        }
+    }
+
+    impl<T: impls::IWeakReferenceSource + impls::Object, O: impls::ObjectBox<Object = T>> Vtbl<O>
+        for IWeakReferenceSource
+    where
+        T::Interface: details::QuIn<T::Interface, T, O>,
+    {
+        const VTBL: <IWeakReferenceSource as Interface>::VitualTable =
+            VT::<T, IWeakReferenceSource, O>::VTBL;
+        const VTBL_REF: &'static <IWeakReferenceSource as Interface>::VitualTable =
+            &<Self as Vtbl<O>>::VTBL;
+
+        fn vtbl() -> &'static Self::VitualTable {
+            &<Self as Vtbl<O>>::VTBL
+        }
+    }
+
+    impl<T: impls::IWeakReferenceSource + impls::Object, O: impls::ObjectBox<Object = T>>
+        QuIn<IWeakReferenceSource, T, O> for IWeakReferenceSource
+    {
+        #[inline(always)]
+        unsafe fn QueryInterface(
+            this: *mut T,
+            guid: Guid,
+            out: *mut *mut core::ffi::c_void,
+        ) -> HResult {
+            unsafe {
+                static GUID: Guid = IWeakReferenceSource::GUID;
+                if guid == GUID {
+                    *out = this as _;
+                    O::AddRef(this as _);
+                    return HResultE::Ok.into();
+                }
+                <IUnknown as QuIn<IUnknown, T, O>>::QueryInterface(this, guid, out)
+            }
+        }
+    }
+}
+
+pub mod impls {
+    pub use crate::impls::*;
+    use crate::{Guid, HResult, OutComPtr};
+
+    pub trait IWeakReference: IUnknown {
+        fn Resolve(&self, iid: *const Guid, out: OutComPtr<super::IUnknown>) -> HResult;
+    }
+
+    pub trait IWeakReferenceSource: IUnknown {
+        fn GetWeakReference(&self, out: OutComPtr<super::IWeakReference>) -> HResult;
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// side table
+
+struct WeakTarget {
+    obj: *mut IUnknown,
+    try_upgrade: unsafe fn(*mut IUnknown) -> bool,
+}
+
+unsafe impl Send for WeakTarget {}
+
+/// side-table control block handed out as `IWeakReference`
+///
+/// holds no reference to its source, the source detaches it before the block is freed
+#[object(IWeakReference)]
+pub struct WeakReference {
+    target: SpinLock<Option<WeakTarget>>,
+}
+
+impl Debug for WeakReference {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("WeakReference")
+            .field("alive", &self.target.lock().is_some())
+            .finish()
+    }
+}
+
+impl impls::IWeakReference for WeakReference {
+    fn Resolve(&self, iid: *const Guid, out: OutComPtr<IUnknown>) -> HResult {
+        if iid.is_null() {
+            return HResultE::Pointer.into();
+        }
+        unsafe {
+            let obj = match &*self.target.lock() {
+                Some(target) if (target.try_upgrade)(target.obj) => target.obj,
+                _ => return HResultE::Ok.into(),
+            };
+            let r = (*obj).QueryInterface(iid, out.as_ptr() as _);
+            (*obj).Release();
+            r
+        }
+    }
+}
+
+/// lazily created side table for objects that implement `IWeakReferenceSource` instead of `IWeak`
+///
+/// embed it in the object and forward `GetWeakReference` to [`WeakRefSource::get_weak_reference`]
+#[derive(Default)]
+pub struct WeakRefSource {
+    table: SpinLock<Option<ObjectPtr<WeakReference>>>,
+}
+
+impl WeakRefSource {
+    pub const fn new() -> Self {
+        Self {
+            table: SpinLock::new(None),
+        }
+    }
+
+    /// hands out the side table, creating it on first use
+    ///
+    /// # Safety
+    /// `this` must be the value of an [`Object<T>`] that owns this source,
+    /// and [`impls::Object::detach`](crate::impls::Object::detach) must detach it
+    pub unsafe fn get_weak_reference<T: crate::impls::Object>(
+        &self,
+        this: &T,
+        out: OutComPtr<IWeakReference>,
+    ) -> HResult {
+        unsafe fn try_upgrade<T: crate::impls::Object>(obj: *mut IUnknown) -> bool {
+            unsafe { Object::<T>::TryUpgrade(obj as _) }
+        }
+
+        let mut table = self.table.lock();
+        let table = table.get_or_insert_with(|| {
+            WeakReference {
+                target: SpinLock::new(Some(WeakTarget {
+                    obj: unsafe { Object::FromValue(this as *const T as *mut T) } as _,
+                    try_upgrade: try_upgrade::<T>,
+                })),
+            }
+            .make_object()
+        });
+        out.write(table.clone().to_com());
+        HResultE::Ok.into()
+    }
+
+    /// cuts the side table loose, weak references handed out so far stop resolving
+    pub fn detach(&self) {
+        let table = self.table.lock().take();
+        if let Some(table) = table {
+            *table.target.lock() = None;
+        }
+    }
+}
+
+/// Autoref dispatch used by `#[object]`, not a public API
+#[doc(hidden)]
+pub mod __private {
+    use super::WeakRefSource;
+
+    pub struct DetachField<'a, T: ?Sized>(pub &'a T);
+
+    pub trait ViaDetach {
+        fn detach_field(&self);
+    }
+
+    impl ViaDetach for &DetachField<'_, WeakRefSource> {
+        fn detach_field(&self) {
+            self.0.detach();
+        }
+    }
+
+    pub trait ViaNothing {
+        fn detach_field(&self) {}
+    }
+
+    impl<T: ?Sized> ViaNothing for DetachField<'_, T> {}
+}
+
+impl Debug for WeakRefSource {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("WeakRefSource")
+            .field("created", &self.table.lock().is_some())
+            .finish()
+    }
+}
+
+impl Drop for WeakRefSource {
+    fn drop(&mut self) {
+        self.detach();
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////// any
+
+enum WeakAny {
+    Native(ComWeak<IWeak>),
+    SideTable(ComPtr<IWeakReference>),
+}
+
+/// weak reference to any object that supports either `IWeak` or `IWeakReferenceSource`
+pub struct ComWeakAny<I: Interface + crate::impls::RefCount> {
+    inner: WeakAny,
+    _p: PhantomData<fn() -> ComPtr<I>>,
+}
+
+impl<I: Interface + crate::impls::RefCount> ComWeakAny<I> {
+    /// fails with `NoInterface` when the object supports neither weak mechanism
+    pub fn new(ptr: &ComPtr<I>) -> Result<Self, HResult> {
+        let inner = if let Ok(weak) = ptr.query_interface::<IWeak>() {
+            WeakAny::Native(weak.downgrade())
+        } else if let Ok(source) = ptr.query_interface::<IWeakReferenceSource>() {
+            let mut out = ptr::null_mut();
+            let r = source.GetWeakReference(&mut out);
+            if r.is_failure() {
+                return Err(r);
+            }
+            match NonNull::new(out) {
+                Some(out) => WeakAny::SideTable(unsafe { ComPtr::new(out) }),
+                None => return Err(HResultE::Pointer.into()),
+            }
+        } else {
+            return Err(HResultE::NoInterface.into());
+        };
+        Ok(Self {
+            inner,
+            _p: PhantomData,
+        })
+    }
+
+    pub fn is_side_table(&self) -> bool {
+        matches!(self.inner, WeakAny::SideTable(_))
+    }
+
+    pub fn upgrade(&self) -> Option<ComPtr<I>> {
+        match &self.inner {
+            WeakAny::Native(weak) => weak.upgrade()?.query_interface::<I>().ok(),
+            WeakAny::SideTable(table) => {
+                let mut out = ptr::null_mut();
+                if table.Resolve(&I::GUID, &mut out).is_failure() {
+                    return None;
+                }
+                NonNull::new(out).map(|out| unsafe { ComPtr::new(out.cast()) })
+            }
+        }
+    }
+}
+
+impl<I: Interface + crate::impls::RefCount> Clone for ComWeakAny<I> {
+    fn clone(&self) -> Self {
+        let inner = match &self.inner {
+            WeakAny::Native(weak) => WeakAny::Native(weak.clone()),
+            WeakAny::SideTable(table) => WeakAny::SideTable(table.clone()),
+        };
+        Self {
+            inner,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<I: Interface + crate::impls::RefCount> Debug for ComWeakAny<I> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ComWeakAny")
+            .field("side_table", &self.is_side_table())
+            .finish()
+    }
+}
+
+impl<I: Interface + crate::impls::RefCount> ComPtr<I> {
+    pub fn downgrade_any(&self) -> Result<ComWeakAny<I>, HResult> {
+        ComWeakAny::new(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::weak_ref::impls;
+    use crate::{object::*, weak_ref::*, *};
+
+    #[object(IWeakReferenceSource)]
+    #[derive(Debug)]
+    pub struct Source {
+        weak: WeakRefSource,
+        a: u32,
+    }
+
+    impl impls::IWeakReferenceSource for Source {
+        fn GetWeakReference(&self, out: OutComPtr<IWeakReference>) -> HResult {
+            unsafe { self.weak.get_weak_reference(self, out) }
+        }
+    }
+
+    #[object(IWeak)]
+    #[derive(Debug)]
+    pub struct Native {}
+
+    impl impls::IWeak for Native {}
+
+    #[object(IUnknown)]
+    #[derive(Debug)]
+    pub struct Plain {}
+
+    #[test]
+    fn side_table() {
+        let a = Source {
+            weak: WeakRefSource::new(),
+            a: 1,
+        }
+        .make_com();
+        let w = a.downgrade_any().unwrap();
+        assert!(w.is_side_table());
+        let b = w.upgrade().unwrap();
+        assert_eq!(b.downcast::<Source>().unwrap().a, 1);
+        assert!(w.clone().upgrade().is_some());
+        drop((a, b));
+        assert!(w.upgrade().is_none());
+    }
+
+    #[test]
+    fn side_table_waits_for_unique_access() {
+        let mut a = Source {
+            weak: WeakRefSource::new(),
+            a: 1,
+        }
+        .make_object();
+        let w = a.clone().to_com().downgrade_any().unwrap();
+        let mut v = a.get_mut_unique().unwrap();
+        v.a = 2;
+        assert!(w.upgrade().is_none());
+        drop(v);
+        assert_eq!(w.upgrade().unwrap().downcast::<Source>().unwrap().a, 2);
+        assert_eq!(a.strong_count(), 1);
+    }
+
+    #[test]
+    fn side_table_outlived_by_unwrapped_value() {
+        let a = Source {
+            weak: WeakRefSource::new(),
+            a: 1,
+        }
+        .make_object();
+        let w = a.clone().to_com().downgrade_any().unwrap();
+        let v = a.try_unwrap().unwrap();
+        assert!(w.upgrade().is_none());
+        assert!(v.weak.table.lock().is_none());
+
+        let b = v.make_object();
+        assert!(w.upgrade().is_none());
+        let w = b.clone().to_com().downgrade_any().unwrap();
+        assert_eq!(w.upgrade().unwrap().downcast::<Source>().unwrap().a, 1);
+        assert_eq!(b.into_inner().unwrap().a, 1);
+        assert!(w.upgrade().is_none());
+    }
+
+    #[test]
+    fn native_and_unsupported() {
+        let a = Native {}.make_com_weak();
+        let w = a.downgrade_any().unwrap();
+        assert!(!w.is_side_table());
+        assert!(w.upgrade().is_some());
+        drop(a);
+        assert!(w.upgrade().is_none());
+
+        let p = Plain {}.make_com();
+        assert_eq!(
+            p.downgrade_any().unwrap_err(),
+            HResult::from(HResultE::NoInterface)
+        );
+    }
+}
@@ -0,0 +1,41 @@
+//! Component shared library loaded by `tests/dlopen.rs`, `tests/loader.rs` and
+//! `tests/error_info.rs`
+
+use cocom::{
+    async_op::{AsyncOperation, IAsyncOperation},
+    module::Class,
+    *,
+};
+
+#[object(IUnknown)]
+#[derive(Debug, Default)]
+pub struct Counter {
+    pub value: u32,
+}
+
+impl Class for Counter {
+    const CLSID: Guid = Guid::from_str("3f0c6a52-8d1e-4b7a-9c25-6e41d0b8a913").unwrap();
+}
+
+#[object(IUnknown)]
+#[derive(Debug, Default)]
+pub struct Greeter {
+    pub name: u32,
+}
+
+impl Class for Greeter {
+    const CLSID: Guid = Guid::from_str("3f0c6a52-8d1e-4b7a-9c25-6e41d0b8a914").unwrap();
+}
+
+cocom::export_module! {
+    classes: [Counter, Greeter],
+}
+
+/// An operation that failed inside the library, its error info is left by the library's thunks
+#[unsafe(no_mangle)]
+pub extern "C" fn fixture_failed_operation() -> *mut IAsyncOperation {
+    AsyncOperation::from_future(async {
+        Err::<(), _>(ComError::new(HResultE::Fail, "failed in the library"))
+    })
+    .leak()
+}
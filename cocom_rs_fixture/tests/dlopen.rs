@@ -0,0 +1,71 @@
+#![cfg(target_os = "linux")]
+
+use core::ffi::{c_char, c_int, c_void};
+use std::{ffi::CString, path::PathBuf, ptr};
+
+use cocom::{module::Class, types::B32, *};
+use cocom_fixture::{Counter, Greeter};
+
+const RTLD_NOW: c_int = 2;
+
+unsafe extern "C" {
+    fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlclose(handle: *mut c_void) -> c_int;
+}
+
+type GetClassObject = unsafe extern "C" fn(*const Guid, *const Guid, *mut *mut c_void) -> HResult;
+type CanUnloadNow = extern "C" fn() -> HResult;
+
+/// the cdylib next to this test binary, in `target/<profile>/deps`
+fn library() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    exe.parent().unwrap().join("libcocom_fixture.so")
+}
+
+unsafe fn symbol<T>(handle: *mut c_void, name: &str) -> T {
+    let name = CString::new(name).unwrap();
+    let sym = unsafe { dlsym(handle, name.as_ptr()) };
+    assert!(!sym.is_null(), "missing export");
+    unsafe { core::mem::transmute_copy(&sym) }
+}
+
+#[test]
+fn load_create_unload() {
+    let path = CString::new(library().into_os_string().into_encoded_bytes()).unwrap();
+    let handle = unsafe { dlopen(path.as_ptr(), RTLD_NOW) };
+    assert!(!handle.is_null(), "dlopen failed");
+    let get_class_object: GetClassObject = unsafe { symbol(handle, "DllGetClassObject") };
+    let can_unload_now: CanUnloadNow = unsafe { symbol(handle, "DllCanUnloadNow") };
+    assert_eq!(can_unload_now(), HResult::ok());
+
+    let mut out = ptr::null_mut();
+    let r = unsafe { get_class_object(&Counter::CLSID, &IClassFactory::GUID, &mut out) };
+    assert_eq!(r, HResult::ok());
+    let factory = unsafe { ComPtr::new_unchecked(out as *mut IClassFactory) };
+    assert_eq!(can_unload_now(), HResult::new(1));
+
+    let mut out = ptr::null_mut();
+    let r = factory.CreateInstance(ptr::null_mut(), &IUnknown::GUID, &mut out);
+    assert_eq!(r, HResult::ok());
+    let counter = unsafe { ComPtr::new_unchecked(out) };
+    assert_eq!(factory.LockServer(B32::TRUE), HResult::ok());
+    drop((factory, counter));
+    assert_eq!(can_unload_now(), HResult::new(1));
+
+    let mut out = ptr::null_mut();
+    let r = unsafe { get_class_object(&Greeter::CLSID, &IClassFactory::GUID, &mut out) };
+    assert_eq!(r, HResult::ok());
+    let factory = unsafe { ComPtr::new_unchecked(out as *mut IClassFactory) };
+    assert_eq!(factory.LockServer(B32::FALSE), HResult::ok());
+    drop(factory);
+    assert_eq!(can_unload_now(), HResult::ok());
+
+    let missing = Guid::from_str("3f0c6a52-8d1e-4b7a-9c25-6e41d0b8a915").unwrap();
+    let mut out = ptr::null_mut();
+    let r = unsafe { get_class_object(&missing, &IClassFactory::GUID, &mut out) };
+    assert_eq!(r, HResult::from(HResultE::ClassNotAvailable));
+    assert!(out.is_null());
+
+    assert_eq!(unsafe { dlclose(handle) }, 0);
+}
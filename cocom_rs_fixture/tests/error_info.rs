@@ -0,0 +1,52 @@
+#![cfg(target_os = "linux")]
+
+//! Apart from `loader.rs`, whose tests count the parked libraries
+
+use core::ffi::{c_char, c_int, c_void};
+use std::{
+    ffi::CString,
+    path::PathBuf,
+    ptr::{self, NonNull},
+};
+
+use cocom::{async_op::IAsyncOperation, loader::*, *};
+
+const RTLD_NOW: c_int = 2;
+
+unsafe extern "C" {
+    fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlclose(handle: *mut c_void) -> c_int;
+}
+
+type FailedOperation = extern "C" fn() -> *mut IAsyncOperation;
+
+fn library() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    exe.parent().unwrap().join("libcocom_fixture.so")
+}
+
+#[test]
+fn error_info_reaches_the_host() {
+    let module = Module::open(library()).unwrap();
+
+    // the same mapping as the module's, only to reach the fixture's own export
+    let path = CString::new(library().into_os_string().into_encoded_bytes()).unwrap();
+    let handle = unsafe { dlopen(path.as_ptr(), RTLD_NOW) };
+    assert!(!handle.is_null(), "dlopen failed");
+    let failed = unsafe { dlsym(handle, c"fixture_failed_operation".as_ptr()) };
+    assert!(!failed.is_null(), "missing export");
+    let failed = unsafe { core::mem::transmute::<*mut c_void, FailedOperation>(failed) };
+
+    let op = unsafe { ComPtr::new(NonNull::new(failed()).unwrap()) };
+    let mut out = ptr::null_mut();
+    let e = op.GetResult(&mut out).to_result().unwrap_err();
+    assert!(out.is_null());
+    assert_eq!(e.code(), HResultE::Fail.into());
+    assert_eq!(e.description(), "failed in the library");
+
+    drop(e);
+    drop(op);
+    unsafe { dlclose(handle) };
+    drop(module);
+}
@@ -0,0 +1,51 @@
+#![cfg(target_os = "linux")]
+
+use std::path::PathBuf;
+
+use cocom::{loader::*, module::Class, *};
+use cocom_fixture::{Counter, Greeter};
+
+fn library() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    exe.parent().unwrap().join("libcocom_fixture.so")
+}
+
+#[test]
+fn open_and_create() {
+    let module = Module::open(library()).unwrap();
+    assert!(module.can_unload_now());
+
+    let unknown = module
+        .get_class_object::<IUnknown>(&Counter::CLSID)
+        .unwrap();
+    let factory = module.class_factory(&Greeter::CLSID).unwrap();
+    let greeter = module.create_instance::<IUnknown>(&Greeter::CLSID).unwrap();
+    assert!(!module.can_unload_now());
+    assert_eq!(
+        module
+            .create_instance::<IWeak>(&Counter::CLSID)
+            .unwrap_err()
+            .code(),
+        HResult::from(HResultE::NoInterface)
+    );
+    let missing = Guid::from_str("3f0c6a52-8d1e-4b7a-9c25-6e41d0b8a915").unwrap();
+    assert_eq!(
+        module.class_factory(&missing).unwrap_err().code(),
+        HResult::from(HResultE::ClassNotAvailable)
+    );
+    drop((unknown, factory));
+
+    // the object keeps the library mapped after the last handle is gone
+    drop(module);
+    assert_eq!(free_unused_modules(), 0);
+    drop(greeter);
+    assert_eq!(free_unused_modules(), 1);
+}
+
+#[test]
+fn open_errors() {
+    assert!(matches!(
+        Module::open("/nonexistent/libnothing.so"),
+        Err(LoadError::Open(_))
+    ));
+}
@@ -0,0 +1,103 @@
+//! The `rust` output of `Tests/Test1/co_com.json`, compiled and called through its vtables
+
+#[rustfmt::skip]
+#[path = "../../Tests/Test1/rust/mod.rs"]
+mod test1;
+
+use core::{cell::Cell, ptr};
+
+use cocom::{object::*, *};
+use test1::{impls, *};
+
+#[object(ITest3, check_reentrancy)]
+#[derive(Debug)]
+struct Test {
+    foo: Cell<u32>,
+    value: Struct2<i32>,
+}
+
+impl impls::ITest1 for Test {
+    fn Add(&self, a: u32, b: u32) -> u32 {
+        a + b
+    }
+}
+
+impl impls::ITest2 for Test {
+    fn Sub(&self, a: u32, b: u32) -> u32 {
+        a - b
+    }
+
+    fn get_Foo(&self) -> u32 {
+        self.foo.get()
+    }
+
+    fn set_Foo(&self, value: u32) {
+        self.foo.set(value)
+    }
+
+    fn get_Foo2(&self) -> u32 {
+        self.foo.get() * 2
+    }
+
+    fn set_Foo3(&self, value: u32) {
+        self.foo.set(value * 3)
+    }
+
+    fn Some(&mut self) {
+        self.value.a += 1;
+    }
+}
+
+impl impls::ITest3 for Test {
+    fn Some1(&mut self, a: Struct1, b: Enum1, c: Enum2) -> *mut Struct2<i32> {
+        self.value.a = unsafe { a.a } + b.0 + c.bits();
+        &mut self.value
+    }
+
+    fn FnPtr(&mut self, r#fn: unsafe extern "C" fn(i32, i32) -> i32) {
+        self.value.a = unsafe { r#fn(self.value.a, 10) };
+    }
+}
+
+unsafe extern "C" fn mul(a: i32, b: i32) -> i32 {
+    a * b
+}
+
+#[test]
+fn calls_through_generated_thunks() {
+    let t = Test {
+        foo: Cell::new(0),
+        value: Struct2 { a: 0 },
+    }
+    .make_com();
+    assert_eq!(t.Add(2, 3), 5);
+    assert_eq!(t.Sub(5, 3), 2);
+    t.set_Foo(4);
+    assert_eq!(t.get_Foo(), 4);
+    t.set_Foo3(2);
+    assert_eq!(t.get_Foo2(), 12);
+
+    let out = t.Some1(Struct1 { a: 1 }, Enum1::C, Enum2::A | Enum2::C);
+    assert_eq!(unsafe { (*out).a }, 8);
+    t.Some();
+    t.FnPtr(mul);
+    assert_eq!(unsafe { (*out).a }, 90);
+
+    let t1 = t.query_interface::<ITest1>().unwrap();
+    assert_eq!(t1.const_ptr() as *const (), t.const_ptr() as *const ());
+    assert!(t1.query_interface::<ITest2>().is_ok());
+    assert!(ptr::eq(
+        t.query_interface::<ITest3>().unwrap().const_ptr() as *const (),
+        t.const_ptr() as *const ()
+    ));
+}
+
+#[test]
+fn generated_types() {
+    assert_eq!(Enum1::B.to_string(), "B");
+    assert_eq!(Enum1(7).to_string(), "7");
+    assert!((Enum2::A | Enum2::B).has_flags(Enum2::B));
+    assert!(!Enum2::A.has_any_flags(Enum2::C));
+    assert_eq!(i32::from(Enum2::from(6)), 6);
+    assert_eq!(format!("{:?}", Struct1 { a: 1 }), "Struct1 { .. }");
+}
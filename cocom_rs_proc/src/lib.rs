@@ -10,6 +10,8 @@ use syn::{
 struct InterfaceAttr {
     guid_lit: LitStr,
     guid: uuid::Uuid,
+    /// emit `cocom::dispatch` metadata
+    dispatch: bool,
 }
 
 impl Parse for InterfaceAttr {
@@ -17,7 +19,22 @@ impl Parse for InterfaceAttr {
         let guid_lit: LitStr = input.parse()?;
         let guid = uuid::Uuid::parse_str(&guid_lit.value())
             .map_err(|e| syn::Error::new(guid_lit.span(), e.to_string()))?;
-        Ok(Self { guid_lit, guid })
+        let mut dispatch = false;
+        while input.parse::<Token![,]>().is_ok() {
+            if input.is_empty() {
+                break;
+            }
+            let flag: Ident = input.parse()?;
+            if flag != "dispatch" || dispatch {
+                return Err(syn::Error::new(flag.span(), "expected `dispatch`"));
+            }
+            dispatch = true;
+        }
+        Ok(Self {
+            guid_lit,
+            guid,
+            dispatch,
+        })
     }
 }
 
@@ -77,6 +94,7 @@ pub fn interface(attr: TokenStream, item: TokenStream) -> TokenStream {
     let vis = &item.vis;
     let name = &item.ident;
     let parent = &item.parents.first();
+    #[allow(clippy::cmp_owned)]
     let has_weak = item.parents.iter().any(|a| a.to_string() == "IWeak");
     let guid = attr.guid_lit.value();
     let name_str = name.to_string();
@@ -84,28 +102,108 @@ pub fn interface(attr: TokenStream, item: TokenStream) -> TokenStream {
     let methods = item.items.iter().map(|item| {
         let attrs = &item.attrs;
         let ident = &item.sig.ident;
-        let inputs = &item.sig.inputs;
-        let params = inputs.iter().skip(1);
-        let args = inputs.iter().skip(1).map(|arg| match arg {
-            syn::FnArg::Typed(t) => {
-                let pat = &t.pat;
-                quote! {#pat}
-            }
-            _ => quote! {},
-        });
+        let (generics, params, args) = facade_params(item);
         let f_name = format_ident!("f_{}", ident);
         let ret = &item.sig.output;
+        if item.sig.asyncness.is_some() {
+            let ret_ty = return_type(item);
+            return quote! {
+                #(#attrs)*
+                pub fn #ident #generics(&self, #(#params),*) -> cocom::async_op::AsyncCall<#ret_ty> {
+                    let mut op = core::ptr::null_mut();
+                    unsafe {
+                        let hr = ((*self.v_ptr()).#f_name)(self as _, #(#args,)* &mut op);
+                        cocom::async_op::AsyncCall::from_raw(hr, op)
+                    }
+                }
+            };
+        }
         quote! {
             #(#attrs)*
-            pub fn #ident(&self, #(#params),*) #ret {
+            pub fn #ident #generics(&self, #(#params),*) #ret {
                 unsafe { ((*self.v_ptr()).#f_name)(self as _, #(#args),*) }
             }
         }
     });
+    let thread_fns = item.items.iter().map(|item| {
+        let ident = &item.sig.ident;
+        let args = call_args(item);
+        let f_name = format_ident!("f_{}", ident);
+        let t_name = format_ident!("thread_{}", ident);
+        let (params, ret) = abi(item);
+        let (call, ret_ty) = if item.sig.asyncness.is_some() {
+            let call = quote! {
+                move |this: &#name| unsafe {
+                    ((*this.v_ptr()).#f_name)(this as _, #(#args,)* __async_op)
+                }
+            };
+            (call, quote! { cocom::HResult })
+        } else {
+            let ret_ty = return_type(item);
+            (
+                quote! { move |this: &#name| this.#ident(#(#args),*) },
+                ret_ty,
+            )
+        };
+        quote! {
+            unsafe extern "C" fn #t_name(this: *const #name, #params) #ret {
+                #[allow(unused_imports)]
+                use cocom::thread::__private::{Failed, ViaAbort, ViaReport};
+                let call = #call;
+                match unsafe { cocom::thread::ThreadProxy::call(this as _, call) } {
+                    Ok(r) => r,
+                    Err(hr) => (&&Failed::<#ret_ty>::new(hr)).failed(),
+                }
+            }
+        }
+    });
+    let thread_slots = item.items.iter().map(|item| {
+        let f_name = format_ident!("f_{}", item.sig.ident);
+        let (params, ret) = abi(item);
+        quote! {
+            #f_name: unsafe extern "C" fn(this: *const #name, #params) #ret
+        }
+    });
+    let thread_inits = item.items.iter().map(|item| {
+        let f_name = format_ident!("f_{}", item.sig.ident);
+        let t_name = format_ident!("thread_{}", item.sig.ident);
+        quote! { #f_name: #t_name }
+    });
+    let thread = quote! {
+        cocom::__std! {
+            const _: () = {
+                /// same layout as the vtable, whose parent field is private to `details`
+                #[repr(C)]
+                struct ThreadVtbl {
+                    b: <#parent as cocom::Interface>::VitualTable,
+                    #(#thread_slots,)*
+                }
+
+                #(#thread_fns)*
+
+                unsafe impl cocom::thread::ThreadInterface for #name {
+                    const THREAD_VTBL: details::#vtbl_name = unsafe {
+                        core::mem::transmute::<ThreadVtbl, details::#vtbl_name>(ThreadVtbl {
+                            b: <#parent as cocom::thread::ThreadInterface>::THREAD_VTBL,
+                            #(#thread_inits,)*
+                        })
+                    };
+                    const THREAD_VTBL_REF: &'static details::#vtbl_name = &Self::THREAD_VTBL;
+
+                    fn implements(iid: &Guid) -> bool {
+                        *iid == <Self as cocom::Interface>::GUID
+                            || <#parent as cocom::thread::ThreadInterface>::implements(iid)
+                    }
+                }
+            };
+        }
+    };
+    let dispatch = attr.dispatch.then(|| dispatch(&item));
     let weak = if !has_weak {
         quote! {}
     } else {
         quote! {
+            #[allow(clippy::not_unsafe_ptr_arg_deref)]
             impl impls::WeakRefCount for #name {
                 fn AddRefWeak(this: *const Self) -> u32 {
                     IWeak::AddRefWeak(unsafe { &*this })
@@ -172,6 +270,7 @@ pub fn interface(attr: TokenStream, item: TokenStream) -> TokenStream {
             }
         }
 
+        #[allow(clippy::not_unsafe_ptr_arg_deref)]
         impl impls::RefCount for #name {
             fn AddRef(this: *const Self) -> u32 {
                 IUnknown::AddRef(unsafe { &*this })
@@ -184,28 +283,282 @@ pub fn interface(attr: TokenStream, item: TokenStream) -> TokenStream {
 
         #weak
 
+        // the facades pass raw pointer arguments through to the vtable
+        #[allow(clippy::not_unsafe_ptr_arg_deref)]
         impl #name {
             #(#methods)*
         }
+
+        #thread
+
+        #dispatch
     }
     .into()
 }
 
+/// `cocom::dispatch::Dispatchable` for `#[interface(.., dispatch)]`
+fn dispatch(item: &ItemInterface) -> proc_macro2::TokenStream {
+    let name = &item.ident;
+    let name_str = name.to_string();
+    let parent = &item.parents.first();
+    let typed = |item: &TraitItemFn| -> Vec<syn::PatType> {
+        item.sig
+            .inputs
+            .iter()
+            .filter_map(|arg| match arg {
+                syn::FnArg::Typed(t) => Some(t.clone()),
+                _ => None,
+            })
+            .collect()
+    };
+    let methods = item.items.iter().enumerate().map(|(i, item)| {
+        let method_str = item.sig.ident.to_string();
+        let slot = i as u32;
+        let params = typed(item).into_iter().map(|t| {
+            let pat = &t.pat;
+            let param_str = quote! {#pat}.to_string();
+            let ty = &t.ty;
+            quote! {
+                cocom::dispatch::ParamInfo {
+                    name: #param_str,
+                    kind: <#ty as cocom::dispatch::DispatchType>::KIND,
+                }
+            }
+        });
+        let ret = if item.sig.asyncness.is_some() {
+            quote! { cocom::dispatch::TypeKind::Interface }
+        } else {
+            let ret_ty = return_type(item);
+            quote! { <#ret_ty as cocom::dispatch::DispatchType>::KIND }
+        };
+        quote! {
+            cocom::dispatch::MethodInfo {
+                name: #method_str,
+                slot: BASE + #slot,
+                params: &[#(#params),*],
+                ret: #ret,
+            }
+        }
+    });
+    let arms = item.items.iter().enumerate().map(|(i, item)| {
+        let ident = &item.sig.ident;
+        let params = typed(item);
+        let names = (0..params.len())
+            .map(|n| format_ident!("__arg{}", n))
+            .collect::<Vec<_>>();
+        let args = params.iter().zip(&names).map(|(t, n)| {
+            let ty = &t.ty;
+            quote! { <#ty as cocom::dispatch::DispatchType>::from_value(#n)? }
+        });
+        let call = if item.sig.asyncness.is_some() {
+            quote! { this.#ident(#(#args),*).into_operation()? }
+        } else {
+            quote! { this.#ident(#(#args),*) }
+        };
+        quote! {
+            #i => {
+                let [#(#names),*] = args else {
+                    return Err(cocom::HResultE::BadParamCount.into());
+                };
+                cocom::dispatch::DispatchType::into_value(#call)
+            }
+        }
+    });
+    quote! {
+        impl cocom::dispatch::Dispatchable for #name {
+            const INFO: &'static cocom::dispatch::InterfaceInfo = {
+                const BASE: u32 = (core::mem::size_of::<<#parent as cocom::Interface>::VitualTable>()
+                    / core::mem::size_of::<usize>()) as u32;
+
+                #[allow(unused_variables)]
+                fn invoke(
+                    this: *const core::ffi::c_void,
+                    index: usize,
+                    args: &[cocom::dispatch::ComValue],
+                ) -> cocom::ComResult<cocom::dispatch::ComValue> {
+                    let this = unsafe { &*(this as *const #name) };
+                    match index {
+                        #(#arms)*
+                        _ => Err(cocom::HResultE::MemberNotFound.into()),
+                    }
+                }
+
+                &cocom::dispatch::InterfaceInfo {
+                    name: #name_str,
+                    iid: <#name as cocom::Interface>::GUID,
+                    parent: Some(<#parent as cocom::dispatch::Dispatchable>::INFO),
+                    methods: &[#(#methods),*],
+                    invoke,
+                }
+            };
+        }
+    }
+}
+
+/// the pattern of every parameter after `self`
+fn call_args(item: &TraitItemFn) -> Vec<proc_macro2::TokenStream> {
+    item.sig
+        .inputs
+        .iter()
+        .skip(1)
+        .map(|arg| match arg {
+            syn::FnArg::Typed(t) => {
+                let pat = &t.pat;
+                quote! {#pat}
+            }
+            _ => quote! {},
+        })
+        .collect()
+}
+
+/// borrowed types the facades take as anything convertible, e.g. `&str` for `Str8` or
+/// `&ComPtr<I>` for `ComRef<I>`
+const BORROWED_ABI_TYPES: [&str; 6] = ["NSpan", "NRoSpan", "Str8", "Str16", "StrAny", "ComRef"];
+
+/// generics, parameters after `self` and the arguments passed on to the slot of a facade
+/// method; borrowed types missing their lifetime get one lifetime shared by the call
+fn facade_params(
+    item: &TraitItemFn,
+) -> (
+    proc_macro2::TokenStream,
+    Vec<proc_macro2::TokenStream>,
+    Vec<proc_macro2::TokenStream>,
+) {
+    let lifetime = syn::Lifetime::new("'__cocom", proc_macro2::Span::call_site());
+    let mut uses_lifetime = false;
+    let (params, args) = item
+        .sig
+        .inputs
+        .iter()
+        .skip(1)
+        .map(|arg| match arg {
+            syn::FnArg::Typed(t) => {
+                let pat = &t.pat;
+                let mut ty = (*t.ty).clone();
+                let Type::Path(path) = &mut ty else {
+                    return (quote! { #t }, quote! { #pat });
+                };
+                let Some(last) = path.path.segments.last_mut() else {
+                    return (quote! { #t }, quote! { #pat });
+                };
+                if !BORROWED_ABI_TYPES.iter().any(|name| last.ident == name) {
+                    return (quote! { #t }, quote! { #pat });
+                }
+                match &mut last.arguments {
+                    syn::PathArguments::None => {
+                        last.arguments =
+                            syn::PathArguments::AngleBracketed(syn::parse_quote! { <#lifetime> });
+                        uses_lifetime = true;
+                    }
+                    syn::PathArguments::AngleBracketed(generics) => {
+                        let has_lifetime = generics
+                            .args
+                            .iter()
+                            .any(|arg| matches!(arg, syn::GenericArgument::Lifetime(_)));
+                        if !has_lifetime {
+                            generics
+                                .args
+                                .insert(0, syn::GenericArgument::Lifetime(lifetime.clone()));
+                            uses_lifetime = true;
+                        }
+                    }
+                    syn::PathArguments::Parenthesized(_) => {}
+                }
+                (quote! { #pat: impl Into<#ty> }, quote! { #pat.into() })
+            }
+            _ => (quote! { #arg }, quote! {}),
+        })
+        .unzip();
+    let mut generics = item
+        .sig
+        .generics
+        .params
+        .iter()
+        .map(|p| quote! { #p })
+        .collect::<Vec<_>>();
+    if uses_lifetime {
+        generics.insert(0, quote! { #lifetime });
+    }
+    let generics = if generics.is_empty() {
+        quote! {}
+    } else {
+        quote! { <#(#generics),*> }
+    };
+    (generics, params, args)
+}
+
+fn return_type(item: &TraitItemFn) -> proc_macro2::TokenStream {
+    match &item.sig.output {
+        syn::ReturnType::Default => quote! { () },
+        syn::ReturnType::Type(_, ty) => quote! { #ty },
+    }
+}
+
+/// parameters after `this` and return type of the vtable slot, `async fn` writes out an
+/// `IAsyncOperation` and returns `HResult`
+fn abi(item: &TraitItemFn) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let params = item.sig.inputs.iter().skip(1);
+    if item.sig.asyncness.is_some() {
+        (
+            quote! { #(#params,)* __async_op: *mut *mut cocom::async_op::IAsyncOperation },
+            quote! { -> cocom::HResult },
+        )
+    } else {
+        let ret = &item.sig.output;
+        (quote! { #(#params),* }, quote! { #ret })
+    }
+}
+
 struct ObjectAttr {
     parent: Type,
     allocator: Option<Type>,
+    check_reentrancy: bool,
+    /// lifecycle hooks forwarded to inherent fns named `on_<hook>`
+    hooks: Vec<Ident>,
 }
 
+const HOOKS: [&str; 3] = ["final_construct", "final_construct_weak", "final_release"];
+
 impl Parse for ObjectAttr {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
         let parent: Type = input.parse()?;
-        let allocator = if let Ok(_) = input.parse::<Token![,]>() {
-            Some(input.parse()?)
-        } else {
-            None
-        };
+        let mut allocator = None;
+        let mut check_reentrancy = false;
+        let mut hooks: Vec<Ident> = Vec::new();
+        while input.parse::<Token![,]>().is_ok() {
+            if input.is_empty() {
+                break;
+            }
+            let fork = input.fork();
+            if let Ok(flag) = fork.parse::<Ident>()
+                && (fork.is_empty() || fork.peek(Token![,]))
+            {
+                if flag == "check_reentrancy" {
+                    input.parse::<Ident>()?;
+                    check_reentrancy = true;
+                    continue;
+                }
+                if HOOKS.iter().any(|h| flag == h) {
+                    input.parse::<Ident>()?;
+                    if hooks.contains(&flag) {
+                        return Err(syn::Error::new(flag.span(), "duplicate hook"));
+                    }
+                    hooks.push(flag);
+                    continue;
+                }
+            }
+            if allocator.is_some() {
+                return Err(input.error("duplicate allocator"));
+            }
+            allocator = Some(input.parse()?);
+        }
 
-        Ok(Self { parent, allocator })
+        Ok(Self {
+            parent,
+            allocator,
+            check_reentrancy,
+            hooks,
+        })
     }
 }
 
@@ -247,19 +600,72 @@ pub fn object(attr: TokenStream, item: TokenStream) -> TokenStream {
     let parent = &attr.parent;
     let allocator = &attr.allocator;
     let ident = &item.ident;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
     let allocator = allocator
         .as_ref()
         .map(|allocator| quote! { #allocator })
         .unwrap_or_else(|| quote! { () });
+    let check_reentrancy = attr
+        .check_reentrancy
+        .then(|| quote! { const CHECK_REENTRANCY: bool = true; });
+    // a distinct name, so a missing inherent fn is an error instead of the trait method calling itself
+    let hooks = attr.hooks.iter().map(|hook| {
+        let target = format_ident!("on_{}", hook);
+        if hook == "final_release" {
+            quote! {
+                fn final_release(&mut self) {
+                    #ident::#target(self)
+                }
+            }
+        } else if hook == "final_construct" {
+            quote! {
+                fn final_construct(this: &cocom::object::ObjectPtr<Self>) {
+                    #ident::#target(this)
+                }
+            }
+        } else {
+            quote! {
+                fn final_construct_weak(this: &cocom::object::WeakObjectPtr<Self>) {
+                    #ident::#target(this)
+                }
+            }
+        }
+    });
+    let fields = item.fields.members().map(|member| {
+        quote! {
+            (&&TraceField(&self.#member)).trace_field(visitor);
+        }
+    });
+    let detach = item.fields.members().map(|member| {
+        quote! {
+            (&&DetachField(&self.#member)).detach_field();
+        }
+    });
     quote! {
         #item
 
-        impl impls::Object for #ident {
+        impl #impl_generics impls::Object for #ident #ty_generics #where_clause {
             type Interface = #parent;
             type Allocator = #allocator;
+            #check_reentrancy
+            #(#hooks)*
+
+            cocom::__trace! {
+                fn trace(&self, visitor: &mut dyn cocom::visit::Visitor) {
+                    #[allow(unused_imports)]
+                    use cocom::visit::__private::{TraceField, ViaNothing, ViaTrace};
+                    #(#fields)*
+                }
+            }
+
+            fn detach(&mut self) {
+                #[allow(unused_imports)]
+                use cocom::weak_ref::__private::{DetachField, ViaDetach, ViaNothing};
+                #(#detach)*
+            }
         }
 
-        impl impls::IUnknown for #ident {}
+        impl #impl_generics impls::IUnknown for #ident #ty_generics #where_clause {}
     }
     .into()
 }